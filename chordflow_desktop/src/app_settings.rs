@@ -0,0 +1,425 @@
+use chordflow_music_theory::{note::generate_all_roots, quality::Quality};
+use serde::{Deserialize, Serialize};
+use strum::EnumCount;
+use toml::Table;
+
+use crate::{
+    audio::{
+        articulation::ChordArticulation,
+        bass::BassLine,
+        comping::CompingPattern,
+        drums::DrumGroove,
+        gap::GapClick,
+        settings::{AudioSettings, AUDIO_SETTINGS},
+        tempo::TempoRamp,
+    },
+    config,
+    import::to_progression,
+    library::LibrarySong,
+    state::{fourths::FourthsConfig, modes::ModeOption, progression::ProgressionChord, song::Song},
+    ui::{
+        app::{AppState, MetronomeState},
+        top_zone::{subdivision_selector::Subdivision, time_signature_selector::BEATS_PER_BAR},
+    },
+    MAX_BPM, MIN_BPM,
+};
+
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// Steps bringing a settings file of version `i + 1` to the next version. A change that
+/// older files cannot be read with adds a step here, which bumps [`VERSION`].
+const MIGRATIONS: &[fn(&mut Table)] = &[];
+/// Version of the settings files this build writes
+pub const VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+/// Volumes in percent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Volumes {
+    pub metronome_accent: u8,
+    pub metronome_beat: u8,
+    pub metronome_subdivision: u8,
+    pub chord: u8,
+    pub bass: u8,
+    pub drums: u8,
+}
+
+impl Volumes {
+    fn of(settings: &AudioSettings) -> Self {
+        let percent = |volume: f32| (volume * 100.0).round() as u8;
+        Self {
+            metronome_accent: percent(settings.get_metronome_accent_volume()),
+            metronome_beat: percent(settings.get_metronome_beat_volume()),
+            metronome_subdivision: percent(settings.get_metronome_subdivision_volume()),
+            chord: percent(settings.get_chord_volume()),
+            bass: percent(settings.get_bass_volume()),
+            drums: percent(settings.get_drum_volume()),
+        }
+    }
+
+    /// The volumes the audio thread is playing at
+    pub fn current() -> Self {
+        Self::of(&AUDIO_SETTINGS)
+    }
+
+    pub fn apply(&self) {
+        let volume = |percent: u8| percent as f32 / 100.0;
+        AUDIO_SETTINGS.set_metronome_accent_volume(volume(self.metronome_accent));
+        AUDIO_SETTINGS.set_metronome_beat_volume(volume(self.metronome_beat));
+        AUDIO_SETTINGS.set_metronome_subdivision_volume(volume(self.metronome_subdivision));
+        AUDIO_SETTINGS.set_chord_volume(volume(self.chord));
+        AUDIO_SETTINGS.set_bass_volume(volume(self.bass));
+        AUDIO_SETTINGS.set_drum_volume(volume(self.drums));
+    }
+}
+
+impl Default for Volumes {
+    fn default() -> Self {
+        Self::of(&AudioSettings::default())
+    }
+}
+
+/// Inner size of the window in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowSize {
+    /// Smallest size the window opens at, so a broken settings file cannot hide it
+    const MIN: WindowSize = WindowSize {
+        width: 480,
+        height: 400,
+    };
+
+    /// This size, at least [`WindowSize::MIN`]
+    pub fn at_least_min(self) -> Self {
+        Self {
+            width: self.width.max(Self::MIN.width),
+            height: self.height.max(Self::MIN.height),
+        }
+    }
+}
+
+impl Default for WindowSize {
+    fn default() -> Self {
+        Self {
+            width: 1000,
+            height: 910,
+        }
+    }
+}
+
+/// Everything that is set up in the app, saved whenever it changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub version: u32,
+    pub bpm: u16,
+    pub beats_per_bar: u8,
+    pub subdivision: Subdivision,
+    pub count_in: bool,
+    pub swing_percent: u8,
+    pub mode: ModeOption,
+    /// Quality of the circle of fourths, by name
+    pub fourths_quality: String,
+    /// Root of the diatonic progression, like `B♭`
+    pub diatonic_root: String,
+    pub diatonic_random: bool,
+    /// Bars per chord of the generated modes
+    pub bars_per_chord: u8,
+    /// Comping pattern per mode
+    pub comping: [CompingPattern; ModeOption::COUNT],
+    pub tempo_ramp_enabled: bool,
+    pub tempo_ramp: TempoRamp,
+    pub gap_click_enabled: bool,
+    pub gap_click: GapClick,
+    pub articulation: ChordArticulation,
+    pub bass_enabled: bool,
+    pub bass: BassLine,
+    pub drums_enabled: bool,
+    pub drums: DrumGroove,
+    pub volumes: Volumes,
+    pub window: WindowSize,
+    /// The custom progression that was practised last
+    pub progression: Option<LibrarySong>,
+}
+
+/// Bring `table` from the version it was saved with to the one after the last of
+/// `migrations`. A table of a newer version keeps it, so it is not saved over as this one.
+fn migrate(table: &mut Table, migrations: &[fn(&mut Table)]) {
+    let latest = migrations.len() as u32 + 1;
+    let version = table
+        .get("version")
+        .and_then(|v| v.as_integer())
+        .unwrap_or(1)
+        .max(1) as u32;
+    if version > latest {
+        log::warn!(
+            "Settings were saved by a newer version ({}), reading what is known",
+            version
+        );
+        return;
+    }
+    for step in migrations.iter().skip(version as usize - 1) {
+        step(table);
+    }
+    table.insert("version".to_string(), latest.into());
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self::capture(
+            &AppState::default(),
+            &MetronomeState::default(),
+            Volumes::default(),
+            WindowSize::default(),
+        )
+    }
+}
+
+/// The progression of the last capture, written out again only once the chords, their
+/// song, the tempo or the meter changed
+#[derive(Debug, Default)]
+pub struct ProgressionCache {
+    chords: Vec<ProgressionChord>,
+    song: Option<Song>,
+    loop_section: Option<String>,
+    bpm: u16,
+    beats_per_bar: u8,
+    saved: Option<LibrarySong>,
+}
+
+impl ProgressionCache {
+    fn get(&mut self, app: &AppState, metronome: &MetronomeState) -> Option<LibrarySong> {
+        let config = &app.progression_config;
+        let unchanged = self.chords == config.chords
+            && self.song == config.song
+            && self.loop_section == config.loop_section
+            && self.bpm == metronome.bpm
+            && self.beats_per_bar == app.beats_per_bar;
+        if !unchanged {
+            let saved = (!config.chords.is_empty()).then(|| {
+                let song = match config.unedited_song() {
+                    Some(song) => song.clone(),
+                    None => Song::from_progression(&config.chords, app.beats_per_bar),
+                };
+                LibrarySong::new(&song.title, &song, metronome.bpm, app.beats_per_bar)
+            });
+            *self = Self {
+                chords: config.chords.clone(),
+                song: config.song.clone(),
+                loop_section: config.loop_section.clone(),
+                bpm: metronome.bpm,
+                beats_per_bar: app.beats_per_bar,
+                saved,
+            };
+        }
+        self.saved.clone()
+    }
+}
+
+impl AppSettings {
+    /// The settings as they are in the app
+    pub fn capture(
+        app: &AppState,
+        metronome: &MetronomeState,
+        volumes: Volumes,
+        window: WindowSize,
+    ) -> Self {
+        Self::capture_cached(
+            app,
+            metronome,
+            volumes,
+            window,
+            &mut ProgressionCache::default(),
+        )
+    }
+
+    /// The settings as they are in the app, with the progression taken from `cache`
+    /// while it is unchanged
+    pub fn capture_cached(
+        app: &AppState,
+        metronome: &MetronomeState,
+        volumes: Volumes,
+        window: WindowSize,
+        cache: &mut ProgressionCache,
+    ) -> Self {
+        let progression = cache.get(app, metronome);
+        Self {
+            version: VERSION,
+            bpm: metronome.bpm,
+            beats_per_bar: metronome.ticks_per_bar,
+            subdivision: metronome.subdivision,
+            count_in: metronome.count_in_enabled,
+            swing_percent: metronome.swing_percent,
+            mode: app.selected_mode,
+            fourths_quality: app.fourths_config.quality.name(),
+            diatonic_root: app.diatonic_config.scale.root.to_string(),
+            diatonic_random: app.diatonic_config.is_random,
+            bars_per_chord: app.bars_per_chord,
+            comping: app.comping,
+            tempo_ramp_enabled: metronome.tempo_ramp_enabled,
+            tempo_ramp: metronome.tempo_ramp,
+            gap_click_enabled: metronome.gap_click_enabled,
+            gap_click: metronome.gap_click,
+            articulation: metronome.articulation,
+            bass_enabled: metronome.bass_enabled,
+            bass: metronome.bass,
+            drums_enabled: metronome.drums_enabled,
+            drums: metronome.drums,
+            volumes,
+            window,
+            progression,
+        }
+    }
+
+    /// Set the app up as saved. The volumes go to the audio thread right away; the
+    /// metronome settings are sent by the caller. Without a saved progression the
+    /// current one is kept.
+    pub fn apply(&self, app: &mut AppState, metronome: &mut MetronomeState) {
+        app.preset = None;
+        metronome.bpm = self.bpm.clamp(MIN_BPM, MAX_BPM);
+        if BEATS_PER_BAR.contains(&self.beats_per_bar) {
+            metronome.ticks_per_bar = self.beats_per_bar;
+            app.beats_per_bar = self.beats_per_bar;
+        }
+        metronome.subdivision = self.subdivision;
+        metronome.count_in_enabled = self.count_in;
+        metronome.swing_percent = self.swing_percent;
+        metronome.tempo_ramp_enabled = self.tempo_ramp_enabled;
+        metronome.tempo_ramp = self.tempo_ramp;
+        metronome.gap_click_enabled = self.gap_click_enabled;
+        metronome.gap_click = self.gap_click;
+        metronome.articulation = self.articulation;
+        metronome.bass_enabled = self.bass_enabled;
+        metronome.bass = self.bass;
+        metronome.drums_enabled = self.drums_enabled;
+        metronome.drums = self.drums;
+
+        app.selected_mode = self.mode;
+        app.fourths_config = FourthsConfig::new(Quality::from_name(&self.fourths_quality));
+        if let Some(root) = generate_all_roots()
+            .into_iter()
+            .find(|root| root.to_string() == self.diatonic_root)
+        {
+            app.diatonic_config.set_root(root);
+        }
+        app.diatonic_config.is_random = self.diatonic_random;
+        app.bars_per_chord = self.bars_per_chord.max(1);
+        app.comping = self.comping;
+        if let Some(saved) = &self.progression {
+            match saved
+                .song()
+                .and_then(|song| Ok((to_progression(&song.chords())?, song)))
+            {
+                Ok((chords, song)) => {
+                    app.progression_config.chords = chords;
+                    app.progression_config.song = Some(song);
+                    app.progression_config.loop_section = None;
+                }
+                Err(e) => log::warn!("Not restoring the last progression: {:#}", e),
+            }
+        }
+        self.volumes.apply();
+    }
+
+    /// The saved settings, or the defaults when there are none or they are unreadable
+    pub fn load() -> Self {
+        let table: Table = config::load(SETTINGS_FILE);
+        if table.is_empty() {
+            return Self::default();
+        }
+        Self::from_table(table)
+    }
+
+    /// Read settings of any version, settings that cannot be read taking their defaults
+    fn from_table(mut table: Table) -> Self {
+        migrate(&mut table, MIGRATIONS);
+        let defaults = Self::default();
+        // A setting of the wrong type is dropped rather than losing all of them
+        let mut settings = Table::new();
+        for (key, value) in table {
+            let mut single = Table::new();
+            single.insert(key.clone(), value.clone());
+            match single.try_into::<Self>() {
+                Ok(_) => {
+                    settings.insert(key, value);
+                }
+                Err(e) => log::warn!("Ignoring the setting {}: {}", key, e),
+            }
+        }
+        settings.try_into().unwrap_or(defaults)
+    }
+
+    /// Whether these were saved by a newer version, which this one must not overwrite
+    /// before something is changed
+    pub fn is_newer(&self) -> bool {
+        self.version > VERSION
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = config::save(SETTINGS_FILE, self) {
+            log::warn!("Could not save the settings: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_file() {
+        let settings = AppSettings {
+            bpm: 140,
+            mode: ModeOption::Diatonic,
+            diatonic_root: "E♭".to_string(),
+            ..AppSettings::default()
+        };
+        let text = toml::to_string(&settings).unwrap();
+        assert_eq!(AppSettings::from_table(text.parse().unwrap()), settings);
+
+        // Unknown and broken settings are dropped, the others kept
+        let table: Table = "version = 99\nbpm = 90\nswing_percent = \"lots\"\nsomething_new = 1"
+            .parse()
+            .unwrap();
+        let settings = AppSettings::from_table(table);
+        assert_eq!(settings.bpm, 90);
+        assert_eq!(settings.swing_percent, AppSettings::default().swing_percent);
+        // A newer version is kept, so its file is not saved over
+        assert!(settings.is_newer());
+    }
+
+    #[test]
+    fn test_migrations_from_the_saved_version() {
+        fn rename(table: &mut Table, from: &str, to: &str) {
+            if let Some(value) = table.remove(from) {
+                table.insert(to.to_string(), value);
+            }
+        }
+        let migrations: &[fn(&mut Table)] = &[
+            |table| rename(table, "tempo", "bpm"),
+            |table| rename(table, "swing", "swing_percent"),
+        ];
+
+        let mut first: Table = "tempo = 90\nswing = 60".parse().unwrap();
+        migrate(&mut first, migrations);
+        assert_eq!(
+            first,
+            "bpm = 90\nswing_percent = 60\nversion = 3".parse().unwrap()
+        );
+
+        // A file of version 2 already has `tempo` renamed, only the later step runs
+        let mut second: Table = "version = 2\ntempo = 90\nswing = 60".parse().unwrap();
+        migrate(&mut second, migrations);
+        assert_eq!(
+            second,
+            "tempo = 90\nswing_percent = 60\nversion = 3"
+                .parse()
+                .unwrap()
+        );
+    }
+}
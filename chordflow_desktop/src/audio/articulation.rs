@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+/// How the chord of the current timeline entry is struck
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, Display, Serialize, Deserialize)]
+pub enum ArticulationStyle {
+    /// One hit held for the whole chord duration
+    #[default]
+    Sustain,
+    /// A hit on every downbeat, held until the next one
+    #[strum(to_string = "Every bar")]
+    EveryBar,
+    /// Short hits on the selected beats
+    Staccato,
+}
+
+/// Chord articulation. Whatever is still sounding is always released at the next chord change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChordArticulation {
+    pub style: ArticulationStyle,
+    /// Beats hit in staccato style, bit 0 is the first beat
+    pub staccato_beats: u16,
+    /// Length of a staccato hit in percent of a beat
+    pub gate_percent: u8,
+}
+
+impl Default for ChordArticulation {
+    fn default() -> Self {
+        Self {
+            style: ArticulationStyle::Sustain,
+            staccato_beats: 0b0101,
+            gate_percent: 30,
+        }
+    }
+}
+
+impl ChordArticulation {
+    pub fn is_beat_selected(&self, beat: u8) -> bool {
+        beat < 16 && self.staccato_beats & (1 << beat) != 0
+    }
+
+    pub fn toggle_beat(&mut self, beat: u8) {
+        if beat < 16 {
+            self.staccato_beats ^= 1 << beat;
+        }
+    }
+
+    /// Whether the chord is struck on this beat.
+    /// `chord_start` is set on the downbeat the chord starts on.
+    pub fn strikes(&self, beat: u8, chord_start: bool) -> bool {
+        match self.style {
+            ArticulationStyle::Sustain => chord_start,
+            ArticulationStyle::EveryBar => beat == 0,
+            ArticulationStyle::Staccato => self.is_beat_selected(beat),
+        }
+    }
+
+    /// How long a hit rings in beats, `None` when it is held until the next hit or change
+    pub fn gate_beats(&self) -> Option<f64> {
+        match self.style {
+            ArticulationStyle::Staccato => Some(self.gate_percent.clamp(1, 100) as f64 / 100.0),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+/// Lowest note of the bass register (E1), roots are placed in the octave above it
+const LOWEST_BASS_NOTE: u8 = 28;
+
+/// What the bass plays in every bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, Display, Serialize, Deserialize)]
+pub enum BassStyle {
+    #[strum(to_string = "Root only")]
+    Root,
+    #[strum(to_string = "Root and fifth")]
+    RootFifth,
+    #[strum(to_string = "Two feel")]
+    TwoFeel,
+    #[default]
+    Walking,
+}
+
+/// How the bass leads into the next chord
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, Display, Serialize, Deserialize)]
+pub enum BassApproach {
+    #[default]
+    Chromatic,
+    #[strum(to_string = "Scale step")]
+    ScaleStep,
+}
+
+/// Configuration of the generated bass line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BassLine {
+    pub style: BassStyle,
+    pub approach: BassApproach,
+}
+
+impl Default for BassLine {
+    fn default() -> Self {
+        Self {
+            style: BassStyle::Walking,
+            approach: BassApproach::Chromatic,
+        }
+    }
+}
+
+/// Root, third and fifth of a chord as semitones above the root key
+struct ChordTones {
+    root: u8,
+    third: u8,
+    fifth: u8,
+}
+
+impl ChordTones {
+    fn new(midi_notes: &[u8]) -> Option<Self> {
+        let root_class = midi_notes.first()? % 12;
+        let has = |interval: u8| {
+            midi_notes
+                .iter()
+                .any(|n| (n + 12 - root_class) % 12 == interval)
+        };
+        let third = if has(4) {
+            4
+        } else if has(3) {
+            3
+        } else if has(5) {
+            5
+        } else {
+            4
+        };
+        let fifth = if has(7) {
+            7
+        } else if has(6) {
+            6
+        } else if has(8) {
+            8
+        } else {
+            7
+        };
+        Some(Self {
+            root: bass_key(root_class),
+            third,
+            fifth,
+        })
+    }
+}
+
+/// Place a pitch class in the bass register
+fn bass_key(pitch_class: u8) -> u8 {
+    LOWEST_BASS_NOTE + (pitch_class + 12 - LOWEST_BASS_NOTE % 12) % 12
+}
+
+impl BassLine {
+    /// Note leading into `target` (in whichever octave is closer) from the side of `previous`
+    fn approach_note(&self, previous: u8, target: u8) -> u8 {
+        let target = if previous.abs_diff(target + 12) < previous.abs_diff(target) {
+            target + 12
+        } else {
+            target
+        };
+        let step = match self.approach {
+            BassApproach::Chromatic => 1,
+            BassApproach::ScaleStep => 2,
+        };
+        if previous > target {
+            target + step
+        } else {
+            target - step
+        }
+    }
+
+    /// Call `note(at, length, key)` for the bass notes of one bar, positions in beats.
+    /// `next_notes` is the chord after the current one and `approaching` is set on the
+    /// last bar before it starts.
+    pub fn bar_notes(
+        &self,
+        chord_notes: &[u8],
+        next_notes: Option<&[u8]>,
+        approaching: bool,
+        beats_per_bar: u8,
+        mut note: impl FnMut(f64, f64, u8),
+    ) {
+        let Some(tones) = ChordTones::new(chord_notes) else {
+            return;
+        };
+        let beats = beats_per_bar.max(1);
+        let root = tones.root;
+        let fifth = root + tones.fifth;
+        // Lead into the next chord, or back to our own root while the chord is held
+        let target = match next_notes.and_then(|n| n.first()) {
+            Some(next) if approaching => bass_key(next % 12),
+            _ => root,
+        };
+        let half = beats / 2;
+
+        match self.style {
+            BassStyle::Root => note(0.0, beats as f64, root),
+            BassStyle::RootFifth | BassStyle::TwoFeel => {
+                if half == 0 {
+                    note(0.0, beats as f64, root);
+                    return;
+                }
+                note(0.0, half as f64, root);
+                let second = if self.style == BassStyle::TwoFeel && approaching {
+                    self.approach_note(root, target)
+                } else {
+                    fifth
+                };
+                note(half as f64, (beats - half) as f64, second);
+            }
+            BassStyle::Walking => {
+                let tones = [root + tones.third, fifth, root + 12];
+                let mut previous = root;
+                note(0.0, 1.0, root);
+                for beat in 1..beats {
+                    let key = if beat == beats - 1 {
+                        self.approach_note(previous, target)
+                    } else {
+                        tones[(beat as usize - 1) % tones.len()]
+                    };
+                    note(beat as f64, 1.0, key);
+                    previous = key;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(line: BassLine, chord: &[u8], next: &[u8], approaching: bool, beats: u8) -> Vec<u8> {
+        let mut keys = Vec::new();
+        line.bar_notes(chord, Some(next), approaching, beats, |_, _, key| {
+            keys.push(key)
+        });
+        keys
+    }
+
+    #[test]
+    fn test_walking_line_approaches_next_root() {
+        let line = BassLine::default();
+        // Dm7 walking to G7: D F A then a half step above G
+        let keys = bar(line, &[62, 65, 69, 72], &[67, 71, 74, 77], true, 4);
+        assert_eq!(keys, vec![38, 41, 45, 44]);
+
+        // Held chord walks back to its own root, from above after climbing
+        let scale = BassLine {
+            approach: BassApproach::ScaleStep,
+            ..line
+        };
+        let keys = bar(scale, &[60, 64, 67], &[65, 69, 72], false, 3);
+        assert_eq!(keys, vec![36, 40, 38]);
+    }
+
+    #[test]
+    fn test_two_feel_and_root_fifth() {
+        let two_feel = BassLine {
+            style: BassStyle::TwoFeel,
+            ..BassLine::default()
+        };
+        assert_eq!(bar(two_feel, &[60, 64, 67], &[65], false, 4), vec![36, 43]);
+        assert_eq!(bar(two_feel, &[60, 64, 67], &[65], true, 4), vec![36, 40]);
+
+        let root_fifth = BassLine {
+            style: BassStyle::RootFifth,
+            ..BassLine::default()
+        };
+        // Diminished chords use their flat fifth
+        assert_eq!(bar(root_fifth, &[59, 62, 65], &[60], true, 4), vec![35, 41]);
+    }
+}
@@ -0,0 +1,367 @@
+use std::f32::consts::TAU;
+
+/// Voices that can sound at once, the oldest is stolen when all are busy
+const MAX_VOICES: usize = 32;
+/// Longest Karplus-Strong delay line in samples, lower notes are played an octave up
+const MAX_DELAY: usize = 4096;
+const MIDI_CHANNELS: usize = 16;
+/// Percussion channel of General MIDI (channel 10)
+const GM_PERCUSSION_CHANNEL: i32 = 9;
+/// Headroom so a full chord with bass and drums does not clip
+const MASTER_GAIN: f32 = 0.25;
+
+const CONTROL_CHANGE: i32 = 0xB0;
+const BANK_SELECT: i32 = 0x00;
+const PROGRAM_CHANGE: i32 = 0xC0;
+
+/// How a voice makes its sound
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VoiceKind {
+    Idle,
+    /// A few decaying harmonics, used for chords
+    Tone,
+    /// Karplus-Strong plucked string, used for bass and guitar programs
+    Pluck,
+    /// Sine burst, optionally sliding down in pitch (wood blocks, kick, toms)
+    Click {
+        pitch_drop: f32,
+    },
+    /// Noise burst, `bright` removes the low end (hats, cymbals, side stick)
+    Noise {
+        bright: bool,
+    },
+}
+
+struct Voice {
+    kind: VoiceKind,
+    channel: i32,
+    key: i32,
+    /// Order the voice was started in, for stealing
+    started: u64,
+    gain: f32,
+    frequency: f32,
+    phase: f32,
+    envelope: f32,
+    /// Envelope multiplier per sample while the note is held
+    decay: f32,
+    /// Envelope multiplier per sample after the note-off
+    release: f32,
+    released: bool,
+    /// Delay line of the plucked string, allocated once
+    delay: Vec<f32>,
+    delay_len: usize,
+    delay_pos: usize,
+    previous: f32,
+}
+
+impl Voice {
+    fn new() -> Self {
+        Self {
+            kind: VoiceKind::Idle,
+            channel: 0,
+            key: 0,
+            started: 0,
+            gain: 0.0,
+            frequency: 0.0,
+            phase: 0.0,
+            envelope: 0.0,
+            decay: 1.0,
+            release: 1.0,
+            released: false,
+            delay: vec![0.0; MAX_DELAY],
+            delay_len: 1,
+            delay_pos: 0,
+            previous: 0.0,
+        }
+    }
+}
+
+/// Small synthesizer that needs no SoundFont: sine and noise clicks, additive chord tones
+/// and Karplus-Strong plucks. It follows the MIDI calls the engine makes on a
+/// `rustysynth::Synthesizer` and never allocates after it is created.
+pub struct BuiltinSynth {
+    sample_rate: f32,
+    voices: Vec<Voice>,
+    banks: [i32; MIDI_CHANNELS],
+    programs: [i32; MIDI_CHANNELS],
+    started: u64,
+    /// Xorshift state for noise and plucks
+    seed: u32,
+}
+
+/// Frequency of a MIDI key in Hz
+fn key_frequency(key: i32) -> f32 {
+    440.0 * 2f32.powf((key - 69) as f32 / 12.0)
+}
+
+impl BuiltinSynth {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1) as f32,
+            voices: (0..MAX_VOICES).map(|_| Voice::new()).collect(),
+            banks: [0; MIDI_CHANNELS],
+            programs: [0; MIDI_CHANNELS],
+            started: 0,
+            seed: 0x9E37_79B9,
+        }
+    }
+
+    fn noise(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    /// Multiplier per sample that brings the envelope down 60 dB in `seconds`
+    fn decay_over(&self, seconds: f32) -> f32 {
+        0.001f32.powf(1.0 / (seconds * self.sample_rate))
+    }
+
+    fn is_percussion(&self, channel: i32) -> bool {
+        let bank = self.banks.get(channel as usize).copied().unwrap_or(0);
+        channel == GM_PERCUSSION_CHANNEL || bank >= 128
+    }
+
+    /// Sound, pitch and length of a General MIDI percussion key
+    fn drum(key: i32) -> (VoiceKind, f32, f32) {
+        let click = |pitch_drop| VoiceKind::Click { pitch_drop };
+        let noise = |bright| VoiceKind::Noise { bright };
+        match key {
+            35 | 36 => (click(0.4), 110.0, 0.3),
+            37 => (noise(true), 0.0, 0.03),
+            38 | 40 => (noise(false), 0.0, 0.15),
+            41 | 43 => (click(0.7), 90.0, 0.35),
+            45 | 47 => (click(0.7), 130.0, 0.3),
+            48 | 50 => (click(0.7), 180.0, 0.25),
+            42 => (noise(true), 0.0, 0.05),
+            44 => (noise(true), 0.0, 0.07),
+            46 => (noise(true), 0.0, 0.35),
+            49 | 57 => (noise(true), 0.0, 1.2),
+            51 | 59 => (noise(true), 0.0, 0.5),
+            76 => (click(1.0), 1800.0, 0.04),
+            77 => (click(1.0), 1300.0, 0.04),
+            _ => (click(1.0), 1000.0, 0.05),
+        }
+    }
+
+    fn free_voice(&self) -> usize {
+        self.voices
+            .iter()
+            .position(|v| v.kind == VoiceKind::Idle)
+            .or_else(|| {
+                self.voices
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, v)| v.started)
+                    .map(|(i, _)| i)
+            })
+            .unwrap_or(0)
+    }
+
+    pub fn note_on(&mut self, channel: i32, key: i32, velocity: i32) {
+        if velocity <= 0 {
+            self.note_off(channel, key);
+            return;
+        }
+        let percussion = self.is_percussion(channel);
+        let program = self.programs.get(channel as usize).copied().unwrap_or(0);
+        let (kind, frequency, seconds) = if percussion {
+            Self::drum(key)
+        } else if (24..40).contains(&program) {
+            // Guitars and basses are plucked
+            (VoiceKind::Pluck, key_frequency(key), 2.5)
+        } else {
+            (VoiceKind::Tone, key_frequency(key), 2.0)
+        };
+        let decay = self.decay_over(seconds);
+        let release = self.decay_over(0.12);
+
+        let index = self.free_voice();
+        self.started += 1;
+        let started = self.started;
+        let sample_rate = self.sample_rate;
+        let voice = &mut self.voices[index];
+        *voice = Voice {
+            kind,
+            channel,
+            key,
+            started,
+            gain: velocity.min(127) as f32 / 127.0,
+            frequency,
+            phase: 0.0,
+            envelope: 1.0,
+            decay,
+            release,
+            released: false,
+            delay: std::mem::take(&mut voice.delay),
+            delay_len: 1,
+            delay_pos: 0,
+            previous: 0.0,
+        };
+
+        if kind == VoiceKind::Pluck {
+            let mut period = sample_rate / frequency.max(1.0);
+            while period >= MAX_DELAY as f32 {
+                period /= 2.0;
+            }
+            let len = (period as usize).max(2);
+            for i in 0..len {
+                let excitation = self.noise();
+                self.voices[index].delay[i] = excitation;
+            }
+            self.voices[index].delay_len = len;
+        }
+    }
+
+    pub fn note_off(&mut self, channel: i32, key: i32) {
+        // Drums ring out, melodic notes are released
+        if self.is_percussion(channel) {
+            return;
+        }
+        for voice in self.voices.iter_mut() {
+            if voice.kind != VoiceKind::Idle && voice.channel == channel && voice.key == key {
+                voice.released = true;
+            }
+        }
+    }
+
+    pub fn note_off_all_channel(&mut self, channel: i32, immediate: bool) {
+        for voice in self.voices.iter_mut().filter(|v| v.channel == channel) {
+            if immediate {
+                voice.kind = VoiceKind::Idle;
+            } else {
+                voice.released = true;
+            }
+        }
+    }
+
+    /// Only bank select and program change matter to the built-in sounds
+    pub fn process_midi_message(&mut self, channel: i32, command: i32, data1: i32, data2: i32) {
+        let Some(index) = usize::try_from(channel).ok().filter(|&c| c < MIDI_CHANNELS) else {
+            return;
+        };
+        match command {
+            CONTROL_CHANGE if data1 == BANK_SELECT => self.banks[index] = data2,
+            PROGRAM_CHANGE => self.programs[index] = data1,
+            _ => {}
+        }
+    }
+
+    pub fn render(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let frames = left.len().min(right.len());
+        left[..frames].fill(0.0);
+        right[..frames].fill(0.0);
+
+        for index in 0..self.voices.len() {
+            if self.voices[index].kind == VoiceKind::Idle {
+                continue;
+            }
+            for frame in 0..frames {
+                let noise = self.noise();
+                let sample = self.voice_sample(index, noise) * MASTER_GAIN;
+                left[frame] += sample;
+                right[frame] += sample;
+                if self.voices[index].kind == VoiceKind::Idle {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Next sample of a voice, `noise` is a fresh white noise sample
+    fn voice_sample(&mut self, index: usize, noise: f32) -> f32 {
+        let sample_rate = self.sample_rate;
+        let voice = &mut self.voices[index];
+        let raw = match voice.kind {
+            VoiceKind::Idle => return 0.0,
+            VoiceKind::Tone => {
+                let mut sum = 0.0;
+                for (harmonic, amplitude) in [1.0, 0.5, 0.25, 0.12].into_iter().enumerate() {
+                    sum += amplitude * (TAU * voice.phase * (harmonic + 1) as f32).sin();
+                }
+                voice.phase = (voice.phase + voice.frequency / sample_rate).fract();
+                sum * 0.5
+            }
+            VoiceKind::Pluck => {
+                let pos = voice.delay_pos;
+                let next = (pos + 1) % voice.delay_len;
+                let out = voice.delay[pos];
+                voice.delay[pos] = 0.5 * (out + voice.delay[next]) * 0.998;
+                voice.delay_pos = next;
+                out
+            }
+            VoiceKind::Click { pitch_drop } => {
+                let out = (TAU * voice.phase).sin();
+                // Slide towards `pitch_drop` of the start pitch as the hit decays
+                let frequency =
+                    voice.frequency * (pitch_drop + (1.0 - pitch_drop) * voice.envelope);
+                voice.phase = (voice.phase + frequency / sample_rate).fract();
+                out
+            }
+            VoiceKind::Noise { bright } => {
+                let out = if bright {
+                    noise - voice.previous
+                } else {
+                    noise
+                };
+                voice.previous = noise;
+                out * 0.6
+            }
+        };
+
+        let out = raw * voice.envelope * voice.gain;
+        voice.envelope *= if voice.released {
+            voice.release
+        } else {
+            voice.decay
+        };
+        if voice.envelope < 1e-4 {
+            voice.kind = VoiceKind::Idle;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn peak(synth: &mut BuiltinSynth, frames: usize) -> f32 {
+        let mut left = vec![0.0; frames];
+        let mut right = vec![0.0; frames];
+        synth.render(&mut left, &mut right);
+        left.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn test_click_rings_out() {
+        let mut synth = BuiltinSynth::new(SAMPLE_RATE);
+        synth.process_midi_message(2, CONTROL_CHANGE, BANK_SELECT, 128);
+        synth.note_on(2, 76, 120);
+        assert!(peak(&mut synth, 480) > 0.05);
+        // Percussion ignores note-offs and is silent well within a beat
+        synth.note_off(2, 76);
+        peak(&mut synth, 9_600);
+        assert_eq!(peak(&mut synth, 480), 0.0);
+    }
+
+    #[test]
+    fn test_melodic_notes_sustain_until_released() {
+        let mut synth = BuiltinSynth::new(SAMPLE_RATE);
+        synth.process_midi_message(1, PROGRAM_CHANGE, 32, 0);
+        for (channel, key) in [(0, 60), (0, 64), (1, 36)] {
+            synth.note_on(channel, key, 100);
+        }
+        peak(&mut synth, 24_000);
+        assert!(peak(&mut synth, 480) > 0.01);
+
+        synth.note_off(0, 60);
+        synth.note_off(0, 64);
+        synth.note_off_all_channel(1, false);
+        peak(&mut synth, 24_000);
+        assert_eq!(peak(&mut synth, 480), 0.0);
+    }
+}
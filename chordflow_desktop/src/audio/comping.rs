@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+/// Delay between two strings of a strummed chord
+const STRUM_DELAY_MS: f64 = 18.0;
+
+/// Rhythm the chord voicing is played with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, Display, Serialize, Deserialize)]
+pub enum CompingPattern {
+    /// Block chords following the articulation settings
+    #[default]
+    #[strum(to_string = "Block chords")]
+    Block,
+    #[strum(to_string = "Whole notes")]
+    Whole,
+    Charleston,
+    #[strum(to_string = "Freddie Green")]
+    FreddieGreen,
+    #[strum(to_string = "Bossa nova")]
+    BossaNova,
+    #[strum(to_string = "Reggae skank")]
+    ReggaeSkank,
+    #[strum(to_string = "Arpeggio up")]
+    ArpeggioUp,
+    #[strum(to_string = "Arpeggio down")]
+    ArpeggioDown,
+    Strum,
+}
+
+/// One hit of a comping pattern. Positions and lengths are in beats from the start of the bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompHit {
+    pub at: f64,
+    pub length: f64,
+    /// Play only this note of the chord (wrapping), or the whole chord
+    pub note: Option<usize>,
+    /// Delay between consecutive notes of the chord in milliseconds
+    pub spread_ms: f64,
+}
+
+impl CompHit {
+    fn chord(at: f64, length: f64) -> Self {
+        Self {
+            at,
+            length,
+            note: None,
+            spread_ms: 0.0,
+        }
+    }
+}
+
+/// Move an off-beat eighth to the swing position, `swing` is the fraction of the beat
+/// the first eighth takes (0.5 is straight, 0.67 triplet swing)
+fn swung(at: f64, swing: f64) -> f64 {
+    let beat = at.floor();
+    if (at - beat - 0.5).abs() < 1e-9 {
+        beat + swing
+    } else {
+        at
+    }
+}
+
+impl CompingPattern {
+    /// Call `hit` for every hit in bar `bar` (counted from the start of the session).
+    /// Hits are laid out on the given time signature and nothing rings past the bar line.
+    pub fn hits(
+        &self,
+        bar: u64,
+        beats_per_bar: u8,
+        swing: f64,
+        chord_notes: usize,
+        mut hit: impl FnMut(CompHit),
+    ) {
+        let beats = beats_per_bar.max(1) as f64;
+        let mut emit = |h: CompHit| {
+            if h.at < beats {
+                let at = swung(h.at, swing);
+                let end = swung(h.at + h.length, swing).min(beats);
+                hit(CompHit {
+                    at,
+                    length: end - at,
+                    ..h
+                });
+            }
+        };
+
+        match self {
+            CompingPattern::Block => {}
+            CompingPattern::Whole => emit(CompHit::chord(0.0, beats)),
+            CompingPattern::Charleston => {
+                emit(CompHit::chord(0.0, 1.0));
+                emit(CompHit::chord(1.5, 1.0));
+            }
+            CompingPattern::FreddieGreen => {
+                for beat in 0..beats_per_bar {
+                    emit(CompHit::chord(beat as f64, 0.6));
+                }
+            }
+            CompingPattern::BossaNova => {
+                // Two-bar figure
+                let figure: &[f64] = if bar.is_multiple_of(2) {
+                    &[0.0, 1.5, 3.0]
+                } else {
+                    &[1.0, 2.5]
+                };
+                for &at in figure {
+                    emit(CompHit::chord(at, 0.5));
+                }
+            }
+            CompingPattern::ReggaeSkank => {
+                for beat in (1..beats_per_bar).step_by(2) {
+                    emit(CompHit::chord(beat as f64, 0.25));
+                }
+            }
+            CompingPattern::ArpeggioUp | CompingPattern::ArpeggioDown => {
+                let notes = chord_notes.max(1);
+                for step in 0..beats_per_bar as usize * 2 {
+                    let note = if *self == CompingPattern::ArpeggioUp {
+                        step % notes
+                    } else {
+                        notes - 1 - step % notes
+                    };
+                    emit(CompHit {
+                        note: Some(note),
+                        ..CompHit::chord(step as f64 * 0.5, 0.5)
+                    });
+                }
+            }
+            CompingPattern::Strum => {
+                for beat in 0..beats_per_bar {
+                    emit(CompHit {
+                        spread_ms: STRUM_DELAY_MS,
+                        ..CompHit::chord(beat as f64, 1.0)
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(pattern: CompingPattern, bar: u64, beats: u8, swing: f64) -> Vec<(f64, f64)> {
+        let mut hits = Vec::new();
+        pattern.hits(bar, beats, swing, 3, |h| hits.push((h.at, h.length)));
+        hits
+    }
+
+    #[test]
+    fn test_patterns_follow_the_time_signature() {
+        assert_eq!(hits(CompingPattern::Whole, 0, 3, 0.5), vec![(0.0, 3.0)]);
+        assert_eq!(hits(CompingPattern::FreddieGreen, 0, 3, 0.5).len(), 3);
+        assert_eq!(
+            hits(CompingPattern::ReggaeSkank, 0, 4, 0.5),
+            vec![(1.0, 0.25), (3.0, 0.25)]
+        );
+        assert_eq!(hits(CompingPattern::BossaNova, 1, 4, 0.5).len(), 2);
+    }
+
+    #[test]
+    fn test_swing_moves_off_beat_eighths() {
+        let charleston = hits(CompingPattern::Charleston, 0, 4, 0.75);
+        assert_eq!(charleston[0], (0.0, 1.0));
+        assert_eq!(charleston[1].0, 1.75);
+
+        let mut notes = Vec::new();
+        CompingPattern::ArpeggioDown.hits(0, 2, 0.5, 3, |h| notes.push(h.note));
+        assert_eq!(notes, vec![Some(2), Some(1), Some(0), Some(2)]);
+    }
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+// General MIDI percussion keys
+const KICK: i32 = 36;
+const SIDE_STICK: i32 = 37;
+const SNARE: i32 = 38;
+const FLOOR_TOM: i32 = 41;
+const CLOSED_HAT: i32 = 42;
+const PEDAL_HAT: i32 = 44;
+const LOW_TOM: i32 = 45;
+const MID_TOM: i32 = 47;
+const CRASH: i32 = 49;
+const HIGH_TOM: i32 = 50;
+const RIDE: i32 = 51;
+
+/// Swing used by swung grooves when the swing setting is straight
+const TRIPLET_SWING: f64 = 2.0 / 3.0;
+
+/// Built-in drum patterns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, Display, Serialize, Deserialize)]
+pub enum DrumStyle {
+    #[default]
+    #[strum(to_string = "Swing ride")]
+    SwingRide,
+    Rock,
+    Funk,
+    #[strum(to_string = "Bossa nova")]
+    Bossa,
+    Shuffle,
+    Waltz,
+}
+
+/// Configuration of the drum groove
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DrumGroove {
+    pub style: DrumStyle,
+    /// Keep the metronome click on top of the groove
+    pub keep_click: bool,
+    /// Play a fill in the last bar before a chord change
+    pub fills: bool,
+}
+
+impl Default for DrumGroove {
+    fn default() -> Self {
+        Self {
+            style: DrumStyle::SwingRide,
+            keep_click: false,
+            fills: false,
+        }
+    }
+}
+
+impl DrumGroove {
+    /// Call `hit(at, key, velocity)` for the hits of one bar, positions in beats.
+    /// `fill` replaces the last beat with a tom fill, `crash` marks the downbeat after a fill.
+    pub fn bar_hits(
+        &self,
+        bar: u64,
+        beats_per_bar: u8,
+        swing: f64,
+        fill: bool,
+        crash: bool,
+        mut hit: impl FnMut(f64, i32, i32),
+    ) {
+        let beats = beats_per_bar.max(1);
+        let fill_from = (beats - 1) as f64;
+        let swung = if swing > 0.5 { swing } else { TRIPLET_SWING };
+        let mut emit = |at: f64, key: i32, velocity: i32| {
+            if at < beats as f64 && !(fill && at >= fill_from) {
+                hit(at, key, velocity);
+            }
+        };
+
+        if crash {
+            emit(0.0, CRASH, 110);
+        }
+
+        for beat in 0..beats {
+            let at = beat as f64;
+            let backbeat = beat % 2 == 1;
+            match self.style {
+                DrumStyle::SwingRide => {
+                    emit(at, RIDE, if backbeat { 85 } else { 95 });
+                    emit(at, KICK, 35);
+                    if backbeat {
+                        emit(at + swung, RIDE, 70);
+                        emit(at, PEDAL_HAT, 80);
+                    }
+                }
+                DrumStyle::Rock => {
+                    emit(at, CLOSED_HAT, 90);
+                    emit(at + 0.5, CLOSED_HAT, 65);
+                    if backbeat {
+                        emit(at, SNARE, 105);
+                    } else {
+                        emit(at, KICK, 110);
+                    }
+                }
+                DrumStyle::Funk => {
+                    for (i, velocity) in [95, 50, 75, 50].into_iter().enumerate() {
+                        emit(at + i as f64 * 0.25, CLOSED_HAT, velocity);
+                    }
+                    if backbeat {
+                        emit(at, SNARE, 110);
+                        emit(at + 0.75, SNARE, 35);
+                    } else {
+                        emit(at, KICK, 110);
+                        if beat == 0 {
+                            emit(at + 0.75, KICK, 90);
+                        }
+                    }
+                }
+                DrumStyle::Bossa => {
+                    emit(at, CLOSED_HAT, 70);
+                    emit(at + 0.5, CLOSED_HAT, 55);
+                    if !backbeat {
+                        emit(at, KICK, 90);
+                        emit(at + 1.5, KICK, 70);
+                    }
+                }
+                DrumStyle::Shuffle => {
+                    emit(at, CLOSED_HAT, 90);
+                    emit(at + swung, CLOSED_HAT, 60);
+                    if backbeat {
+                        emit(at, SNARE, 100);
+                    } else {
+                        emit(at, KICK, 105);
+                    }
+                }
+                DrumStyle::Waltz => {
+                    if beat == 0 {
+                        emit(at, KICK, 105);
+                    } else {
+                        emit(at, SNARE, 60);
+                    }
+                    emit(at, CLOSED_HAT, if beat == 0 { 90 } else { 65 });
+                }
+            }
+        }
+
+        if self.style == DrumStyle::Bossa {
+            // Two-bar clave on the side stick
+            let clave: &[f64] = if bar.is_multiple_of(2) {
+                &[0.0, 1.5, 3.0]
+            } else {
+                &[1.0, 2.5]
+            };
+            for &at in clave {
+                emit(at, SIDE_STICK, 85);
+            }
+        }
+
+        if fill {
+            for (i, key) in [HIGH_TOM, MID_TOM, LOW_TOM, FLOOR_TOM]
+                .into_iter()
+                .enumerate()
+            {
+                hit(fill_from + i as f64 * 0.25, key, 85 + i as i32 * 5);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(groove: DrumGroove, beats: u8, fill: bool) -> Vec<(f64, i32)> {
+        let mut hits = Vec::new();
+        groove.bar_hits(0, beats, 0.5, fill, false, |at, key, _| {
+            hits.push((at, key))
+        });
+        hits
+    }
+
+    #[test]
+    fn test_rock_backbeat_follows_time_signature() {
+        let rock = DrumGroove {
+            style: DrumStyle::Rock,
+            ..DrumGroove::default()
+        };
+        let snares = |beats| {
+            hits(rock, beats, false)
+                .into_iter()
+                .filter(|&(_, key)| key == SNARE)
+                .map(|(at, _)| at)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(snares(4), vec![1.0, 3.0]);
+        assert_eq!(snares(3), vec![1.0]);
+    }
+
+    #[test]
+    fn test_fill_replaces_last_beat() {
+        let hits = hits(DrumGroove::default(), 4, true);
+        let last_beat: Vec<_> = hits.iter().filter(|&&(at, _)| at >= 3.0).collect();
+        assert_eq!(last_beat.len(), 4);
+        assert!(last_beat.iter().all(|&&(_, key)| key != RIDE));
+    }
+}
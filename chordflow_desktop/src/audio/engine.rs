@@ -0,0 +1,797 @@
+use crossbeam_channel::Sender;
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::{
+    audio::{
+        articulation::ChordArticulation,
+        bass::BassLine,
+        comping::CompingPattern,
+        drums::DrumGroove,
+        gap::GapClickState,
+        note_queue::NoteQueue,
+        samples::{ClickSound, SamplePlayer},
+        settings::AUDIO_SETTINGS,
+        soundfont::{Instrument, InstrumentPart},
+        source::SoundSource,
+        tempo::{RampAction, TempoRampState},
+        timeline::{ScheduledChord, Timeline},
+    },
+    AudioCommand, AudioEvent, AUDIO_EVT, MAX_BPM, MIN_BPM,
+};
+
+// Percussion channel (MIDI channel 10 = index 9)
+pub const PERCUSSION_CHANNEL: i32 = 9;
+
+// The click has its own channel so its preset can differ from the drum groove's kit
+pub const CLICK_CHANNEL: i32 = 2;
+
+// Different click sounds for different beat types
+// Using woodblock and sidestick sounds from General MIDI percussion
+const CLICK_ACCENT: i32 = 76; // Hi wood block - for downbeat (first beat of bar)
+const CLICK_NORMAL: i32 = 77; // Low wood block - for regular beats
+const CLICK_SUBDIVISION: i32 = 37; // Side stick - for subdivisions
+
+// Velocities for different beat types
+const VELOCITY_ACCENT: i32 = 120;
+const VELOCITY_NORMAL: i32 = 100;
+const VELOCITY_SUBDIVISION: i32 = 70;
+
+// Chord configuration
+pub const CHORD_CHANNEL: i32 = 0; // Use channel 0 for melodic instruments
+const CHORD_VELOCITY: i32 = 80;
+
+// Bass configuration
+pub const BASS_CHANNEL: i32 = 1;
+const BASS_VELOCITY: i32 = 90;
+
+// MIDI status bytes and the bank select controller
+const CONTROL_CHANGE: i32 = 0xB0;
+const BANK_SELECT: i32 = 0x00;
+const PROGRAM_CHANGE: i32 = 0xC0;
+
+/// Chord velocity after the chord volume setting, clamped to the MIDI range
+fn chord_velocity() -> i32 {
+    ((CHORD_VELOCITY as f32) * AUDIO_SETTINGS.get_chord_volume()).clamp(0.0, 127.0) as i32
+}
+
+/// Transport, chord timeline and synthesizer of the audio thread.
+///
+/// Every musical decision is taken inside [`Engine::render`], so clicks and chord changes
+/// start on the exact sample they fall on instead of whenever the UI gets around to it.
+/// The engine is owned by the audio callback: neither handling commands nor rendering may
+/// lock or allocate.
+pub struct Engine {
+    synth: Box<SoundSource>,
+    /// Preset per [`InstrumentPart`], applied again when the synthesizer is replaced
+    instruments: [Option<Instrument>; InstrumentPart::COUNT],
+    sample_rate: u32,
+    is_playing: bool,
+    is_count_in: bool,
+    bpm: u16,
+    ticks_per_bar: u8,
+    subdivisions_per_beat: u8,
+    /// Frames rendered since the engine was created
+    frame: u64,
+    /// Frame of the next click, kept fractional so the tempo does not drift
+    next_click: f64,
+    subdivision: u8,
+    beat: u8,
+    /// Bar counted from the start of the session
+    bar: u64,
+    timeline: Timeline,
+    articulation: ChordArticulation,
+    comping: CompingPattern,
+    /// Fraction of a beat the first of two eighths takes, 0.5 is straight
+    swing: f64,
+    /// Chord whose notes are currently held
+    sounding: Option<ScheduledChord>,
+    bass: Option<BassLine>,
+    drums: Option<DrumGroove>,
+    /// The previous bar ended with a fill, so the next downbeat gets a crash
+    drum_fill_played: bool,
+    /// User samples replacing the synthesized click
+    samples: SamplePlayer,
+    /// Note events due later, e.g. comping hits and note-offs
+    notes: NoteQueue,
+    tempo_ramp: Option<TempoRampState>,
+    gap_click: Option<GapClickState>,
+    /// Playback stops on the downbeat of this bar, for rendering a fixed length
+    end_bar: Option<u64>,
+    /// Where ticks, chord changes and released objects go, the UI unless rendering offline
+    events: Sender<AudioEvent>,
+}
+
+impl Engine {
+    pub fn new(synth: SoundSource, sample_rate: u32) -> Self {
+        Self::with_events(synth, sample_rate, AUDIO_EVT.0.clone())
+    }
+
+    /// An engine reporting its events somewhere other than the UI
+    pub fn with_events(synth: SoundSource, sample_rate: u32, events: Sender<AudioEvent>) -> Self {
+        Self {
+            synth: Box::new(synth),
+            instruments: [None; InstrumentPart::COUNT],
+            sample_rate,
+            is_playing: false,
+            is_count_in: false,
+            bpm: 120,
+            ticks_per_bar: 4,
+            subdivisions_per_beat: 1,
+            frame: 0,
+            next_click: 0.0,
+            subdivision: 0,
+            beat: 0,
+            bar: 0,
+            timeline: Timeline::default(),
+            articulation: ChordArticulation::default(),
+            comping: CompingPattern::default(),
+            swing: 0.5,
+            sounding: None,
+            bass: None,
+            drums: None,
+            drum_fill_played: false,
+            samples: SamplePlayer::default(),
+            notes: NoteQueue::default(),
+            tempo_ramp: None,
+            gap_click: None,
+            end_bar: None,
+            events,
+        }
+    }
+
+    /// The sound source back, e.g. to read what a recorder wrote down
+    pub fn into_source(self) -> SoundSource {
+        *self.synth
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    /// Stop before the downbeat of `bar` instead of playing on
+    pub fn stop_at_bar(&mut self, bar: Option<u64>) {
+        self.end_bar = bar;
+    }
+
+    pub fn handle_command(&mut self, cmd: AudioCommand) {
+        match cmd {
+            AudioCommand::Start => self.start(false),
+            AudioCommand::StartWithCountIn => self.start(true),
+            AudioCommand::Stop => {
+                self.is_playing = false;
+                self.release_chord();
+                self.release_bass();
+                // Reset counters so next start is clean
+                self.is_count_in = false;
+                self.subdivision = 0;
+                self.beat = 0;
+            }
+            AudioCommand::Restart => {
+                // The practice state schedules a fresh timeline right after a restart
+                self.timeline.clear();
+                self.release_chord();
+                self.release_bass();
+                self.bar = 0;
+                self.restart_bar();
+            }
+            AudioCommand::SetBPM(bpm) => {
+                self.bpm = bpm.clamp(MIN_BPM, MAX_BPM);
+            }
+            AudioCommand::SetSubdivision(subdivisions) => {
+                self.subdivisions_per_beat = subdivisions.max(1);
+                self.restart_bar();
+            }
+            AudioCommand::SetTimeSignature(beats_per_bar) => {
+                self.ticks_per_bar = beats_per_bar.clamp(1, 16);
+                self.release_chord();
+                self.release_bass();
+                self.restart_bar();
+            }
+            AudioCommand::SetSwing(percent) => {
+                self.swing = percent.clamp(50, 75) as f64 / 100.0;
+            }
+            AudioCommand::ScheduleChord(chord) => {
+                self.timeline.push(chord);
+            }
+            AudioCommand::DropChordsAfter(index) => {
+                self.timeline.truncate_after(index);
+            }
+            AudioCommand::SetArticulation(articulation) => {
+                self.articulation = articulation;
+            }
+            AudioCommand::SetComping(pattern) => {
+                self.comping = pattern;
+            }
+            AudioCommand::SetBass(bass) => {
+                self.release_bass();
+                self.bass = bass;
+            }
+            AudioCommand::SetDrums(drums) => {
+                self.drums = drums;
+                self.drum_fill_played = false;
+            }
+            AudioCommand::SetSoundSource(mut synth) => {
+                self.release_chord();
+                self.release_bass();
+                std::mem::swap(&mut self.synth, &mut synth);
+                for part in InstrumentPart::iter() {
+                    self.apply_instrument(part);
+                }
+                // Freeing the old sound source is left to the UI thread. The event queue only
+                // fills up when the UI stalls, then it is dropped here after all.
+                let _ = self.events.try_send(AudioEvent::SoundSourceReleased(synth));
+            }
+            AudioCommand::SetClickSample(sound, sample) => {
+                if let Some(old) = self.samples.replace(sound, sample) {
+                    let _ = self.events.try_send(AudioEvent::ClickSampleReleased(old));
+                }
+            }
+            AudioCommand::SetInstrument(part, instrument) => {
+                self.instruments[part as usize] = Some(instrument);
+                self.apply_instrument(part);
+            }
+            AudioCommand::SetTempoRamp(ramp) => {
+                self.tempo_ramp = ramp.map(TempoRampState::new);
+                if let Some(start_bpm) = self.tempo_ramp.as_ref().map(|r| r.start_bpm()) {
+                    self.set_bpm(start_bpm);
+                }
+            }
+            AudioCommand::SetGapClick(gap) => {
+                self.gap_click = gap.map(GapClickState::new);
+            }
+        }
+    }
+
+    /// Render the next `left.len()` frames, triggering every click and note on its own frame
+    pub fn render(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let frames = left.len().min(right.len());
+        let mut done = 0;
+        while done < frames {
+            let mut end = frames;
+            if let Some(event) = self.notes.pop_due(self.frame) {
+                if event.velocity > 0 {
+                    self.synth.note_on(event.channel, event.key, event.velocity);
+                } else {
+                    self.synth.note_off(event.channel, event.key);
+                }
+                continue;
+            }
+            if let Some(note_frame) = self.notes.next_frame() {
+                end = end.min(done.saturating_add((note_frame - self.frame) as usize));
+            }
+            if self.is_playing {
+                let click_frame = self.next_click.ceil() as u64;
+                if click_frame <= self.frame {
+                    self.click();
+                    continue;
+                }
+                end = end.min(done.saturating_add((click_frame - self.frame) as usize));
+            }
+            self.synth
+                .render(&mut left[done..end], &mut right[done..end]);
+            self.samples
+                .mix(&mut left[done..end], &mut right[done..end]);
+            self.frame += (end - done) as u64;
+            done = end;
+        }
+    }
+
+    fn start(&mut self, count_in: bool) {
+        // Resume at the start of the current chord
+        self.bar = self.timeline.resume_bar();
+        self.timeline.reannounce();
+        self.is_count_in = count_in;
+        if let Some(ramp) = self.tempo_ramp.as_mut() {
+            ramp.reset();
+        }
+        if let Some(start_bpm) = self.tempo_ramp.as_ref().map(|r| r.start_bpm()) {
+            self.set_bpm(start_bpm);
+        }
+        if let Some(gap) = self.gap_click.as_mut() {
+            gap.reset();
+        }
+        self.subdivision = 0;
+        self.beat = 0;
+        self.next_click = self.frame as f64;
+        self.is_playing = true;
+    }
+
+    /// Start the current bar over, immediately if playing
+    fn restart_bar(&mut self) {
+        self.subdivision = 0;
+        self.beat = 0;
+        self.next_click = self.frame as f64;
+    }
+
+    fn set_bpm(&mut self, bpm: u16) {
+        let bpm = bpm.clamp(MIN_BPM, MAX_BPM);
+        if bpm != self.bpm {
+            self.bpm = bpm;
+            let _ = self.events.try_send(AudioEvent::TempoChanged(bpm));
+        }
+    }
+
+    fn samples_per_beat(&self) -> f64 {
+        self.sample_rate as f64 * 60.0 / self.bpm as f64
+    }
+
+    fn samples_per_subdivision(&self) -> f64 {
+        self.samples_per_beat() / self.subdivisions_per_beat as f64
+    }
+
+    /// Play the click that is due on the current frame and move to the next one
+    fn click(&mut self) {
+        let sample = self.frame;
+        let is_main_beat = self.subdivision == 0;
+        let in_count_in = self.is_count_in;
+
+        // The session is over before its final downbeat sounds
+        let downbeat = is_main_beat && self.beat == 0 && !in_count_in;
+        if downbeat && self.end_bar.is_some_and(|end| self.bar >= end) {
+            self.is_playing = false;
+            self.release_chord();
+            self.release_bass();
+            let _ = self.events.try_send(AudioEvent::Stopped);
+            return;
+        }
+
+        // Chord changes happen on main beats, most of them on the downbeat
+        let mut chord_changed = false;
+        if is_main_beat && !in_count_in {
+            if let Some(chord) = self.timeline.advance_to(self.bar, self.beat) {
+                let _ = self.events.try_send(AudioEvent::ChordChanged {
+                    sample,
+                    index: chord.index,
+                });
+                chord_changed = true;
+            }
+        }
+
+        // Determine which sound to play and apply volume settings
+        let (sound, note, base_velocity, volume_multiplier) = if !is_main_beat {
+            (
+                ClickSound::Subdivision,
+                CLICK_SUBDIVISION,
+                VELOCITY_SUBDIVISION,
+                AUDIO_SETTINGS.get_metronome_subdivision_volume(),
+            )
+        } else if self.beat == 0 {
+            (
+                ClickSound::Accent,
+                CLICK_ACCENT,
+                VELOCITY_ACCENT,
+                AUDIO_SETTINGS.get_metronome_accent_volume(),
+            )
+        } else {
+            (
+                ClickSound::Beat,
+                CLICK_NORMAL,
+                VELOCITY_NORMAL,
+                AUDIO_SETTINGS.get_metronome_beat_volume(),
+            )
+        };
+        // Apply volume and clamp to valid MIDI velocity range (0-127)
+        let velocity = ((base_velocity as f32) * volume_multiplier).clamp(0.0, 127.0) as i32;
+
+        // Gap-click decides per main beat, subdivisions follow their beat.
+        // The count-in is never muted.
+        let (click_muted, chord_muted) = match self.gap_click.as_mut() {
+            Some(gap) if !in_count_in => {
+                let muted = if is_main_beat {
+                    gap.on_beat(self.beat)
+                } else {
+                    gap.is_muted()
+                };
+                (muted, muted && gap.config.mute_chords)
+            }
+            _ => (false, false),
+        };
+
+        // A groove replaces the click unless asked to keep it, the count-in is always clicked
+        let groove_replaces_click =
+            !in_count_in && self.drums.is_some_and(|drums| !drums.keep_click);
+        if !click_muted && !groove_replaces_click && !self.samples.trigger(sound, volume_multiplier)
+        {
+            self.synth.note_on(CLICK_CHANNEL, note, velocity);
+        }
+
+        // Ticks and chords belong to main beats outside of the count-in
+        if is_main_beat && !in_count_in {
+            let _ = self.events.try_send(AudioEvent::Tick {
+                sample,
+                bar: self.timeline.bar_in_chord(self.bar),
+                beat: self.beat + 1,
+            });
+
+            // A chord starting within the bar takes over the rest of it
+            let bar_start = self.beat == 0 || chord_changed;
+            if self.comping == CompingPattern::Block {
+                self.articulate(chord_muted, chord_changed);
+            } else if bar_start {
+                self.comp_bar(chord_muted);
+            }
+            if bar_start {
+                self.play_bass_bar(chord_muted);
+            }
+            self.play_drum_beat(click_muted);
+        }
+
+        // Schedule next click (subdivision or main beat) at the tempo of this bar,
+        // a new tempo starts on the bar line
+        self.next_click += self.samples_per_subdivision();
+
+        self.subdivision = (self.subdivision + 1) % self.subdivisions_per_beat;
+        if self.subdivision == 0 {
+            self.beat = (self.beat + 1) % self.ticks_per_bar;
+            if self.beat == 0 {
+                self.on_bar_complete(in_count_in);
+            }
+        }
+    }
+
+    /// Strike, hold or release the current chord on a main beat
+    fn articulate(&mut self, muted: bool, chord_changed: bool) {
+        let Some(chord) = self.timeline.current().copied() else {
+            return;
+        };
+        let chord_start =
+            chord_changed || (self.beat == 0 && self.timeline.bar_in_chord(self.bar) == 1);
+        let strikes = self.articulation.strikes(self.beat, chord_start);
+
+        // Whatever is still ringing stops at the next hit or chord change
+        if chord_start || strikes {
+            self.release_chord();
+        }
+        if !strikes || muted {
+            return;
+        }
+
+        let velocity = chord_velocity();
+        for &note in chord.midi_notes() {
+            self.synth.note_on(CHORD_CHANNEL, note as i32, velocity);
+        }
+        self.sounding = Some(chord);
+        if let Some(gate) = self.articulation.gate_beats() {
+            let release = self.frame + (gate * self.samples_per_beat()) as u64;
+            for &note in chord.midi_notes() {
+                self.notes.note_off(release, CHORD_CHANNEL, note as i32);
+            }
+        }
+    }
+
+    /// Queue the comping pattern for the rest of the bar from the current frame on
+    fn comp_bar(&mut self, muted: bool) {
+        // Nothing of the previous bar rings past the bar line
+        self.release_chord();
+        let Some(chord) = self.timeline.current().copied() else {
+            return;
+        };
+        if muted {
+            return;
+        }
+
+        let from = self.beat as f64;
+        let samples_per_beat = self.samples_per_beat();
+        let bar_start = self.frame as f64 - from * samples_per_beat;
+        let spread_frame = self.sample_rate as f64 / 1000.0;
+        let velocity = chord_velocity();
+        let notes = chord.midi_notes();
+        let queue = &mut self.notes;
+        self.comping.hits(
+            self.bar,
+            self.ticks_per_bar,
+            self.swing,
+            notes.len(),
+            |hit| {
+                if hit.at < from {
+                    return;
+                }
+                let start = (bar_start + hit.at * samples_per_beat) as u64;
+                let end = (bar_start + (hit.at + hit.length) * samples_per_beat) as u64;
+                let mut play = |key: u8, delay: u64| {
+                    queue.note_on(
+                        (start + delay).min(end),
+                        CHORD_CHANNEL,
+                        key as i32,
+                        velocity,
+                    );
+                    queue.note_off(end, CHORD_CHANNEL, key as i32);
+                };
+                match hit.note {
+                    Some(i) if !notes.is_empty() => play(notes[i % notes.len()], 0),
+                    Some(_) => {}
+                    None => {
+                        for (i, &key) in notes.iter().enumerate() {
+                            play(key, (i as f64 * hit.spread_ms * spread_frame) as u64);
+                        }
+                    }
+                }
+            },
+        );
+        self.sounding = Some(chord);
+    }
+
+    /// Queue the bass line from the current frame until the bar or the chord ends
+    fn play_bass_bar(&mut self, muted: bool) {
+        self.release_bass();
+        let (Some(bass), Some(chord)) = (self.bass, self.timeline.current().copied()) else {
+            return;
+        };
+        if muted {
+            return;
+        }
+
+        let next = self.timeline.next().copied();
+        let approaching = self.timeline.is_last_bar(self.bar);
+        // A chord sharing the bar gets a line of its own beats
+        let end_beat = self
+            .timeline
+            .next_change_in(self.bar)
+            .filter(|&beat| beat > self.beat)
+            .unwrap_or(self.ticks_per_bar);
+        let bar_start = self.frame;
+        let samples_per_beat = self.samples_per_beat();
+        let velocity =
+            ((BASS_VELOCITY as f32) * AUDIO_SETTINGS.get_bass_volume()).clamp(0.0, 127.0) as i32;
+        let queue = &mut self.notes;
+        bass.bar_notes(
+            chord.midi_notes(),
+            next.as_ref().map(|c| c.midi_notes()),
+            approaching,
+            end_beat - self.beat,
+            |at, length, key| {
+                let start = bar_start + (at * samples_per_beat) as u64;
+                // Leave a little air between consecutive notes
+                let end = bar_start + ((at + length * 0.95) * samples_per_beat) as u64;
+                queue.note_on(start, BASS_CHANNEL, key as i32, velocity);
+                queue.note_off(end, BASS_CHANNEL, key as i32);
+            },
+        );
+    }
+
+    /// Queue the drum hits of the beat starting on the current frame
+    fn play_drum_beat(&mut self, muted: bool) {
+        let Some(drums) = self.drums else {
+            return;
+        };
+        let fill = drums.fills && self.timeline.is_last_bar(self.bar);
+        let crash = self.beat == 0 && std::mem::take(&mut self.drum_fill_played);
+        if self.beat + 1 == self.ticks_per_bar {
+            self.drum_fill_played = fill;
+        }
+        if muted {
+            return;
+        }
+
+        let beat = self.beat as f64;
+        let beat_start = self.frame;
+        let samples_per_beat = self.samples_per_beat();
+        let volume = AUDIO_SETTINGS.get_drum_volume();
+        let queue = &mut self.notes;
+        drums.bar_hits(
+            self.bar,
+            self.ticks_per_bar,
+            self.swing,
+            fill,
+            crash,
+            |at, key, velocity| {
+                if at.floor() == beat {
+                    let frame = beat_start + ((at - beat) * samples_per_beat) as u64;
+                    let velocity = ((velocity as f32) * volume).clamp(0.0, 127.0) as i32;
+                    queue.note_on(frame, PERCUSSION_CHANNEL, key, velocity);
+                }
+            },
+        );
+    }
+
+    /// Select the part's bank and program on its channel
+    fn apply_instrument(&mut self, part: InstrumentPart) {
+        let Some(instrument) = self.instruments[part as usize] else {
+            return;
+        };
+        let channel = match part {
+            InstrumentPart::Chord => CHORD_CHANNEL,
+            InstrumentPart::Bass => BASS_CHANNEL,
+            InstrumentPart::Click => CLICK_CHANNEL,
+        };
+        self.synth.note_off_all_channel(channel, true);
+        self.synth.process_midi_message(
+            channel,
+            CONTROL_CHANGE,
+            BANK_SELECT,
+            instrument.bank as i32,
+        );
+        self.synth
+            .process_midi_message(channel, PROGRAM_CHANGE, instrument.program as i32, 0);
+    }
+
+    fn release_bass(&mut self) {
+        self.notes.clear_channel(BASS_CHANNEL);
+        self.synth.note_off_all_channel(BASS_CHANNEL, false);
+    }
+
+    /// Stop every chord note, including the ones still queued
+    fn release_chord(&mut self) {
+        self.notes.clear_channel(CHORD_CHANNEL);
+        if let Some(chord) = self.sounding.take() {
+            for &note in chord.midi_notes() {
+                self.synth.note_off(CHORD_CHANNEL, note as i32);
+            }
+        }
+    }
+
+    /// A bar just finished: tempo changes only ever happen here
+    fn on_bar_complete(&mut self, in_count_in: bool) {
+        if in_count_in {
+            self.is_count_in = false;
+            return;
+        }
+
+        let cycle_complete = self.timeline.is_last_bar(self.bar);
+        self.bar += 1;
+
+        if let Some(gap) = self.gap_click.as_mut() {
+            gap.on_bar_complete();
+        }
+
+        let bar_seconds = 60.0 / self.bpm as f64 * self.ticks_per_bar as f64;
+        let action = self
+            .tempo_ramp
+            .as_mut()
+            .map(|ramp| ramp.on_bar_complete(bar_seconds, cycle_complete));
+        match action {
+            Some(RampAction::Play(bpm)) => self.set_bpm(bpm),
+            Some(RampAction::Stop) => {
+                self.is_playing = false;
+                let _ = self.events.try_send(AudioEvent::Stopped);
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        fs::File,
+        sync::{Arc, LazyLock},
+    };
+
+    use rustysynth::{SoundFont, Synthesizer, SynthesizerSettings};
+
+    use super::*;
+    use crate::audio::{
+        articulation::ArticulationStyle, bass::BassLine, builtin::BuiltinSynth, drums::DrumGroove,
+        gap::GapClick, soundfont::Instrument, tempo::TempoRamp,
+    };
+
+    const SAMPLE_RATE: u32 = 44_100;
+    const BLOCK_FRAMES: usize = 256;
+
+    thread_local! {
+        static COUNTING: Cell<bool> = const { Cell::new(false) };
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// System allocator that counts calls made by a thread while it is counting
+    struct CountingAllocator;
+
+    fn count() {
+        let _ = COUNTING.try_with(|counting| {
+            if counting.get() {
+                let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+            }
+        });
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count();
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            count();
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count();
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Number of allocations and deallocations `f` makes on the current thread
+    fn allocations_in(f: impl FnOnce()) -> usize {
+        ALLOCATIONS.with(|a| a.set(0));
+        COUNTING.with(|c| c.set(true));
+        f();
+        COUNTING.with(|c| c.set(false));
+        ALLOCATIONS.with(|a| a.get())
+    }
+
+    fn sound_font_engine() -> Engine {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/TimGM6mb.sf2");
+        let sound_font = Arc::new(SoundFont::new(&mut File::open(path).unwrap()).unwrap());
+        let settings = SynthesizerSettings::new(SAMPLE_RATE as i32);
+        let synth = Synthesizer::new(&sound_font, &settings).unwrap();
+        Engine::new(SoundSource::SoundFont(synth), SAMPLE_RATE)
+    }
+
+    #[test]
+    fn test_callback_does_not_allocate() {
+        assert_callback_does_not_allocate(sound_font_engine());
+    }
+
+    #[test]
+    fn test_builtin_callback_does_not_allocate() {
+        let synth = BuiltinSynth::new(SAMPLE_RATE);
+        assert_callback_does_not_allocate(Engine::new(SoundSource::Builtin(synth), SAMPLE_RATE));
+    }
+
+    fn assert_callback_does_not_allocate(mut engine: Engine) {
+        let mut sample = Some(vec![0.5; 2_000].into_boxed_slice());
+        let mut left = [0.0; BLOCK_FRAMES];
+        let mut right = [0.0; BLOCK_FRAMES];
+        // The statics are initialised once, before the stream starts
+        LazyLock::force(&AUDIO_EVT);
+        LazyLock::force(&AUDIO_SETTINGS);
+
+        let allocations = allocations_in(|| {
+            engine.handle_command(AudioCommand::SetBPM(240));
+            engine.handle_command(AudioCommand::SetClickSample(
+                ClickSound::Accent,
+                sample.take(),
+            ));
+            engine.handle_command(AudioCommand::SetInstrument(
+                InstrumentPart::Click,
+                Instrument {
+                    bank: 0,
+                    program: 115,
+                },
+            ));
+            engine.handle_command(AudioCommand::SetSubdivision(2));
+            engine.handle_command(AudioCommand::SetTempoRamp(Some(TempoRamp::default())));
+            engine.handle_command(AudioCommand::SetGapClick(Some(GapClick::default())));
+            engine.handle_command(AudioCommand::SetArticulation(ChordArticulation {
+                style: ArticulationStyle::Staccato,
+                ..ChordArticulation::default()
+            }));
+            for index in 0..8 {
+                let chord =
+                    ScheduledChord::new(index, index, (index % 2) as u8, 1, &[60, 64, 67, 71]);
+                engine.handle_command(AudioCommand::ScheduleChord(chord));
+            }
+            engine.handle_command(AudioCommand::StartWithCountIn);
+            engine.handle_command(AudioCommand::SetSwing(67));
+            engine.handle_command(AudioCommand::SetBass(Some(BassLine::default())));
+            engine.handle_command(AudioCommand::SetDrums(Some(DrumGroove {
+                fills: true,
+                ..DrumGroove::default()
+            })));
+
+            // Several bars of clicks, subdivisions and chord changes
+            for _ in 0..SAMPLE_RATE as usize * 8 / BLOCK_FRAMES {
+                engine.render(&mut left, &mut right);
+            }
+
+            engine.handle_command(AudioCommand::SetComping(CompingPattern::Strum));
+            engine.handle_command(AudioCommand::SetTimeSignature(3));
+            for _ in 0..SAMPLE_RATE as usize * 4 / BLOCK_FRAMES {
+                engine.render(&mut left, &mut right);
+            }
+
+            engine.handle_command(AudioCommand::DropChordsAfter(4));
+            engine.handle_command(AudioCommand::Stop);
+            engine.handle_command(AudioCommand::Restart);
+            engine.render(&mut left, &mut right);
+        });
+
+        assert_eq!(allocations, 0);
+    }
+}
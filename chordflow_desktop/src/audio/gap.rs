@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+
+/// Configuration for silencing the metronome to train internal time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GapClick {
+    /// Bars that are played before a gap
+    pub play_bars: u8,
+    /// Bars that are muted after the played bars, 0 disables bar gaps
+    pub mute_bars: u8,
+    /// Beats that are muted in every bar, bit 0 is the first beat
+    pub muted_beats: u16,
+    /// Chance in percent for any remaining beat to be muted
+    pub random_mute_percent: u8,
+    /// Silence the chords together with the click
+    pub mute_chords: bool,
+}
+
+impl Default for GapClick {
+    fn default() -> Self {
+        Self {
+            play_bars: 2,
+            mute_bars: 2,
+            muted_beats: 0,
+            random_mute_percent: 0,
+            mute_chords: false,
+        }
+    }
+}
+
+impl GapClick {
+    pub fn is_beat_selected(&self, beat: u8) -> bool {
+        beat < 16 && self.muted_beats & (1 << beat) != 0
+    }
+
+    pub fn toggle_beat(&mut self, beat: u8) {
+        if beat < 16 {
+            self.muted_beats ^= 1 << beat;
+        }
+    }
+
+    /// Whether the bar with this index (counted from the start of playback) is silent
+    pub fn is_bar_muted(&self, bar: u32) -> bool {
+        if self.mute_bars == 0 {
+            return false;
+        }
+        let period = self.play_bars as u32 + self.mute_bars as u32;
+        bar % period >= self.play_bars as u32
+    }
+}
+
+/// Running gap-click state, advanced by the audio engine
+#[derive(Debug, Clone, PartialEq)]
+pub struct GapClickState {
+    pub config: GapClick,
+    bar: u32,
+    beat_muted: bool,
+    rng: u32,
+}
+
+impl GapClickState {
+    pub fn new(config: GapClick) -> Self {
+        Self {
+            config,
+            bar: 0,
+            beat_muted: false,
+            rng: 0x9E37_79B9,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.bar = 0;
+        self.beat_muted = false;
+    }
+
+    /// Decide whether the main beat that is about to sound is muted.
+    /// Subdivisions of that beat follow the same decision through [`Self::is_muted`].
+    pub fn on_beat(&mut self, beat_in_bar: u8) -> bool {
+        self.beat_muted = self.config.is_bar_muted(self.bar)
+            || self.config.is_beat_selected(beat_in_bar)
+            || self.roll() < self.config.random_mute_percent as u32;
+        self.beat_muted
+    }
+
+    pub fn on_bar_complete(&mut self) {
+        self.bar = self.bar.wrapping_add(1);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.beat_muted
+    }
+
+    /// Xorshift so the audio thread never touches a thread-local RNG
+    fn roll(&mut self) -> u32 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        x % 100
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_pattern() {
+        let gap = GapClick {
+            play_bars: 2,
+            mute_bars: 1,
+            ..Default::default()
+        };
+        let muted: Vec<bool> = (0..6).map(|b| gap.is_bar_muted(b)).collect();
+        assert_eq!(muted, [false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn test_muted_beats_follow_bars() {
+        let mut gap = GapClick {
+            mute_bars: 0,
+            ..Default::default()
+        };
+        gap.toggle_beat(1);
+        gap.toggle_beat(3);
+        let mut state = GapClickState::new(gap);
+        let bar: Vec<bool> = (0..4).map(|b| state.on_beat(b)).collect();
+        assert_eq!(bar, [false, true, false, true]);
+        state.on_bar_complete();
+        assert!(!state.on_beat(0));
+        assert!(!state.is_muted());
+    }
+
+    #[test]
+    fn test_random_mute_extremes() {
+        let mut always = GapClickState::new(GapClick {
+            mute_bars: 0,
+            random_mute_percent: 100,
+            ..Default::default()
+        });
+        let mut never = GapClickState::new(GapClick {
+            mute_bars: 0,
+            random_mute_percent: 0,
+            ..Default::default()
+        });
+        assert!((0..32).all(|b| always.on_beat(b % 4)));
+        assert!((0..32).all(|b| !never.on_beat(b % 4)));
+    }
+}
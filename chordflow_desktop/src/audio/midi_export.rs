@@ -0,0 +1,389 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use midly::{
+    num::{u15, u24, u28, u4, u7},
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, Track, TrackEvent, TrackEventKind,
+};
+use strum::IntoEnumIterator;
+
+use crate::{
+    audio::{
+        engine::{BASS_CHANNEL, CHORD_CHANNEL, CLICK_CHANNEL, PERCUSSION_CHANNEL},
+        recorder::{NoteRecorder, RecordedKind, RecordedMessage},
+        render::{OfflineRenderer, RenderLength},
+        soundfont::{InstrumentPart, SOUND_FONT},
+        source::SoundSource,
+    },
+    state::schedule::PracticeChord,
+    AudioCommand, AudioEvent,
+};
+
+/// Ticks per quarter note of the exported file
+const PPQ: u16 = 480;
+/// The recording only keeps time, any rate will do
+const RECORD_RATE: u32 = 48_000;
+/// Percussion has no note-off from the engine, in the file it lasts a 32nd
+const PERCUSSION_TICKS: u32 = PPQ as u32 / 8;
+/// Beat length assumed when the session is too short to measure one, 120 bpm
+const DEFAULT_BEAT_FRAMES: u64 = RECORD_RATE as u64 / 2;
+/// MIDI channel General MIDI plays percussion on
+const GM_PERCUSSION_CHANNEL: u8 = 9;
+
+/// Tracks of the exported file and the engine channel each one records
+const TRACKS: [(&str, i32); 4] = [
+    ("Click", CLICK_CHANNEL),
+    ("Chords", CHORD_CHANNEL),
+    ("Bass", BASS_CHANNEL),
+    ("Drums", PERCUSSION_CHANNEL),
+];
+
+/// Order of events on the same tick: meta first, a note ends before it is struck again
+fn event_order(kind: &TrackEventKind) -> u8 {
+    match kind {
+        TrackEventKind::Meta(_) => 0,
+        TrackEventKind::Midi {
+            message: MidiMessage::NoteOff { .. },
+            ..
+        } => 2,
+        TrackEventKind::Midi {
+            message: MidiMessage::NoteOn { .. },
+            ..
+        } => 3,
+        _ => 1,
+    }
+}
+
+/// What the engine played in a session that was rendered for export
+pub struct Recording {
+    pub beats_per_bar: u8,
+    pub messages: Vec<RecordedMessage>,
+    /// Frame of every main beat
+    pub beats: Vec<u64>,
+    /// Frame each chord started on and its name
+    pub chords: Vec<(u64, String)>,
+}
+
+impl Recording {
+    /// Play a session through the engine, writing down its notes instead of sounding them.
+    /// The count-in is left out, the file starts on the first chord.
+    pub fn record(
+        length: RenderLength,
+        commands: Vec<AudioCommand>,
+        beats_per_bar: u8,
+        mut next_chord: impl FnMut() -> Option<PracticeChord>,
+    ) -> Recording {
+        let recorder = SoundSource::Recorder(NoteRecorder::default());
+        let mut renderer = OfflineRenderer::new(recorder, RECORD_RATE);
+        let instruments = SOUND_FONT.lock().settings.clone();
+        for part in InstrumentPart::iter() {
+            renderer.send(AudioCommand::SetInstrument(
+                part,
+                instruments.instrument(part),
+            ));
+        }
+        for command in commands {
+            renderer.send(command);
+        }
+
+        let mut names = Vec::new();
+        let rendered = renderer.render_session(length, false, || {
+            let chord = next_chord()?;
+            names.push(chord.name.clone());
+            Some(chord.to_scheduled())
+        });
+        let messages = match renderer.into_source() {
+            SoundSource::Recorder(recorder) => recorder.finish(),
+            _ => Vec::new(),
+        };
+
+        let mut beats = Vec::new();
+        let mut chords = Vec::new();
+        for event in rendered.events {
+            match event {
+                AudioEvent::Tick { sample, .. } => beats.push(sample),
+                AudioEvent::ChordChanged { sample, index } => {
+                    let name = names.get(index as usize).cloned().unwrap_or_default();
+                    chords.push((sample, name));
+                }
+                _ => {}
+            }
+        }
+        Recording {
+            beats_per_bar,
+            messages,
+            beats,
+            chords,
+        }
+    }
+
+    fn beat_frames(&self, beat: usize) -> u64 {
+        match (self.beats.get(beat), self.beats.get(beat + 1)) {
+            (Some(start), Some(end)) => end - start,
+            // Past the last beat the tempo stays what it was
+            _ if self.beats.len() >= 2 => {
+                let last = self.beats.len() - 1;
+                self.beats[last] - self.beats[last - 1]
+            }
+            _ => DEFAULT_BEAT_FRAMES,
+        }
+        .max(1)
+    }
+
+    /// Position of a frame in ticks. The beats the engine clicked are the grid, so tempo
+    /// changes and rounding to whole frames never make the notes drift off the bar lines.
+    fn ticks(&self, frame: u64) -> u32 {
+        let beat = self.beats.partition_point(|&b| b <= frame);
+        let (beat, start) = match beat.checked_sub(1) {
+            Some(beat) => (beat, self.beats[beat]),
+            None => (0, 0),
+        };
+        let fraction = (frame - start) as f64 / self.beat_frames(beat) as f64;
+        ((beat as f64 + fraction) * PPQ as f64).round() as u32
+    }
+
+    /// Tempo at every beat where it changes, in microseconds per quarter note
+    fn tempo_changes(&self) -> Vec<(u32, u32)> {
+        let mut changes: Vec<(u32, u32)> = Vec::new();
+        for beat in 0..self.beats.len().max(1) {
+            // The engine's tempo is whole beats per minute
+            let bpm = (60.0 * RECORD_RATE as f64 / self.beat_frames(beat) as f64).round();
+            let micros = (60_000_000.0 / bpm.max(1.0)) as u32;
+            if changes.last().is_none_or(|&(_, last)| last != micros) {
+                changes.push((beat as u32 * PPQ as u32, micros));
+            }
+        }
+        changes
+    }
+
+    /// The MIDI events of one engine channel on the channel they are written to
+    fn channel_events(&self, engine_channel: i32) -> Vec<(u32, TrackEventKind<'_>)> {
+        let messages = self
+            .messages
+            .iter()
+            .filter(|m| m.channel as i32 == engine_channel);
+        // A percussive click goes on the General MIDI drum channel, where it needs no kit
+        let click_on_drums = engine_channel == CLICK_CHANNEL
+            && messages.clone().any(|m| {
+                matches!(
+                    m.kind,
+                    RecordedKind::NoteOn {
+                        percussion: true,
+                        ..
+                    }
+                )
+            });
+        let channel = u4::new(if click_on_drums {
+            GM_PERCUSSION_CHANNEL
+        } else {
+            engine_channel as u8
+        });
+
+        let mut events = Vec::new();
+        for message in messages {
+            let tick = self.ticks(message.frame);
+            let midi = |message| TrackEventKind::Midi { channel, message };
+            match message.kind {
+                RecordedKind::NoteOn {
+                    key,
+                    velocity,
+                    percussion,
+                } => {
+                    let (key, vel) = (u7::new(key), u7::new(velocity));
+                    events.push((tick, midi(MidiMessage::NoteOn { key, vel })));
+                    if percussion {
+                        let off = MidiMessage::NoteOff {
+                            key,
+                            vel: u7::new(0),
+                        };
+                        events.push((tick + PERCUSSION_TICKS, midi(off)));
+                    }
+                }
+                RecordedKind::NoteOff { key } => {
+                    let off = MidiMessage::NoteOff {
+                        key: u7::new(key),
+                        vel: u7::new(0),
+                    };
+                    events.push((tick, midi(off)));
+                }
+                _ if click_on_drums => {}
+                RecordedKind::Controller { controller, value } => {
+                    let message = MidiMessage::Controller {
+                        controller: u7::new(controller),
+                        value: u7::new(value),
+                    };
+                    events.push((tick, midi(message)));
+                }
+                RecordedKind::Program(program) => {
+                    let message = MidiMessage::ProgramChange {
+                        program: u7::new(program),
+                    };
+                    events.push((tick, midi(message)));
+                }
+            }
+        }
+        events
+    }
+
+    /// A multi-track file: tempo, time signature and chord markers first, then a track
+    /// per part that played
+    pub fn to_smf(&self) -> Smf<'_> {
+        let mut smf = Smf::new(Header::new(
+            Format::Parallel,
+            Timing::Metrical(u15::new(PPQ)),
+        ));
+
+        let mut conductor = vec![(
+            0,
+            TrackEventKind::Meta(MetaMessage::TimeSignature(self.beats_per_bar, 2, 24, 8)),
+        )];
+        for (tick, micros) in self.tempo_changes() {
+            conductor.push((
+                tick,
+                TrackEventKind::Meta(MetaMessage::Tempo(u24::new(micros))),
+            ));
+        }
+        let chord_names: Vec<(u32, &[u8])> = self
+            .chords
+            .iter()
+            .map(|(frame, name)| (self.ticks(*frame), name.as_bytes()))
+            .collect();
+        for &(tick, name) in &chord_names {
+            conductor.push((tick, TrackEventKind::Meta(MetaMessage::Marker(name))));
+        }
+        smf.tracks.push(track("ChordFlow", conductor));
+
+        for (name, engine_channel) in TRACKS {
+            let mut events = self.channel_events(engine_channel);
+            let has_notes = events.iter().any(|(_, kind)| event_order(kind) == 3);
+            if engine_channel == CHORD_CHANNEL {
+                // Chord names as text too, for editors that only show a track's own events
+                for &(tick, name) in &chord_names {
+                    events.push((tick, TrackEventKind::Meta(MetaMessage::Text(name))));
+                }
+            } else if !has_notes {
+                continue;
+            }
+            smf.tracks.push(track(name, events));
+        }
+        smf
+    }
+}
+
+/// Events at absolute ticks to a named track with delta times
+fn track<'a>(name: &'a str, mut events: Vec<(u32, TrackEventKind<'a>)>) -> Track<'a> {
+    events.sort_by_key(|(tick, kind)| (*tick, event_order(kind)));
+    let mut track = vec![TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::TrackName(name.as_bytes())),
+    }];
+    let mut previous = 0;
+    for (tick, kind) in events {
+        track.push(TrackEvent {
+            delta: u28::new(tick - previous),
+            kind,
+        });
+        previous = tick;
+    }
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+    track
+}
+
+/// Record a session and save it as a Standard MIDI File
+pub fn export_midi(
+    path: &Path,
+    length: RenderLength,
+    commands: Vec<AudioCommand>,
+    beats_per_bar: u8,
+    next_chord: impl FnMut() -> Option<PracticeChord>,
+) -> Result<()> {
+    let recording = Recording::record(length, commands, beats_per_bar, next_chord);
+    recording
+        .to_smf()
+        .save(path)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::schedule::{ChordLength, PracticeSchedule};
+
+    fn count_notes(track: &Track) -> usize {
+        track
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.kind,
+                    TrackEventKind::Midi {
+                        message: MidiMessage::NoteOn { .. },
+                        ..
+                    }
+                )
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_exported_file_has_tempo_markers_and_parts() {
+        let mut schedule = PracticeSchedule::default();
+        let mut chords = [
+            ("Cmaj7", vec![60, 64, 67, 71]),
+            ("Dm7", vec![62, 65, 69, 72]),
+        ]
+        .into_iter()
+        .map(|(name, notes)| {
+            schedule
+                .push(name.to_string(), notes, ChordLength::Bars(1), 3, None)
+                .clone()
+        })
+        .collect::<Vec<_>>()
+        .into_iter();
+        let commands = vec![
+            AudioCommand::SetBPM(90),
+            AudioCommand::SetTimeSignature(3),
+            AudioCommand::SetSubdivision(2),
+        ];
+        let recording = Recording::record(RenderLength::Cycles(1), commands, 3, || chords.next());
+
+        let mut bytes = Vec::new();
+        recording.to_smf().write_std(&mut bytes).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        // Conductor, click and chords, nothing for the parts that did not play
+        assert_eq!(smf.tracks.len(), 3);
+
+        let conductor = &smf.tracks[0];
+        assert!(conductor.iter().any(|e| matches!(
+            e.kind,
+            TrackEventKind::Meta(MetaMessage::TimeSignature(3, 2, _, _))
+        )));
+        let tempos: Vec<u32> = conductor
+            .iter()
+            .filter_map(|e| match e.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(micros)) => Some(micros.as_int()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tempos, [666_666]);
+
+        let mut tick = 0;
+        let mut markers = Vec::new();
+        for event in conductor.iter() {
+            tick += event.delta.as_int();
+            if let TrackEventKind::Meta(MetaMessage::Marker(name)) = event.kind {
+                markers.push((tick, String::from_utf8_lossy(name).to_string()));
+            }
+        }
+        assert_eq!(
+            markers,
+            [(0, "Cmaj7".to_string()), (3 * 480, "Dm7".to_string())]
+        );
+
+        // Two bars of 3/4 in eighths, and two chords of four notes
+        assert_eq!(count_notes(&smf.tracks[1]), 12);
+        assert_eq!(count_notes(&smf.tracks[2]), 8);
+    }
+}
@@ -1,2 +1,19 @@
+pub mod articulation;
+pub mod bass;
+pub mod builtin;
+pub mod comping;
+pub mod drums;
+pub mod engine;
+pub mod gap;
+pub mod midi_export;
+pub mod note_queue;
+pub mod output;
+pub mod recorder;
+pub mod render;
+pub mod samples;
 pub mod settings;
+pub mod soundfont;
+pub mod source;
 pub mod stream;
+pub mod tempo;
+pub mod timeline;
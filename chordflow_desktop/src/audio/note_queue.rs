@@ -0,0 +1,118 @@
+/// Note events the audio thread can hold ahead of time without allocating
+const QUEUE_CAPACITY: usize = 256;
+
+/// A note-on or note-off due on a specific stream frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteEvent {
+    pub frame: u64,
+    pub channel: i32,
+    pub key: i32,
+    /// 0 is a note-off
+    pub velocity: i32,
+}
+
+/// Fixed-capacity queue of future note events, popped in frame order.
+/// At equal frames note-offs come first so a repeated note is not cut short.
+#[derive(Debug)]
+pub struct NoteQueue {
+    events: Vec<NoteEvent>,
+}
+
+impl Default for NoteQueue {
+    fn default() -> Self {
+        Self {
+            events: Vec::with_capacity(QUEUE_CAPACITY),
+        }
+    }
+}
+
+impl NoteQueue {
+    /// Queue an event without growing the queue. When it is full a note-on is dropped,
+    /// while a note-off takes the place of the last note-on due so no note is left hanging.
+    pub fn push(&mut self, event: NoteEvent) {
+        if self.events.len() >= QUEUE_CAPACITY {
+            if event.velocity > 0 {
+                return;
+            }
+            let Some((index, _)) = self
+                .events
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| e.velocity > 0)
+                .max_by_key(|(_, e)| e.frame)
+            else {
+                return;
+            };
+            self.events.swap_remove(index);
+        }
+        self.events.push(event);
+    }
+
+    pub fn note_on(&mut self, frame: u64, channel: i32, key: i32, velocity: i32) {
+        self.push(NoteEvent {
+            frame,
+            channel,
+            key,
+            velocity,
+        });
+    }
+
+    pub fn note_off(&mut self, frame: u64, channel: i32, key: i32) {
+        self.note_on(frame, channel, key, 0);
+    }
+
+    /// Frame of the earliest queued event
+    pub fn next_frame(&self) -> Option<u64> {
+        self.events.iter().map(|e| e.frame).min()
+    }
+
+    /// Remove and return the earliest event due on or before `frame`
+    pub fn pop_due(&mut self, frame: u64) -> Option<NoteEvent> {
+        let (index, _) = self
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.frame <= frame)
+            .min_by_key(|(_, e)| (e.frame, e.velocity > 0))?;
+        Some(self.events.swap_remove(index))
+    }
+
+    /// Forget everything pending on a channel
+    pub fn clear_channel(&mut self, channel: i32) {
+        self.events.retain(|e| e.channel != channel);
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_offs_kept_when_full() {
+        let mut queue = NoteQueue::default();
+        for frame in 0..QUEUE_CAPACITY as u64 {
+            queue.note_on(frame, 0, 60, 100);
+        }
+        queue.note_on(0, 1, 40, 100);
+        queue.note_off(10, 0, 60);
+
+        let mut events = Vec::new();
+        while let Some(event) = queue.pop_due(u64::MAX) {
+            events.push(event);
+        }
+        assert_eq!(events.len(), QUEUE_CAPACITY);
+        assert!(events.iter().all(|e| e.channel == 0));
+        assert!(events.contains(&NoteEvent {
+            frame: 10,
+            channel: 0,
+            key: 60,
+            velocity: 0
+        }));
+        // The last note-on due made room for the note-off
+        assert_eq!(events.last().unwrap().frame, QUEUE_CAPACITY as u64 - 2);
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    sync::{
+        atomic::{AtomicI32, AtomicU32, Ordering},
+        LazyLock,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use cpal::{
+    traits::{DeviceTrait, HostTrait},
+    BufferSize, Device, Host, SampleRate, StreamConfig, SupportedBufferSize,
+};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+const SETTINGS_FILE: &str = "output.toml";
+/// Rates offered in the picker when a device supports a range of them
+const COMMON_SAMPLE_RATES: [u32; 6] = [22_050, 44_100, 48_000, 88_200, 96_000, 192_000];
+/// Buffer sizes in frames offered in the picker, within what the device supports
+pub const BUFFER_SIZES: [u32; 8] = [32, 64, 128, 256, 512, 1024, 2048, 4096];
+/// Largest audio/visual offset the calibration may set, either way
+pub const MAX_AV_OFFSET_MS: i32 = 500;
+
+/// Output chosen in the settings, remembered between sessions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputSettings {
+    /// Audio host, e.g. CoreAudio, ALSA or JACK; `None` is the system default
+    pub host: Option<String>,
+    /// Device name, `None` is the host's default output
+    pub device: Option<String>,
+    /// `None` plays at the device's default rate
+    pub sample_rate: Option<u32>,
+    /// Frames per callback, `None` leaves it to the driver. Smaller is less latency
+    /// but more CPU and a higher risk of dropouts.
+    pub buffer_size: Option<u32>,
+    /// Milliseconds the beat display waits on top of the reported latency, for outputs
+    /// that add their own delay like Bluetooth headphones. Set by the tap-along test.
+    pub av_offset_ms: i32,
+}
+
+/// An output device offered in the picker
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputDevice {
+    pub host: String,
+    pub name: String,
+    pub sample_rates: Vec<u32>,
+    /// Smallest and largest buffer in frames, when the driver reports them
+    pub buffer_sizes: Option<(u32, u32)>,
+}
+
+/// The output the stream is playing on
+#[derive(Debug, Clone, Default)]
+pub struct OutputStatus {
+    pub settings: OutputSettings,
+    pub host: String,
+    pub device: String,
+    pub sample_rate: u32,
+    /// Frames per callback when the buffer size was set, else the driver's choice
+    pub buffer_size: Option<u32>,
+    /// Why the stream is not playing on the chosen output
+    pub error: Option<String>,
+}
+
+pub static OUTPUT: LazyLock<Mutex<OutputStatus>> = LazyLock::new(|| {
+    let settings: OutputSettings = config::load(SETTINGS_FILE);
+    AV_OFFSET_MS.store(settings.av_offset_ms, Ordering::Relaxed);
+    Mutex::new(OutputStatus {
+        settings,
+        ..OutputStatus::default()
+    })
+});
+
+/// Time from the callback rendering a frame until it is heard, as the stream reports it
+static LATENCY_MICROS: AtomicU32 = AtomicU32::new(0);
+/// Copy of [`OutputSettings::av_offset_ms`] that the UI reads without locking
+static AV_OFFSET_MS: AtomicI32 = AtomicI32::new(0);
+
+/// Called from the audio callback, must not block
+pub fn record_latency(latency: Duration) {
+    let micros = latency.as_micros().min(u32::MAX as u128) as u32;
+    LATENCY_MICROS.store(micros, Ordering::Relaxed);
+}
+
+/// Output latency of the running stream
+pub fn output_latency() -> Duration {
+    Duration::from_micros(LATENCY_MICROS.load(Ordering::Relaxed) as u64)
+}
+
+/// How long the UI holds a beat back so it is shown when it is heard
+pub fn display_delay() -> Duration {
+    let offset = AV_OFFSET_MS.load(Ordering::Relaxed);
+    let delay = output_latency().as_millis() as i64 + offset as i64;
+    Duration::from_millis(delay.max(0) as u64)
+}
+
+/// Change the audio/visual offset without touching the stream
+pub fn set_av_offset(ms: i32) {
+    let ms = ms.clamp(-MAX_AV_OFFSET_MS, MAX_AV_OFFSET_MS);
+    AV_OFFSET_MS.store(ms, Ordering::Relaxed);
+    let mut status = OUTPUT.lock();
+    status.settings.av_offset_ms = ms;
+    if let Err(e) = config::save(SETTINGS_FILE, &status.settings) {
+        log::warn!("Failed to save output settings: {}", e);
+    }
+}
+
+/// Why the audio thread has to rebuild the stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputRequest {
+    /// Another output was chosen
+    Switch,
+    /// The stream's device disappeared
+    DeviceLost,
+}
+
+pub static OUTPUT_REQUEST: LazyLock<(Sender<OutputRequest>, Receiver<OutputRequest>)> =
+    LazyLock::new(|| bounded(1));
+
+/// Play on another output and remember the choice. The audio/visual offset is kept,
+/// it is changed with [`set_av_offset`].
+pub fn set_output(settings: OutputSettings) {
+    let mut status = OUTPUT.lock();
+    status.settings = OutputSettings {
+        av_offset_ms: status.settings.av_offset_ms,
+        ..settings
+    };
+    if let Err(e) = config::save(SETTINGS_FILE, &status.settings) {
+        log::warn!("Failed to save output settings: {}", e);
+    }
+    let _ = OUTPUT_REQUEST.0.try_send(OutputRequest::Switch);
+}
+
+fn host_by_name(name: &str) -> Option<Host> {
+    cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name() == name)
+        .and_then(|id| cpal::host_from_id(id).ok())
+}
+
+fn describe(host: &Host, device: &Device) -> Option<OutputDevice> {
+    let name = device.name().ok()?;
+    let mut sample_rates = Vec::new();
+    let mut buffer_sizes: Option<(u32, u32)> = None;
+    for range in device.supported_output_configs().ok()? {
+        let (min, max) = (range.min_sample_rate().0, range.max_sample_rate().0);
+        for rate in COMMON_SAMPLE_RATES.into_iter().chain([min, max]) {
+            if (min..=max).contains(&rate) && !sample_rates.contains(&rate) {
+                sample_rates.push(rate);
+            }
+        }
+        if let SupportedBufferSize::Range { min, max } = *range.buffer_size() {
+            buffer_sizes = Some(match buffer_sizes {
+                Some((low, high)) => (low.min(min), high.max(max)),
+                None => (min, max),
+            });
+        }
+    }
+    sample_rates.sort_unstable();
+    Some(OutputDevice {
+        host: host.id().name().to_string(),
+        name,
+        sample_rates,
+        buffer_sizes,
+    })
+}
+
+/// Every output device of every available host
+pub fn list_output_devices() -> Vec<OutputDevice> {
+    let mut devices = Vec::new();
+    for id in cpal::available_hosts() {
+        let Ok(host) = cpal::host_from_id(id) else {
+            continue;
+        };
+        let Ok(outputs) = host.output_devices() else {
+            continue;
+        };
+        devices.extend(outputs.filter_map(|device| describe(&host, &device)));
+    }
+    devices
+}
+
+/// An output ready to build a stream on
+pub struct OpenedOutput {
+    pub host: String,
+    pub device: Device,
+    pub config: StreamConfig,
+    /// Why this is not the chosen output
+    pub fallback: Option<String>,
+}
+
+/// Device and stream configuration for the chosen output. When the chosen device or rate
+/// is not available the default device or rate is used and the reason is kept with it.
+pub fn open_output(settings: &OutputSettings) -> Result<OpenedOutput> {
+    let mut fallback = None;
+    let chosen_host = settings.host.as_deref().and_then(|name| {
+        let host = host_by_name(name);
+        if host.is_none() {
+            fallback = Some(format!("{} is not available", name));
+        }
+        host
+    });
+    let host = chosen_host.unwrap_or_else(cpal::default_host);
+
+    let chosen_device = settings.device.as_deref().and_then(|name| {
+        let device = host
+            .output_devices()
+            .ok()?
+            .find(|d| d.name().is_ok_and(|n| n == name));
+        if device.is_none() {
+            fallback = Some(format!("{} is not connected", name));
+        }
+        device
+    });
+    let device = match chosen_device {
+        Some(device) => device,
+        None => host
+            .default_output_device()
+            .context("No default output device")?,
+    };
+
+    // Start from the default output configuration so we match the device's expectations
+    let mut config = device.default_output_config()?.config();
+    if let Some(rate) = settings.sample_rate {
+        let supported = device.supported_output_configs()?.any(|range| {
+            range.channels() == config.channels
+                && (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&rate)
+        });
+        if supported {
+            config.sample_rate = SampleRate(rate);
+        } else if fallback.is_none() {
+            fallback = Some(format!("{} Hz is not supported by this device", rate));
+        }
+    }
+    if let Some(frames) = settings.buffer_size {
+        // A size the driver does not accept would fail the stream, so only sizes a
+        // configuration of the channels and rate reports are requested
+        let rate = config.sample_rate.0;
+        let supported = device.supported_output_configs()?.any(|range| {
+            range.channels() == config.channels
+                && (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&rate)
+                && match *range.buffer_size() {
+                    SupportedBufferSize::Range { min, max } => (min..=max).contains(&frames),
+                    SupportedBufferSize::Unknown => false,
+                }
+        });
+        if supported {
+            config.buffer_size = BufferSize::Fixed(frames);
+        } else if fallback.is_none() {
+            fallback = Some(format!("A buffer of {} frames is not supported", frames));
+        }
+    }
+
+    let fallback = fallback.map(|reason| {
+        let name = device
+            .name()
+            .unwrap_or_else(|_| "the default device".to_string());
+        format!("{}, playing on {} instead.", reason, name)
+    });
+    Ok(OpenedOutput {
+        host: host.id().name().to_string(),
+        device,
+        config,
+        fallback,
+    })
+}
@@ -0,0 +1,190 @@
+const MIDI_CHANNELS: usize = 16;
+/// Percussion channel of General MIDI (channel 10)
+const GM_PERCUSSION_CHANNEL: i32 = 9;
+
+const CONTROL_CHANGE: i32 = 0xB0;
+const BANK_SELECT: i32 = 0x00;
+const PROGRAM_CHANGE: i32 = 0xC0;
+
+/// A MIDI message the engine sent, on the frame it sent it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedMessage {
+    pub frame: u64,
+    pub channel: u8,
+    pub kind: RecordedKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedKind {
+    /// `percussion` notes get no note-off from the engine, they ring out
+    NoteOn {
+        key: u8,
+        velocity: u8,
+        percussion: bool,
+    },
+    NoteOff {
+        key: u8,
+    },
+    Controller {
+        controller: u8,
+        value: u8,
+    },
+    Program(u8),
+}
+
+/// Sound source that writes down what it is asked to play instead of playing it,
+/// for exporting a session as MIDI. Every note-on gets a matching note-off.
+#[derive(Debug, Default)]
+pub struct NoteRecorder {
+    frame: u64,
+    banks: [i32; MIDI_CHANNELS],
+    /// Held keys per channel, one bit per key
+    sounding: [u128; MIDI_CHANNELS],
+    messages: Vec<RecordedMessage>,
+}
+
+impl NoteRecorder {
+    fn push(&mut self, channel: i32, kind: RecordedKind) {
+        self.messages.push(RecordedMessage {
+            frame: self.frame,
+            channel: channel as u8,
+            kind,
+        });
+    }
+
+    fn channel(channel: i32) -> Option<usize> {
+        usize::try_from(channel).ok().filter(|&c| c < MIDI_CHANNELS)
+    }
+
+    pub fn note_on(&mut self, channel: i32, key: i32, velocity: i32) {
+        let (Some(index), Ok(key)) = (Self::channel(channel), u8::try_from(key)) else {
+            return;
+        };
+        if velocity <= 0 || key > 127 {
+            self.note_off(channel, key as i32);
+            return;
+        }
+        // A key struck again ends its previous note first
+        self.note_off(channel, key as i32);
+        let percussion = channel == GM_PERCUSSION_CHANNEL || self.banks[index] >= 128;
+        self.push(
+            channel,
+            RecordedKind::NoteOn {
+                key,
+                velocity: velocity.min(127) as u8,
+                percussion,
+            },
+        );
+        if !percussion {
+            self.sounding[index] |= 1 << key;
+        }
+    }
+
+    pub fn note_off(&mut self, channel: i32, key: i32) {
+        let (Some(index), Ok(key)) = (Self::channel(channel), u8::try_from(key)) else {
+            return;
+        };
+        if key < 128 && self.sounding[index] & (1 << key) != 0 {
+            self.sounding[index] &= !(1 << key);
+            self.push(channel, RecordedKind::NoteOff { key });
+        }
+    }
+
+    pub fn note_off_all_channel(&mut self, channel: i32, _immediate: bool) {
+        let Some(index) = Self::channel(channel) else {
+            return;
+        };
+        for key in 0..128 {
+            if self.sounding[index] & (1 << key) != 0 {
+                self.note_off(channel, key);
+            }
+        }
+    }
+
+    pub fn process_midi_message(&mut self, channel: i32, command: i32, data1: i32, data2: i32) {
+        let Some(index) = Self::channel(channel) else {
+            return;
+        };
+        let byte = |value: i32| value.clamp(0, 127) as u8;
+        match command {
+            CONTROL_CHANGE => {
+                if data1 == BANK_SELECT {
+                    self.banks[index] = data2;
+                }
+                let kind = RecordedKind::Controller {
+                    controller: byte(data1),
+                    value: byte(data2),
+                };
+                self.push(channel, kind);
+            }
+            PROGRAM_CHANGE => self.push(channel, RecordedKind::Program(byte(data1))),
+            _ => {}
+        }
+    }
+
+    /// Nothing is heard, only the clock moves on
+    pub fn render(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let frames = left.len().min(right.len());
+        left[..frames].fill(0.0);
+        right[..frames].fill(0.0);
+        self.frame += frames as u64;
+    }
+
+    /// Everything recorded, with the notes still held ended on the last frame
+    pub fn finish(mut self) -> Vec<RecordedMessage> {
+        for channel in 0..MIDI_CHANNELS as i32 {
+            self.note_off_all_channel(channel, true);
+        }
+        self.messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_note_is_ended() {
+        let mut recorder = NoteRecorder::default();
+        let (mut left, mut right) = ([0.0; 10], [0.0; 10]);
+        recorder.note_on(0, 60, 90);
+        recorder.render(&mut left, &mut right);
+        recorder.note_on(0, 60, 80);
+        recorder.note_on(9, 42, 100);
+        recorder.render(&mut left, &mut right);
+
+        let messages = recorder.finish();
+        let kinds: Vec<(u64, RecordedKind)> = messages.iter().map(|m| (m.frame, m.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (
+                    0,
+                    RecordedKind::NoteOn {
+                        key: 60,
+                        velocity: 90,
+                        percussion: false
+                    }
+                ),
+                (10, RecordedKind::NoteOff { key: 60 }),
+                (
+                    10,
+                    RecordedKind::NoteOn {
+                        key: 60,
+                        velocity: 80,
+                        percussion: false
+                    }
+                ),
+                (
+                    10,
+                    RecordedKind::NoteOn {
+                        key: 42,
+                        velocity: 100,
+                        percussion: true
+                    }
+                ),
+                (20, RecordedKind::NoteOff { key: 60 }),
+            ]
+        );
+    }
+}
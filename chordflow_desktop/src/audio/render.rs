@@ -0,0 +1,376 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use crossbeam_channel::{unbounded, Receiver};
+use strum::IntoEnumIterator;
+
+use crate::{
+    audio::{
+        builtin::BuiltinSynth,
+        engine::Engine,
+        samples::{decode_sample, ClickSound, CLICK_SAMPLES},
+        soundfont::{build_synthesizer, InstrumentPart, SOUND_FONT},
+        source::SoundSource,
+        timeline::ScheduledChord,
+    },
+    AudioCommand, AudioEvent,
+};
+
+/// Sample rate of exported files, what phones and players expect
+pub const WAV_SAMPLE_RATE: u32 = 44_100;
+/// Frames rendered between looking at the engine's events
+const BLOCK_FRAMES: usize = 1024;
+/// Chords scheduled beyond the playing one, as during live playback
+const LOOKAHEAD_CHORDS: u64 = 4;
+/// Longest ring-out after the session ends
+const MAX_TAIL_SECONDS: usize = 4;
+/// Level below which the ring-out counts as silent
+const SILENCE: f32 = 1e-4;
+
+/// How long a rendered session lasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderLength {
+    /// Passes through the progression or the cycle of the mode
+    Cycles(u32),
+    Seconds(u32),
+}
+
+/// Audio of a rendered session and the events the engine raised on the way
+#[derive(Default)]
+pub struct Rendered {
+    pub sample_rate: u32,
+    pub left: Vec<f32>,
+    pub right: Vec<f32>,
+    pub events: Vec<AudioEvent>,
+}
+
+impl Rendered {
+    pub fn frames(&self) -> usize {
+        self.left.len()
+    }
+
+    fn peak_since(&self, frame: usize) -> f32 {
+        self.left[frame..]
+            .iter()
+            .chain(&self.right[frame..])
+            .fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+}
+
+/// Runs the audio engine without an output device, as fast as it can.
+///
+/// Nothing depends on wall-clock time, so the same commands always render the same
+/// samples and events. That makes it the harness for testing the engine as well.
+pub struct OfflineRenderer {
+    engine: Engine,
+    events: Receiver<AudioEvent>,
+    sample_rate: u32,
+}
+
+impl OfflineRenderer {
+    pub fn new(source: SoundSource, sample_rate: u32) -> Self {
+        let (sender, events) = unbounded();
+        Self {
+            engine: Engine::with_events(source, sample_rate, sender),
+            events,
+            sample_rate,
+        }
+    }
+
+    /// A renderer with the SoundFont, presets and click samples of live playback
+    pub fn with_current_sounds(sample_rate: u32) -> Self {
+        let (sound_font, settings) = {
+            let status = SOUND_FONT.lock();
+            (status.sound_font.clone(), status.settings.clone())
+        };
+        let source = sound_font
+            .and_then(|font| build_synthesizer(&font, sample_rate).ok())
+            .map(SoundSource::SoundFont)
+            .unwrap_or_else(|| SoundSource::Builtin(BuiltinSynth::new(sample_rate)));
+        let mut renderer = Self::new(source, sample_rate);
+        for part in InstrumentPart::iter() {
+            renderer.send(AudioCommand::SetInstrument(part, settings.instrument(part)));
+        }
+
+        let paths = CLICK_SAMPLES.lock().settings.paths.clone();
+        for (sound, path) in ClickSound::iter().zip(paths) {
+            // A sample that does not load is already reported for live playback
+            let sample = path.and_then(|path| decode_sample(&path, sample_rate).ok());
+            renderer.send(AudioCommand::SetClickSample(sound, sample));
+        }
+        renderer
+    }
+
+    pub fn into_source(self) -> SoundSource {
+        self.engine.into_source()
+    }
+
+    pub fn send(&mut self, cmd: AudioCommand) {
+        self.engine.handle_command(cmd);
+    }
+
+    /// Append `frames` frames and the events raised while rendering them
+    pub fn render(&mut self, frames: usize, out: &mut Rendered) {
+        let start = out.left.len();
+        out.sample_rate = self.sample_rate;
+        out.left.resize(start + frames, 0.0);
+        out.right.resize(start + frames, 0.0);
+        self.engine
+            .render(&mut out.left[start..], &mut out.right[start..]);
+        out.events.extend(self.events.try_iter());
+    }
+
+    /// Play a session from its first chord until `length` is reached and let it ring out.
+    ///
+    /// `next_chord` is asked for chords as playback needs them, like the practice state
+    /// during live playback. For a number of cycles it should run out after the last
+    /// chord of the last cycle, the session then ends after that chord's bars.
+    pub fn render_session(
+        &mut self,
+        length: RenderLength,
+        count_in: bool,
+        mut next_chord: impl FnMut() -> Option<ScheduledChord>,
+    ) -> Rendered {
+        let max_frames = match length {
+            RenderLength::Cycles(_) => usize::MAX,
+            RenderLength::Seconds(seconds) => seconds as usize * self.sample_rate as usize,
+        };
+        let stop_when_out_of_chords = matches!(length, RenderLength::Cycles(_));
+
+        let mut scheduled = 0;
+        let mut end_bar = 0;
+        let mut out_of_chords = false;
+        let mut fill = |engine: &mut Engine, playing: u64| {
+            while !out_of_chords && scheduled <= playing + LOOKAHEAD_CHORDS {
+                match next_chord() {
+                    Some(chord) => {
+                        end_bar = chord.start_bar + chord.bars as u64;
+                        engine.handle_command(AudioCommand::ScheduleChord(chord));
+                        scheduled += 1;
+                    }
+                    None => {
+                        out_of_chords = true;
+                        if stop_when_out_of_chords {
+                            engine.stop_at_bar(Some(end_bar));
+                        }
+                    }
+                }
+            }
+        };
+
+        let mut out = Rendered::default();
+        fill(&mut self.engine, 0);
+        self.send(if count_in {
+            AudioCommand::StartWithCountIn
+        } else {
+            AudioCommand::Start
+        });
+        while self.engine.is_playing() && out.frames() < max_frames {
+            let first_event = out.events.len();
+            self.render(BLOCK_FRAMES.min(max_frames - out.frames()), &mut out);
+            let playing = out.events[first_event..]
+                .iter()
+                .rev()
+                .find_map(|e| match e {
+                    AudioEvent::ChordChanged { index, .. } => Some(*index),
+                    _ => None,
+                });
+            if let Some(playing) = playing {
+                fill(&mut self.engine, playing);
+            }
+        }
+
+        // Let the last notes ring out
+        self.send(AudioCommand::Stop);
+        let tail_end = out.frames() + MAX_TAIL_SECONDS * self.sample_rate as usize;
+        while out.frames() < tail_end {
+            let start = out.frames();
+            self.render(BLOCK_FRAMES, &mut out);
+            if out.peak_since(start) < SILENCE {
+                break;
+            }
+        }
+        out
+    }
+}
+
+/// Write rendered audio as a 16-bit stereo WAV file, turned down if it would clip
+pub fn write_wav(path: &Path, rendered: &Rendered) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: rendered.sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)
+        .with_context(|| format!("Failed to create {:?}", path))?;
+    let gain = 1.0 / rendered.peak_since(0).max(1.0);
+    for (left, right) in rendered.left.iter().zip(&rendered.right) {
+        for sample in [left, right] {
+            writer.write_sample((sample * gain * i16::MAX as f32) as i16)?;
+        }
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{
+        articulation::{ArticulationStyle, ChordArticulation},
+        engine::CHORD_CHANNEL,
+        recorder::{NoteRecorder, RecordedKind},
+        tempo::TempoRamp,
+    };
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn renderer() -> OfflineRenderer {
+        let mut renderer = OfflineRenderer::new(
+            SoundSource::Builtin(BuiltinSynth::new(SAMPLE_RATE)),
+            SAMPLE_RATE,
+        );
+        renderer.send(AudioCommand::SetBPM(120));
+        renderer
+    }
+
+    /// Two one-bar chords, `cycles` times
+    fn chords(cycles: u64) -> impl FnMut() -> Option<ScheduledChord> {
+        let mut index = 0;
+        move || {
+            if index == cycles * 2 {
+                return None;
+            }
+            let notes: &[u8] = if index.is_multiple_of(2) {
+                &[60, 64, 67]
+            } else {
+                &[62, 65, 69]
+            };
+            let chord = ScheduledChord::new(index, index, 0, 1, notes);
+            index += 1;
+            Some(chord)
+        }
+    }
+
+    #[test]
+    fn test_cycles_end_after_the_last_chord() {
+        let rendered = renderer().render_session(RenderLength::Cycles(3), false, chords(3));
+        // A bar of 4/4 at 120 bpm is two seconds
+        let bar = 2 * SAMPLE_RATE as u64;
+        let changes: Vec<(u64, u64)> = rendered
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                AudioEvent::ChordChanged { sample, index } => Some((*index, *sample)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(changes, (0..6).map(|i| (i, i * bar)).collect::<Vec<_>>());
+
+        let ticks = rendered
+            .events
+            .iter()
+            .filter(|e| matches!(e, AudioEvent::Tick { .. }))
+            .count();
+        assert_eq!(ticks, 24);
+        assert!(rendered
+            .events
+            .iter()
+            .any(|e| matches!(e, AudioEvent::Stopped)));
+        // Six bars and a ring-out
+        assert!(rendered.frames() as u64 > 6 * bar);
+        assert!(
+            rendered.frames() as u64 <= 6 * bar + (BLOCK_FRAMES + MAX_TAIL_SECONDS * 48_000) as u64
+        );
+        assert!(rendered.peak_since(0) > 0.01);
+    }
+
+    #[test]
+    fn test_ramp_tempo_starts_on_the_bar_line() {
+        let mut renderer = renderer();
+        renderer.send(AudioCommand::SetTempoRamp(Some(TempoRamp {
+            start_bpm: 120,
+            target_bpm: 240,
+            step: 120,
+            every: 1,
+            ..TempoRamp::default()
+        })));
+        let rendered = renderer.render_session(RenderLength::Seconds(3), false, chords(4));
+        let ticks: Vec<u64> = rendered
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                AudioEvent::Tick { sample, .. } => Some(*sample),
+                _ => None,
+            })
+            .take(6)
+            .collect();
+        // The whole first bar is played at 120 bpm, the second one at 240
+        let beat = SAMPLE_RATE as u64 / 2;
+        assert_eq!(
+            ticks,
+            [0, beat, 2 * beat, 3 * beat, 4 * beat, 4 * beat + beat / 2]
+        );
+    }
+
+    /// Frames the first chord's root is struck and released on with `style`
+    fn root_notes(style: ArticulationStyle) -> Vec<(u64, bool)> {
+        let recorder = SoundSource::Recorder(NoteRecorder::default());
+        let mut renderer = OfflineRenderer::new(recorder, SAMPLE_RATE);
+        renderer.send(AudioCommand::SetBPM(120));
+        renderer.send(AudioCommand::SetArticulation(ChordArticulation {
+            style,
+            staccato_beats: 0b0101,
+            gate_percent: 30,
+        }));
+        renderer.render_session(RenderLength::Seconds(4), false, chords(1));
+        let SoundSource::Recorder(recorder) = renderer.into_source() else {
+            unreachable!();
+        };
+        recorder
+            .finish()
+            .into_iter()
+            .filter(|m| m.channel as i32 == CHORD_CHANNEL)
+            .filter_map(|m| match m.kind {
+                RecordedKind::NoteOn { key: 60, .. } => Some((m.frame, true)),
+                RecordedKind::NoteOff { key: 60 } => Some((m.frame, false)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_notes_end_after_their_gate() {
+        let beat = SAMPLE_RATE as u64 / 2;
+        let gate = beat * 30 / 100;
+        // Staccato hits on the first and third beat, each as long as the gate
+        assert_eq!(
+            root_notes(ArticulationStyle::Staccato),
+            [
+                (0, true),
+                (gate, false),
+                (2 * beat, true),
+                (2 * beat + gate, false)
+            ]
+        );
+        // Held hits last until the next chord
+        assert_eq!(
+            root_notes(ArticulationStyle::EveryBar),
+            [(0, true), (4 * beat, false)]
+        );
+    }
+
+    #[test]
+    fn test_rendering_is_deterministic() {
+        let render = || {
+            let mut renderer = renderer();
+            renderer.send(AudioCommand::SetSubdivision(2));
+            renderer.render_session(RenderLength::Seconds(3), true, chords(u64::MAX / 4))
+        };
+        let (first, second) = (render(), render());
+        assert!(first.frames() >= 3 * SAMPLE_RATE as usize);
+        assert_eq!(first.left, second.left);
+        assert_eq!(first.right, second.right);
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumCount, EnumIter, IntoEnumIterator};
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, errors::Error as DecodeError,
+    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+
+use crate::{config, AudioCommand, AUDIO_CMD};
+
+const SETTINGS_FILE: &str = "click_samples.toml";
+/// Click samples are one-shots, longer files are refused
+const MAX_SAMPLE_SECONDS: usize = 2;
+/// Sample clicks that can overlap, e.g. a long accent under fast subdivisions
+const MAX_SAMPLE_VOICES: usize = 8;
+
+/// The metronome sounds that can be replaced by a sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, EnumCount, Display)]
+pub enum ClickSound {
+    Accent,
+    Beat,
+    Subdivision,
+}
+
+/// Decode an audio file to mono and resample it to `sample_rate`
+pub fn decode_sample(path: &Path, sample_rate: u32) -> Result<Box<[f32]>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .context("Unsupported audio file")?;
+    let mut format = probed.format;
+    let track = format.default_track().context("No audio track")?;
+    let track_id = track.id;
+    let source_rate = track
+        .codec_params
+        .sample_rate
+        .context("Unknown sample rate")?;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let max_frames = source_rate as usize * MAX_SAMPLE_SECONDS;
+    let mut mono = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = decoder.decode(&packet)?;
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        mono.extend(
+            buffer
+                .samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+        if mono.len() > max_frames {
+            anyhow::bail!("Sample is longer than {} seconds", MAX_SAMPLE_SECONDS);
+        }
+    }
+    if mono.is_empty() {
+        anyhow::bail!("Sample is empty");
+    }
+    Ok(resample(&mono, source_rate, sample_rate).into_boxed_slice())
+}
+
+/// Linear interpolation, good enough for short percussive samples
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || from == 0 || to == 0 {
+        return samples.to_vec();
+    }
+    let step = from as f64 / to as f64;
+    let len = (samples.len() as f64 / step).ceil() as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = samples.get(index).copied().unwrap_or(0.0);
+            let next = samples.get(index + 1).copied().unwrap_or(0.0);
+            current + (next - current) * fraction
+        })
+        .collect()
+}
+
+/// Sample files chosen for the click, remembered between sessions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClickSampleSettings {
+    /// File per [`ClickSound`], `None` keeps the synthesized click
+    pub paths: [Option<PathBuf>; ClickSound::COUNT],
+}
+
+/// Chosen samples and why any of them could not be used
+#[derive(Debug, Clone, Default)]
+pub struct ClickSampleStatus {
+    pub settings: ClickSampleSettings,
+    pub errors: [Option<String>; ClickSound::COUNT],
+    /// Output sample rate the samples are resampled to
+    pub sample_rate: u32,
+}
+
+pub static CLICK_SAMPLES: LazyLock<Mutex<ClickSampleStatus>> =
+    LazyLock::new(|| Mutex::new(ClickSampleStatus::default()));
+
+/// Decode the remembered samples for the stream. A sample that fails to load is
+/// reported and its synthesized click is used instead.
+pub fn open_click_samples(sample_rate: u32) -> [Option<Box<[f32]>>; ClickSound::COUNT] {
+    let settings: ClickSampleSettings = config::load(SETTINGS_FILE);
+    let mut status = ClickSampleStatus {
+        settings,
+        errors: Default::default(),
+        sample_rate,
+    };
+    let mut samples: [Option<Box<[f32]>>; ClickSound::COUNT] = Default::default();
+    for sound in ClickSound::iter() {
+        let Some(path) = status.settings.paths[sound as usize].clone() else {
+            continue;
+        };
+        match decode_sample(&path, sample_rate) {
+            Ok(sample) => samples[sound as usize] = Some(sample),
+            Err(e) => {
+                log::error!("Failed to load click sample {:?}: {:#}", path, e);
+                status.errors[sound as usize] =
+                    Some(format!("Could not load {}: {:#}", path.display(), e));
+            }
+        }
+    }
+    *CLICK_SAMPLES.lock() = status;
+    samples
+}
+
+/// Play `path` for a click sound, or the synthesized click again when `path` is `None`.
+/// Decoding is slow, call this off the UI thread. On failure the current sound is kept.
+pub fn set_click_sample(sound: ClickSound, path: Option<PathBuf>) -> Result<()> {
+    let sample_rate = CLICK_SAMPLES.lock().sample_rate;
+    let sample = path
+        .as_deref()
+        .map(|path| decode_sample(path, sample_rate))
+        .transpose();
+
+    let mut status = CLICK_SAMPLES.lock();
+    let sample = match sample {
+        Ok(sample) => sample,
+        Err(e) => {
+            log::error!("Failed to load click sample {:?}: {:#}", path, e);
+            status.errors[sound as usize] = Some(format!("Could not load the sample: {:#}", e));
+            return Err(e);
+        }
+    };
+    status.settings.paths[sound as usize] = path;
+    status.errors[sound as usize] = None;
+    if let Err(e) = config::save(SETTINGS_FILE, &status.settings) {
+        log::warn!("Failed to save click sample settings: {}", e);
+    }
+    let _ = AUDIO_CMD
+        .0
+        .try_send(AudioCommand::SetClickSample(sound, sample));
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SampleVoice {
+    sound: Option<ClickSound>,
+    position: usize,
+    gain: f32,
+}
+
+/// Plays the click samples on the audio thread without allocating
+#[derive(Default)]
+pub struct SamplePlayer {
+    samples: [Option<Box<[f32]>>; ClickSound::COUNT],
+    voices: [SampleVoice; MAX_SAMPLE_VOICES],
+}
+
+impl SamplePlayer {
+    /// Swap in a sample, returning the previous one so it can be freed elsewhere
+    pub fn replace(&mut self, sound: ClickSound, sample: Option<Box<[f32]>>) -> Option<Box<[f32]>> {
+        for voice in self.voices.iter_mut() {
+            if voice.sound == Some(sound) {
+                voice.sound = None;
+            }
+        }
+        std::mem::replace(&mut self.samples[sound as usize], sample)
+    }
+
+    /// Start the sample of `sound`, false when there is none and the synth should click
+    pub fn trigger(&mut self, sound: ClickSound, gain: f32) -> bool {
+        if self.samples[sound as usize].is_none() {
+            return false;
+        }
+        // A free voice, otherwise the one that has played longest
+        let index = self
+            .voices
+            .iter()
+            .position(|v| v.sound.is_none())
+            .or_else(|| {
+                self.voices
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, v)| v.position)
+                    .map(|(i, _)| i)
+            })
+            .unwrap_or(0);
+        self.voices[index] = SampleVoice {
+            sound: Some(sound),
+            position: 0,
+            gain,
+        };
+        true
+    }
+
+    /// Add the playing samples to both channels
+    pub fn mix(&mut self, left: &mut [f32], right: &mut [f32]) {
+        for voice in self.voices.iter_mut() {
+            let Some(sound) = voice.sound else {
+                continue;
+            };
+            let Some(sample) = self.samples[sound as usize].as_deref() else {
+                voice.sound = None;
+                continue;
+            };
+            let remaining = &sample[voice.position.min(sample.len())..];
+            for ((l, r), s) in left.iter_mut().zip(right.iter_mut()).zip(remaining) {
+                *l += s * voice.gain;
+                *r += s * voice.gain;
+            }
+            voice.position += left.len().min(right.len());
+            if voice.position >= sample.len() {
+                voice.sound = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resample_keeps_duration() {
+        let samples: Vec<f32> = (0..441).map(|i| i as f32).collect();
+        let resampled = resample(&samples, 44_100, 48_000);
+        assert_eq!(resampled.len(), 480);
+        assert_eq!(resampled[0], 0.0);
+        // Interpolated between the source samples
+        assert!((resampled[240] - 220.5).abs() < 0.01);
+        assert_eq!(resample(&samples, 48_000, 48_000), samples);
+    }
+
+    #[test]
+    fn test_player_mixes_samples_across_blocks() {
+        let mut player = SamplePlayer::default();
+        assert!(!player.trigger(ClickSound::Beat, 1.0));
+
+        player.replace(ClickSound::Beat, Some(vec![1.0; 6].into_boxed_slice()));
+        assert!(player.trigger(ClickSound::Beat, 0.5));
+        let (mut left, mut right) = ([0.0; 4], [0.0; 4]);
+        player.mix(&mut left, &mut right);
+        assert_eq!(left, [0.5; 4]);
+        let (mut left, mut right) = ([0.0; 4], [0.0; 4]);
+        player.mix(&mut left, &mut right);
+        assert_eq!(right, [0.5, 0.5, 0.0, 0.0]);
+        assert!(player.voices.iter().all(|v| v.sound.is_none()));
+    }
+}
@@ -13,6 +13,10 @@ pub struct AudioSettings {
     pub metronome_subdivision_volume: AtomicF32,
     /// Volume for chord playback - range 0.0 to 1.0
     pub chord_volume: AtomicF32,
+    /// Volume for the bass line - range 0.0 to 1.0
+    pub bass_volume: AtomicF32,
+    /// Volume for the drum groove - range 0.0 to 1.0
+    pub drum_volume: AtomicF32,
 }
 
 impl Default for AudioSettings {
@@ -22,6 +26,8 @@ impl Default for AudioSettings {
             metronome_beat_volume: AtomicF32::new(0.8),
             metronome_subdivision_volume: AtomicF32::new(0.5),
             chord_volume: AtomicF32::new(0.7),
+            bass_volume: AtomicF32::new(0.8),
+            drum_volume: AtomicF32::new(0.8),
         }
     }
 }
@@ -70,4 +76,26 @@ impl AudioSettings {
         self.chord_volume
             .store(volume.clamp(0.0, 1.0), Ordering::Relaxed);
     }
+
+    /// Get bass volume (0.0-1.0)
+    pub fn get_bass_volume(&self) -> f32 {
+        self.bass_volume.load(Ordering::Relaxed)
+    }
+
+    /// Set bass volume (0.0-1.0)
+    pub fn set_bass_volume(&self, volume: f32) {
+        self.bass_volume
+            .store(volume.clamp(0.0, 1.0), Ordering::Relaxed);
+    }
+
+    /// Get drum volume (0.0-1.0)
+    pub fn get_drum_volume(&self) -> f32 {
+        self.drum_volume.load(Ordering::Relaxed)
+    }
+
+    /// Set drum volume (0.0-1.0)
+    pub fn set_drum_volume(&self, volume: f32) {
+        self.drum_volume
+            .store(volume.clamp(0.0, 1.0), Ordering::Relaxed);
+    }
 }
@@ -0,0 +1,425 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+};
+
+use anyhow::{Context, Result};
+use dioxus::prelude::*;
+use parking_lot::Mutex;
+use rustysynth::{SoundFont, Synthesizer, SynthesizerSettings};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumCount, EnumIter};
+
+use crate::{
+    audio::{builtin::BuiltinSynth, source::SoundSource},
+    config, AudioCommand, AUDIO_CMD,
+};
+
+const SETTINGS_FILE: &str = "soundfont.toml";
+
+// Bundle the soundfont file using Dioxus asset system
+const SOUNDFONT_ASSET: Asset = asset!("/assets/TimGM6mb.sf2");
+
+/// Get the soundfont file path
+/// Uses the Dioxus asset system to get the correct path for both development and bundled builds
+fn get_soundfont_path() -> Result<PathBuf> {
+    // Get the bundled asset info - this contains the actual filename (with hash in production)
+    let bundled = SOUNDFONT_ASSET.bundled();
+    let bundled_path = bundled.bundled_path();
+    let absolute_source_path = bundled.absolute_source_path();
+
+    log::info!("Soundfont bundled_path: {}", bundled_path);
+    log::info!("Soundfont absolute_source_path: {}", absolute_source_path);
+    log::info!("Current directory: {:?}", std::env::current_dir());
+
+    // In development mode, use the absolute source path directly
+    let source_path = PathBuf::from(absolute_source_path);
+    if source_path.exists() {
+        log::info!("Found soundfont at source path: {:?}", source_path);
+        return Ok(source_path);
+    }
+
+    // Try development path (relative to cwd)
+    let dev_path = PathBuf::from("assets/TimGM6mb.sf2");
+    if dev_path.exists() {
+        log::info!("Found soundfont at dev path: {:?}", dev_path);
+        return Ok(dev_path);
+    }
+
+    // For bundled builds, resolve based on executable location
+    if let Ok(exe_path) = std::env::current_exe() {
+        log::info!("Executable path: {:?}", exe_path);
+
+        // Extract the filename from the bundled path (e.g., "TimGM6mb-dxhf18b947c4b5781f.sf2")
+        let bundled_filename = PathBuf::from(bundled_path)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "TimGM6mb.sf2".to_string());
+
+        log::info!("Looking for bundled filename: {}", bundled_filename);
+
+        if let Some(macos_dir) = exe_path.parent() {
+            // macOS bundle: executable is in .app/Contents/MacOS/
+            // Resources are in .app/Contents/Resources/
+            if let Some(contents_dir) = macos_dir.parent() {
+                let resources_dir = contents_dir.join("Resources");
+                log::info!("Resources dir: {:?}", resources_dir);
+
+                // Check in Resources/assets/ (where dx puts hashed assets)
+                let bundle_assets_path = resources_dir.join("assets").join(&bundled_filename);
+                log::info!("Checking bundle assets path: {:?}", bundle_assets_path);
+                if bundle_assets_path.exists() {
+                    log::info!("Found soundfont at: {:?}", bundle_assets_path);
+                    return Ok(bundle_assets_path);
+                }
+
+                // Check directly in Resources/ (for non-hashed bundle builds)
+                let bundle_path = resources_dir.join("TimGM6mb.sf2");
+                log::info!("Checking bundle path: {:?}", bundle_path);
+                if bundle_path.exists() {
+                    log::info!("Found soundfont at: {:?}", bundle_path);
+                    return Ok(bundle_path);
+                }
+
+                // Also check for the hashed version directly in Resources
+                let bundle_hashed_path = resources_dir.join(&bundled_filename);
+                if bundle_hashed_path.exists() {
+                    log::info!("Found soundfont at: {:?}", bundle_hashed_path);
+                    return Ok(bundle_hashed_path);
+                }
+            }
+
+            // Linux/Windows: assets folder next to executable
+            let assets_path = macos_dir.join("assets").join(&bundled_filename);
+            if assets_path.exists() {
+                log::info!("Found soundfont at: {:?}", assets_path);
+                return Ok(assets_path);
+            }
+
+            // Try without hash for Linux/Windows
+            let assets_path_no_hash = macos_dir.join("assets").join("TimGM6mb.sf2");
+            if assets_path_no_hash.exists() {
+                log::info!("Found soundfont at: {:?}", assets_path_no_hash);
+                return Ok(assets_path_no_hash);
+            }
+        }
+    }
+
+    let error_msg = format!(
+        "Could not find soundfont file. Bundled path: '{}', Source path: '{}'",
+        bundled_path, absolute_source_path
+    );
+    log::error!("{}", error_msg);
+    Err(anyhow::anyhow!(error_msg))
+}
+
+/// Parts of the backing that each play their own preset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, EnumCount, Display)]
+pub enum InstrumentPart {
+    Chord,
+    Bass,
+    Click,
+}
+
+impl InstrumentPart {
+    /// Preset of the part in a General MIDI font
+    pub fn default_instrument(self) -> Instrument {
+        match self {
+            InstrumentPart::Chord => Instrument {
+                bank: 0,
+                program: 0,
+            },
+            InstrumentPart::Bass => Instrument {
+                bank: 0,
+                program: 32,
+            },
+            // The standard drum kit, the click uses its wood blocks
+            InstrumentPart::Click => Instrument {
+                bank: 128,
+                program: 0,
+            },
+        }
+    }
+}
+
+/// Bank and program of a SoundFont preset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instrument {
+    pub bank: u16,
+    pub program: u8,
+}
+
+/// A preset offered in the instrument browser
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub instrument: Instrument,
+}
+
+/// Presets of a font ordered by bank and program
+pub fn presets(sound_font: &SoundFont) -> Vec<Preset> {
+    let mut presets: Vec<Preset> = sound_font
+        .get_presets()
+        .iter()
+        .map(|preset| Preset {
+            name: preset.get_name().trim().to_string(),
+            instrument: Instrument {
+                bank: preset.get_bank_number().clamp(0, u16::MAX as i32) as u16,
+                program: preset.get_patch_number().clamp(0, 127) as u8,
+            },
+        })
+        .collect();
+    presets.sort_by_key(|p| (p.instrument.bank, p.instrument.program));
+    presets
+}
+
+/// SoundFont choice remembered between sessions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundFontSettings {
+    /// Custom font file, `None` plays the bundled font
+    pub path: Option<PathBuf>,
+    /// Instrument per [`InstrumentPart`]
+    pub instruments: [Instrument; InstrumentPart::COUNT],
+    /// Play the built-in synthesized sounds instead of a SoundFont
+    pub builtin: bool,
+}
+
+impl Default for SoundFontSettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            instruments: [
+                InstrumentPart::Chord.default_instrument(),
+                InstrumentPart::Bass.default_instrument(),
+                InstrumentPart::Click.default_instrument(),
+            ],
+            builtin: false,
+        }
+    }
+}
+
+impl SoundFontSettings {
+    /// Settings of the previous session, or the defaults when there are none
+    pub fn load() -> Self {
+        config::load(SETTINGS_FILE)
+    }
+
+    /// Save, logging rather than failing since the current session is unaffected
+    fn remember(&self) {
+        if let Err(e) = config::save(SETTINGS_FILE, self) {
+            log::warn!("Failed to save soundfont settings: {}", e);
+        }
+    }
+
+    pub fn instrument(&self, part: InstrumentPart) -> Instrument {
+        self.instruments[part as usize]
+    }
+}
+
+/// The font the synthesizer is playing, shared with the settings panel
+#[derive(Clone, Default)]
+pub struct SoundFontStatus {
+    pub settings: SoundFontSettings,
+    /// Loaded font, `None` while the built-in sounds play
+    pub sound_font: Option<Arc<SoundFont>>,
+    /// Output sample rate new synthesizers are built for
+    pub sample_rate: u32,
+    /// Why the chosen font is not the one playing
+    pub error: Option<String>,
+}
+
+impl SoundFontStatus {
+    /// File name of the font that is playing
+    pub fn name(&self) -> String {
+        if self.sound_font.is_none() {
+            return "Built-in sounds".to_string();
+        }
+        match &self.settings.path {
+            Some(path) if self.error.is_none() => path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string()),
+            _ => "TimGM6mb (bundled)".to_string(),
+        }
+    }
+}
+
+pub static SOUND_FONT: LazyLock<Mutex<SoundFontStatus>> =
+    LazyLock::new(|| Mutex::new(SoundFontStatus::default()));
+
+/// Load a SoundFont file
+pub fn load_sound_font(path: &Path) -> Result<Arc<SoundFont>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open soundfont file at {:?}", path))?;
+    let sound_font = SoundFont::new(&mut file)
+        .map_err(|e| anyhow::anyhow!("Failed to parse soundfont: {}", e))?;
+    Ok(Arc::new(sound_font))
+}
+
+pub fn build_synthesizer(sound_font: &Arc<SoundFont>, sample_rate: u32) -> Result<Synthesizer> {
+    let settings = SynthesizerSettings::new(sample_rate as i32);
+    Synthesizer::new(sound_font, &settings)
+        .map_err(|e| anyhow::anyhow!("Failed to create synthesizer: {}", e))
+}
+
+/// Sound source for the stream: the remembered font, else the bundled font, and when
+/// neither loads the built-in sounds, so the metronome always works
+pub fn open_sound_source(sample_rate: u32) -> SoundSource {
+    let settings = SoundFontSettings::load();
+    let mut status = SoundFontStatus {
+        settings,
+        sound_font: None,
+        sample_rate,
+        error: None,
+    };
+    if status.settings.builtin {
+        *SOUND_FONT.lock() = status;
+        return SoundSource::Builtin(BuiltinSynth::new(sample_rate));
+    }
+
+    let custom = status.settings.path.as_deref().and_then(|path| {
+        load_sound_font(path)
+            .inspect_err(|e| {
+                log::error!("Failed to load soundfont {:?}: {}", path, e);
+                status.error = Some(fallback_message(path, e));
+            })
+            .ok()
+    });
+    let loaded = match custom {
+        Some(sound_font) => Ok(sound_font),
+        None => get_soundfont_path().and_then(|path| {
+            log::info!("Attempting to open soundfont at: {:?}", path);
+            load_sound_font(&path)
+        }),
+    }
+    .and_then(|sound_font| Ok((build_synthesizer(&sound_font, sample_rate)?, sound_font)));
+
+    let source = match loaded {
+        Ok((synthesizer, sound_font)) => {
+            status.sound_font = Some(sound_font);
+            SoundSource::SoundFont(synthesizer)
+        }
+        Err(e) => {
+            log::error!("Falling back to the built-in sounds: {}", e);
+            status.error = Some(format!(
+                "Could not load the bundled SoundFont: {:#}. Playing the built-in sounds instead.",
+                e
+            ));
+            SoundSource::Builtin(BuiltinSynth::new(sample_rate))
+        }
+    };
+    *SOUND_FONT.lock() = status;
+    source
+}
+
+/// Sound source for a rebuilt stream, reusing the font that is already loaded
+pub fn reopen_sound_source(sample_rate: u32) -> SoundSource {
+    let mut status = SOUND_FONT.lock();
+    status.sample_rate = sample_rate;
+    let Some(sound_font) = status.sound_font.clone() else {
+        return SoundSource::Builtin(BuiltinSynth::new(sample_rate));
+    };
+    match build_synthesizer(&sound_font, sample_rate) {
+        Ok(synthesizer) => SoundSource::SoundFont(synthesizer),
+        Err(e) => {
+            log::error!("Falling back to the built-in sounds: {}", e);
+            status.sound_font = None;
+            status.error = Some(format!("{:#}. Playing the built-in sounds instead.", e));
+            SoundSource::Builtin(BuiltinSynth::new(sample_rate))
+        }
+    }
+}
+
+fn fallback_message(path: &Path, error: &anyhow::Error) -> String {
+    format!(
+        "Could not load {}: {:#}. Playing the bundled SoundFont instead.",
+        path.display(),
+        error
+    )
+}
+
+/// Swap the playing font for `path`, or for the bundled font when `path` is `None`.
+/// Loading is slow, call this off the UI thread. On failure the current font keeps playing.
+pub fn switch_sound_font(path: Option<PathBuf>) -> Result<()> {
+    let sample_rate = SOUND_FONT.lock().sample_rate;
+    let loaded = match &path {
+        Some(path) => load_sound_font(path),
+        None => get_soundfont_path().and_then(|bundled| load_sound_font(&bundled)),
+    }
+    .and_then(|sound_font| Ok((build_synthesizer(&sound_font, sample_rate)?, sound_font)));
+
+    let mut status = SOUND_FONT.lock();
+    let (synthesizer, sound_font) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            log::error!("Failed to switch soundfont to {:?}: {}", path, e);
+            let message = match &path {
+                Some(path) => format!("Could not load {}: {:#}", path.display(), e),
+                None => format!("Could not load the bundled SoundFont: {:#}", e),
+            };
+            status.error = Some(message);
+            return Err(e);
+        }
+    };
+
+    status.settings.path = path;
+    status.settings.builtin = false;
+    status.sound_font = Some(sound_font);
+    status.error = None;
+    status.settings.remember();
+    let _ = AUDIO_CMD.0.try_send(AudioCommand::SetSoundSource(Box::new(
+        SoundSource::SoundFont(synthesizer),
+    )));
+    Ok(())
+}
+
+/// Play the built-in sounds instead of a SoundFont and remember the choice
+pub fn use_builtin_sounds() {
+    let mut status = SOUND_FONT.lock();
+    status.settings.builtin = true;
+    status.sound_font = None;
+    status.error = None;
+    status.settings.remember();
+    let builtin = BuiltinSynth::new(status.sample_rate);
+    let _ = AUDIO_CMD.0.try_send(AudioCommand::SetSoundSource(Box::new(
+        SoundSource::Builtin(builtin),
+    )));
+}
+
+/// Choose the preset a part plays and remember it
+pub fn set_instrument(part: InstrumentPart, instrument: Instrument) {
+    let mut status = SOUND_FONT.lock();
+    status.settings.instruments[part as usize] = instrument;
+    status.settings.remember();
+    let _ = AUDIO_CMD
+        .0
+        .try_send(AudioCommand::SetInstrument(part, instrument));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_round_trip() {
+        let mut settings = SoundFontSettings {
+            path: Some(PathBuf::from("/fonts/Jazz Kit.sf2")),
+            ..SoundFontSettings::default()
+        };
+        settings.instruments[InstrumentPart::Bass as usize] = Instrument {
+            bank: 8,
+            program: 33,
+        };
+        let text = toml::to_string(&settings).unwrap();
+        assert_eq!(
+            toml::from_str::<SoundFontSettings>(&text).unwrap(),
+            settings
+        );
+
+        // Missing entries fall back to the defaults
+        let empty: SoundFontSettings = toml::from_str("").unwrap();
+        assert_eq!(empty, SoundFontSettings::default());
+    }
+}
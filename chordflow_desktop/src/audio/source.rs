@@ -0,0 +1,60 @@
+use rustysynth::Synthesizer;
+
+use crate::audio::{builtin::BuiltinSynth, recorder::NoteRecorder};
+
+/// What the engine plays its notes on
+pub enum SoundSource {
+    SoundFont(Synthesizer),
+    /// Synthesized sounds for when no SoundFont can be loaded, or to save CPU
+    Builtin(BuiltinSynth),
+    /// Writes the notes down instead of playing them, for exporting MIDI
+    Recorder(NoteRecorder),
+}
+
+impl SoundSource {
+    pub fn note_on(&mut self, channel: i32, key: i32, velocity: i32) {
+        match self {
+            SoundSource::SoundFont(synth) => synth.note_on(channel, key, velocity),
+            SoundSource::Builtin(synth) => synth.note_on(channel, key, velocity),
+            SoundSource::Recorder(synth) => synth.note_on(channel, key, velocity),
+        }
+    }
+
+    pub fn note_off(&mut self, channel: i32, key: i32) {
+        match self {
+            SoundSource::SoundFont(synth) => synth.note_off(channel, key),
+            SoundSource::Builtin(synth) => synth.note_off(channel, key),
+            SoundSource::Recorder(synth) => synth.note_off(channel, key),
+        }
+    }
+
+    pub fn note_off_all_channel(&mut self, channel: i32, immediate: bool) {
+        match self {
+            SoundSource::SoundFont(synth) => synth.note_off_all_channel(channel, immediate),
+            SoundSource::Builtin(synth) => synth.note_off_all_channel(channel, immediate),
+            SoundSource::Recorder(synth) => synth.note_off_all_channel(channel, immediate),
+        }
+    }
+
+    pub fn process_midi_message(&mut self, channel: i32, command: i32, data1: i32, data2: i32) {
+        match self {
+            SoundSource::SoundFont(synth) => {
+                synth.process_midi_message(channel, command, data1, data2)
+            }
+            SoundSource::Builtin(synth) => {
+                synth.process_midi_message(channel, command, data1, data2)
+            }
+            SoundSource::Recorder(synth) => {
+                synth.process_midi_message(channel, command, data1, data2)
+            }
+        }
+    }
+
+    pub fn render(&mut self, left: &mut [f32], right: &mut [f32]) {
+        match self {
+            SoundSource::SoundFont(synth) => synth.render(left, right),
+            SoundSource::Builtin(synth) => synth.render(left, right),
+            SoundSource::Recorder(synth) => synth.render(left, right),
+        }
+    }
+}
@@ -1,408 +1,225 @@
-use crate::{audio::settings::AUDIO_SETTINGS, AudioCommand, AudioEvent, AUDIO_CMD, AUDIO_EVT};
+use crate::{
+    audio::{
+        engine::Engine,
+        output::{
+            open_output, record_latency, OpenedOutput, OutputRequest, OUTPUT, OUTPUT_REQUEST,
+        },
+        samples::{open_click_samples, ClickSound},
+        settings::AUDIO_SETTINGS,
+        soundfont::{open_sound_source, reopen_sound_source, InstrumentPart, SOUND_FONT},
+    },
+    AudioCommand, AudioEvent, AUDIO_CMD, AUDIO_EVT,
+};
 use anyhow::Result;
 use cpal::{
-    traits::{DeviceTrait, HostTrait, StreamTrait},
-    Stream,
-};
-use dioxus::prelude::*;
-use rustysynth::{SoundFont, Synthesizer, SynthesizerSettings};
-use std::{
-    fs::File,
-    path::PathBuf,
-    sync::{
-        atomic::{AtomicBool, AtomicU8, AtomicU16, AtomicU64, Ordering},
-        Arc,
-    },
+    traits::{DeviceTrait, StreamTrait},
+    BufferSize, Stream, StreamError,
 };
+use crossbeam_channel::{bounded, select, Sender};
+use rtrb::{Producer, PushError, RingBuffer};
+use std::{sync::LazyLock, time::Duration};
+use strum::IntoEnumIterator;
+
+/// Largest block the engine renders at once
+const MAX_BLOCK_FRAMES: usize = 4096;
+/// Commands that can wait for the audio callback
+const COMMAND_QUEUE_SIZE: usize = 256;
+/// How long to wait before trying again when no output can be opened
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A running stream and the real-time queue into its callback
+struct Output {
+    /// Playback stops when the stream is dropped
+    _stream: Stream,
+    producer: Producer<AudioCommand>,
+}
 
-// Bundle the soundfont file using Dioxus asset system
-const SOUNDFONT_ASSET: Asset = asset!("/assets/TimGM6mb.sf2");
-
-/// Get the soundfont file path
-/// Uses the Dioxus asset system to get the correct path for both development and bundled builds
-fn get_soundfont_path() -> Result<PathBuf> {
-    // Get the bundled asset info - this contains the actual filename (with hash in production)
-    let bundled = SOUNDFONT_ASSET.bundled();
-    let bundled_path = bundled.bundled_path();
-    let absolute_source_path = bundled.absolute_source_path();
-
-    log::info!("Soundfont bundled_path: {}", bundled_path);
-    log::info!("Soundfont absolute_source_path: {}", absolute_source_path);
-    log::info!("Current directory: {:?}", std::env::current_dir());
-
-    // In development mode, use the absolute source path directly
-    let source_path = PathBuf::from(absolute_source_path);
-    if source_path.exists() {
-        log::info!("Found soundfont at source path: {:?}", source_path);
-        return Ok(source_path);
-    }
-
-    // Try development path (relative to cwd)
-    let dev_path = PathBuf::from("assets/TimGM6mb.sf2");
-    if dev_path.exists() {
-        log::info!("Found soundfont at dev path: {:?}", dev_path);
-        return Ok(dev_path);
+impl Output {
+    /// Queue a command for the callback, waiting for it to make room. A stream that
+    /// is about to be rebuilt may never make room, then the command is dropped.
+    fn push(&mut self, mut cmd: AudioCommand) {
+        while let Err(PushError::Full(rejected)) = self.producer.push(cmd) {
+            if !OUTPUT_REQUEST.1.is_empty() {
+                return;
+            }
+            cmd = rejected;
+            std::thread::sleep(Duration::from_millis(1));
+        }
     }
+}
 
-    // For bundled builds, resolve based on executable location
-    if let Ok(exe_path) = std::env::current_exe() {
-        log::info!("Executable path: {:?}", exe_path);
-
-        // Extract the filename from the bundled path (e.g., "TimGM6mb-dxhf18b947c4b5781f.sf2")
-        let bundled_filename = PathBuf::from(bundled_path)
-            .file_name()
-            .map(|s| s.to_string_lossy().to_string())
-            .unwrap_or_else(|| "TimGM6mb.sf2".to_string());
-
-        log::info!("Looking for bundled filename: {}", bundled_filename);
-
-        if let Some(macos_dir) = exe_path.parent() {
-            // macOS bundle: executable is in .app/Contents/MacOS/
-            // Resources are in .app/Contents/Resources/
-            if let Some(contents_dir) = macos_dir.parent() {
-                let resources_dir = contents_dir.join("Resources");
-                log::info!("Resources dir: {:?}", resources_dir);
-
-                // Check in Resources/assets/ (where dx puts hashed assets)
-                let bundle_assets_path = resources_dir.join("assets").join(&bundled_filename);
-                log::info!("Checking bundle assets path: {:?}", bundle_assets_path);
-                if bundle_assets_path.exists() {
-                    log::info!("Found soundfont at: {:?}", bundle_assets_path);
-                    return Ok(bundle_assets_path);
-                }
+/// Start the audio thread and wait for the first stream to play
+pub fn init_stream() -> Result<()> {
+    let (ready, started) = bounded(1);
+    std::thread::Builder::new()
+        .name("audio-output".to_string())
+        .spawn(move || run_output(ready))?;
+    started.recv()?
+}
 
-                // Check directly in Resources/ (for non-hashed bundle builds)
-                let bundle_path = resources_dir.join("TimGM6mb.sf2");
-                log::info!("Checking bundle path: {:?}", bundle_path);
-                if bundle_path.exists() {
-                    log::info!("Found soundfont at: {:?}", bundle_path);
-                    return Ok(bundle_path);
+/// The audio thread owns the stream, which cannot move between threads. It forwards UI
+/// commands into the callback and rebuilds the stream when the output changes or is lost.
+fn run_output(ready: Sender<Result<()>>) {
+    let mut output = match build_output(true) {
+        Ok(output) => {
+            let _ = ready.send(Ok(()));
+            Some(output)
+        }
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+
+    loop {
+        let request = select! {
+            recv(AUDIO_CMD.1) -> cmd => match (cmd, output.as_mut()) {
+                (Ok(cmd), Some(output)) => {
+                    output.push(cmd);
+                    continue;
                 }
-
-                // Also check for the hashed version directly in Resources
-                let bundle_hashed_path = resources_dir.join(&bundled_filename);
-                if bundle_hashed_path.exists() {
-                    log::info!("Found soundfont at: {:?}", bundle_hashed_path);
-                    return Ok(bundle_hashed_path);
+                // Without a stream the UI resends everything once one is running again
+                (Ok(_), None) => continue,
+                (Err(_), _) => return,
+            },
+            recv(OUTPUT_REQUEST.1) -> request => request.ok(),
+            default(RETRY_INTERVAL) => {
+                if output.is_some() {
+                    continue;
                 }
+                None
             }
+        };
+        if let Some(request) = request {
+            log::info!("Rebuilding the audio stream: {:?}", request);
+        }
 
-            // Linux/Windows: assets folder next to executable
-            let assets_path = macos_dir.join("assets").join(&bundled_filename);
-            if assets_path.exists() {
-                log::info!("Found soundfont at: {:?}", assets_path);
-                return Ok(assets_path);
+        // Release the device before opening it again
+        drop(output.take());
+        match build_output(false) {
+            Ok(rebuilt) => {
+                output = Some(rebuilt);
+                let _ = AUDIO_EVT.0.try_send(AudioEvent::StreamRestarted);
             }
-
-            // Try without hash for Linux/Windows
-            let assets_path_no_hash = macos_dir.join("assets").join("TimGM6mb.sf2");
-            if assets_path_no_hash.exists() {
-                log::info!("Found soundfont at: {:?}", assets_path_no_hash);
-                return Ok(assets_path_no_hash);
+            Err(e) => {
+                log::error!("Failed to open an audio output: {}", e);
+                OUTPUT.lock().error = Some(format!("No audio output: {:#}", e));
             }
         }
     }
-
-    let error_msg = format!(
-        "Could not find soundfont file. Bundled path: '{}', Source path: '{}'",
-        bundled_path, absolute_source_path
-    );
-    log::error!("{}", error_msg);
-    Err(anyhow::anyhow!(error_msg))
 }
 
-pub fn init_stream() -> Result<Stream> {
-    let host = cpal::default_host();
-    let device = host
-        .default_output_device()
-        .ok_or_else(|| anyhow::anyhow!("No default output device"))?;
-    let channels = device.default_output_config()?.channels() as usize;
-    // Start from the default output configuration so we match the device's expectations
-    let config = device.default_output_config()?.config();
+fn build_output(first: bool) -> Result<Output> {
+    let settings = OUTPUT.lock().settings.clone();
+    let OpenedOutput {
+        host,
+        device,
+        config,
+        fallback,
+    } = open_output(&settings)?;
+    let channels = config.channels as usize;
     let sample_rate = config.sample_rate.0;
-
-    let bpm: Arc<AtomicU16> = Arc::new(AtomicU16::new(120));
-    let is_playing: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
-    let sample_counter = Arc::new(AtomicU64::new(0));
-    let next_click_sample = Arc::new(AtomicU64::new(0));
-    let chord: Arc<parking_lot::Mutex<Option<Vec<u8>>>> = Arc::new(parking_lot::Mutex::new(None));
-    let subdivisions_per_beat: Arc<AtomicU8> = Arc::new(AtomicU8::new(1));
-    let current_subdivision: Arc<AtomicU8> = Arc::new(AtomicU8::new(0));
-    let ticks_per_bar: Arc<AtomicU8> = Arc::new(AtomicU8::new(4));
-    let current_beat_in_bar: Arc<AtomicU8> = Arc::new(AtomicU8::new(0));
-    let is_count_in: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
-
-    // Load and verify soundfont
-    let sf2_path = get_soundfont_path()?;
-    log::info!("Attempting to open soundfont at: {:?}", sf2_path);
-    let mut sf2 = File::open(&sf2_path)
-        .map_err(|e| anyhow::anyhow!("Failed to open soundfont file at {:?}: {}", sf2_path, e))?;
-    let sound_font = Arc::new(
-        SoundFont::new(&mut sf2)
-            .map_err(|e| anyhow::anyhow!("Failed to parse soundfont: {}", e))?,
+    let rate = sample_rate as f64;
+    log::info!(
+        "Opening output {:?} at {} Hz",
+        device.name().unwrap_or_default(),
+        sample_rate
     );
 
-    // Create the synthesizer.
-    let settings = SynthesizerSettings::new(sample_rate as i32);
-    let synthesizer = Arc::new(parking_lot::Mutex::new(
-        Synthesizer::new(&sound_font, &settings)
-            .map_err(|e| anyhow::anyhow!("Failed to create synthesizer: {}", e))?,
-    ));
-
-    // Clone atomics for the command handler thread
-    let bpm_cmd = bpm.clone();
-    let is_playing_cmd = is_playing.clone();
-    let next_click_sample_cmd = next_click_sample.clone();
-    let sample_counter_cmd = sample_counter.clone();
-    let chord_cmd = chord.clone();
-    let subdivisions_per_beat_cmd = subdivisions_per_beat.clone();
-    let current_subdivision_cmd = current_subdivision.clone();
-    let current_beat_in_bar_cmd = current_beat_in_bar.clone();
-    let is_count_in_cmd = is_count_in.clone();
-    let _ticks_per_bar_cmd = ticks_per_bar.clone();
-
-    // Spawn a dedicated thread to handle audio commands
-    std::thread::spawn(move || {
-        loop {
-            while let Ok(cmd) = AUDIO_CMD.1.try_recv() {
-                match cmd {
-                    AudioCommand::Start => {
-                        // Reset all counters to start of bar
-                        current_subdivision_cmd.store(0, Ordering::Relaxed);
-                        current_beat_in_bar_cmd.store(0, Ordering::Relaxed);
-                        // Not in count-in mode
-                        is_count_in_cmd.store(false, Ordering::Relaxed);
-                        // Schedule first tick immediately
-                        let current = sample_counter_cmd.load(Ordering::Relaxed);
-                        next_click_sample_cmd.store(current, Ordering::Relaxed);
-                        // Start playing
-                        is_playing_cmd.store(true, Ordering::Relaxed);
-                    }
-                    AudioCommand::StartWithCountIn => {
-                        // Reset all counters to start of bar
-                        current_subdivision_cmd.store(0, Ordering::Relaxed);
-                        current_beat_in_bar_cmd.store(0, Ordering::Relaxed);
-                        // Enable count-in mode
-                        is_count_in_cmd.store(true, Ordering::Relaxed);
-                        // Schedule first tick immediately
-                        let current = sample_counter_cmd.load(Ordering::Relaxed);
-                        next_click_sample_cmd.store(current, Ordering::Relaxed);
-                        // Start playing
-                        is_playing_cmd.store(true, Ordering::Relaxed);
-                    }
-                    AudioCommand::Stop => {
-                        is_playing_cmd.store(false, Ordering::Relaxed);
-                        // Reset counters so next start is clean
-                        current_subdivision_cmd.store(0, Ordering::Relaxed);
-                        current_beat_in_bar_cmd.store(0, Ordering::Relaxed);
-                        // Reset count-in state
-                        is_count_in_cmd.store(false, Ordering::Relaxed);
-                    }
-                    AudioCommand::Restart => {
-                        // Reset all counters to start of bar
-                        current_subdivision_cmd.store(0, Ordering::Relaxed);
-                        current_beat_in_bar_cmd.store(0, Ordering::Relaxed);
-                        // Schedule next tick immediately if playing
-                        if is_playing_cmd.load(Ordering::Relaxed) {
-                            let current = sample_counter_cmd.load(Ordering::Relaxed);
-                            next_click_sample_cmd.store(current, Ordering::Relaxed);
-                        }
-                    }
-                    AudioCommand::SetBPM(new_bpm) => {
-                        bpm_cmd.store(new_bpm, Ordering::Relaxed);
-                    }
-                    AudioCommand::SetSubdivision(subdivs) => {
-                        subdivisions_per_beat_cmd.store(subdivs, Ordering::Relaxed);
-                        // Reset counters to start of bar when changing subdivision
-                        current_subdivision_cmd.store(0, Ordering::Relaxed);
-                        current_beat_in_bar_cmd.store(0, Ordering::Relaxed);
-                        // Reschedule next tick immediately if playing
-                        if is_playing_cmd.load(Ordering::Relaxed) {
-                            let current = sample_counter_cmd.load(Ordering::Relaxed);
-                            next_click_sample_cmd.store(current, Ordering::Relaxed);
-                        }
-                    }
-                    AudioCommand::SetChord(midi_notes) => {
-                        if let Some(notes) = midi_notes {
-                            *chord_cmd.lock() = Some(notes);
-                        } else {
-                            *chord_cmd.lock() = None;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            // Small sleep to avoid busy-waiting
-            std::thread::sleep(std::time::Duration::from_millis(5));
-        }
-    });
-
-    // Percussion channel (MIDI channel 10 = index 9)
-    const PERCUSSION_CHANNEL: i32 = 9;
+    // The engine owns transport, chord timeline and synthesizer and lives in the callback
+    let mut engine = build_engine(sample_rate, first);
 
-    // Different click sounds for different beat types
-    // Using woodblock and sidestick sounds from General MIDI percussion
-    const CLICK_ACCENT: i32 = 76;     // Hi wood block - for downbeat (first beat of bar)
-    const CLICK_NORMAL: i32 = 77;     // Low wood block - for regular beats
-    const CLICK_SUBDIVISION: i32 = 37; // Side stick - for subdivisions
+    // Lock-free queue into the callback. The UI keeps sending on the crossbeam channel,
+    // the audio thread is the single producer of the real-time queue.
+    let (producer, mut consumer) = RingBuffer::new(COMMAND_QUEUE_SIZE);
 
-    // Velocities for different beat types
-    const VELOCITY_ACCENT: i32 = 120;
-    const VELOCITY_NORMAL: i32 = 100;
-    const VELOCITY_SUBDIVISION: i32 = 70;
+    // Initialise the statics the callback touches before it starts running
+    LazyLock::force(&AUDIO_EVT);
+    LazyLock::force(&AUDIO_SETTINGS);
 
-    // Chord configuration
-    const CHORD_CHANNEL: i32 = 0; // Use channel 0 for melodic instruments
-    const CHORD_VELOCITY: i32 = 80;
+    // Render buffers are allocated once, larger device buffers are rendered in blocks
+    let mut left = vec![0.0; MAX_BLOCK_FRAMES];
+    let mut right = vec![0.0; MAX_BLOCK_FRAMES];
 
-    let synth_clone = synthesizer.clone();
-    let chord_clone = chord.clone();
-    let is_count_in_clone = is_count_in.clone();
     let stream = device.build_output_stream(
         &config,
-        move |buffer: &mut [f32], _: &cpal::OutputCallbackInfo| {
-            buffer.fill(0.0);
-
-            // Total number of samples that have been generated so far
-            let current_sample = sample_counter.load(Ordering::Relaxed);
-
-            if !is_playing.load(Ordering::Relaxed) {
-                return;
+        move |buffer: &mut [f32], info: &cpal::OutputCallbackInfo| {
+            while let Ok(cmd) = consumer.pop() {
+                engine.handle_command(cmd);
             }
 
-            let current_bpm = bpm.load(Ordering::Relaxed);
-            let subdivs = subdivisions_per_beat.load(Ordering::Relaxed) as u64;
-
-            // How many frames (multi-channel sample groups) we must fill in this callback
-            let frames = buffer.len() / channels;
-
-            // Calculate samples per beat and per subdivision
-            let samples_per_beat = (sample_rate as f64 * 60.0 / current_bpm as f64) as u64;
-            let samples_per_subdivision = samples_per_beat / subdivs;
-
-            for frame in 0..frames {
-                let next_click = next_click_sample.load(Ordering::Relaxed);
-                let frame_sample = current_sample + frame as u64;
-
-                if frame_sample >= next_click {
-                    let left_over_frames = frame_sample - next_click;
-                    let mut synth = synth_clone.lock();
-
-                    // Get current subdivision within the beat (0 = main beat)
-                    let curr_subdiv = current_subdivision.load(Ordering::Relaxed);
-                    let curr_beat = current_beat_in_bar.load(Ordering::Relaxed);
-
-                    // Determine which sound to play and apply volume settings
-                    let (note, base_velocity, volume_multiplier) = if curr_subdiv == 0 {
-                        // This is a main beat
-                        if curr_beat == 0 {
-                            // First beat of bar - accent
-                            (
-                                CLICK_ACCENT,
-                                VELOCITY_ACCENT,
-                                AUDIO_SETTINGS.get_metronome_accent_volume(),
-                            )
-                        } else {
-                            // Regular beat
-                            (
-                                CLICK_NORMAL,
-                                VELOCITY_NORMAL,
-                                AUDIO_SETTINGS.get_metronome_beat_volume(),
-                            )
-                        }
-                    } else {
-                        // This is a subdivision
-                        (
-                            CLICK_SUBDIVISION,
-                            VELOCITY_SUBDIVISION,
-                            AUDIO_SETTINGS.get_metronome_subdivision_volume(),
-                        )
-                    };
-
-                    // Apply volume and clamp to valid MIDI velocity range (0-127)
-                    let velocity = ((base_velocity as f32) * volume_multiplier).clamp(0.0, 127.0) as i32;
-
-                    // Trigger click sound
-                    synth.note_on(PERCUSSION_CHANNEL, note, velocity);
-
-                    // Check if we're in count-in mode
-                    let in_count_in = is_count_in_clone.load(Ordering::Relaxed);
-
-                    // Only send Tick event and play chord on main beats (not subdivisions)
-                    // and not during count-in
-                    if curr_subdiv == 0 {
-                        if !in_count_in {
-                            // Not in count-in, send tick and play chord normally
-                            let _ = AUDIO_EVT.0.try_send(AudioEvent::Tick);
-
-                            // Play chord if one is set
-                            if let Some(ref midi_notes) = *chord_clone.lock() {
-                                let chord_volume = AUDIO_SETTINGS.get_chord_volume();
-                                let chord_velocity =
-                                    ((CHORD_VELOCITY as f32) * chord_volume).clamp(0.0, 127.0) as i32;
-                                for &note in midi_notes {
-                                    synth.note_on(CHORD_CHANNEL, note as i32, chord_velocity);
-                                }
-                            }
-                        }
+            // Not every host knows when the buffer will be played, the buffer's own
+            // length is the least latency there is
+            let timestamp = info.timestamp();
+            let latency = timestamp
+                .playback
+                .duration_since(&timestamp.callback)
+                .unwrap_or_else(|| {
+                    Duration::from_secs_f64(buffer.len() as f64 / channels as f64 / rate)
+                });
+            record_latency(latency);
+
+            for block in buffer.chunks_mut(MAX_BLOCK_FRAMES * channels) {
+                // How many frames (multi-channel sample groups) we must fill in this block
+                let frames = block.len() / channels;
+                let (left, right) = (&mut left[..frames], &mut right[..frames]);
+                engine.render(left, right);
+
+                if channels == 2 {
+                    // Stereo output
+                    for (i, frame) in block.chunks_exact_mut(2).enumerate() {
+                        frame[0] = left[i];
+                        frame[1] = right[i];
                     }
-
-                    // Advance subdivision counter
-                    let next_subdiv = (curr_subdiv + 1) % subdivs as u8;
-                    current_subdivision.store(next_subdiv, Ordering::Relaxed);
-
-                    // If we've completed all subdivisions, advance beat counter
-                    if next_subdiv == 0 {
-                        let next_beat = (curr_beat + 1) % ticks_per_bar.load(Ordering::Relaxed);
-                        current_beat_in_bar.store(next_beat, Ordering::Relaxed);
-
-                        // If count-in is active and we've wrapped back to beat 0, disable count-in
-                        if in_count_in && next_beat == 0 {
-                            is_count_in_clone.store(false, Ordering::Relaxed);
-                        }
+                } else {
+                    // Mix down to mono (or duplicate to every channel)
+                    for (i, sample) in block.iter_mut().enumerate() {
+                        *sample = (left[i / channels] + right[i / channels]) * 0.5;
                     }
-
-                    // Schedule next click (subdivision or main beat)
-                    next_click_sample.store(
-                        next_click + samples_per_subdivision - left_over_frames,
-                        Ordering::Relaxed,
-                    );
                 }
             }
-
-            // Render synthesizer output
-            let mut synth = synth_clone.lock();
-
-            if channels == 2 {
-                // Stereo output
-                let mut left = vec![0.0; frames];
-                let mut right = vec![0.0; frames];
-
-                synth.render(&mut left[..], &mut right[..]);
-
-                // Mix synthesizer output into buffer
-                for (i, frame) in buffer.chunks_exact_mut(2).enumerate() {
-                    frame[0] += left[i];
-                    frame[1] += right[i];
-                }
-            } else {
-                // Mono output - need separate buffers for render
-                let mut left = vec![0.0; frames];
-                let mut right = vec![0.0; frames];
-                synth.render(&mut left[..], &mut right[..]);
-
-                // Mix down to mono
-                for (i, sample) in buffer.iter_mut().enumerate() {
-                    *sample += (left[i / channels] + right[i / channels]) * 0.5;
-                }
+        },
+        move |err| {
+            log::error!("Audio stream error: {}", err);
+            if matches!(err, StreamError::DeviceNotAvailable) {
+                let _ = OUTPUT_REQUEST.0.try_send(OutputRequest::DeviceLost);
             }
-
-            sample_counter.fetch_add(frames as u64, Ordering::Relaxed);
         },
-        move |err| eprintln!("Audio stream error: {}", err),
         None,
     )?;
-
     stream.play()?;
-    Ok(stream)
+
+    let mut status = OUTPUT.lock();
+    status.host = host;
+    status.device = device.name().unwrap_or_default();
+    status.sample_rate = sample_rate;
+    status.buffer_size = match config.buffer_size {
+        BufferSize::Fixed(frames) => Some(frames),
+        BufferSize::Default => None,
+    };
+    status.error = fallback;
+
+    Ok(Output {
+        _stream: stream,
+        producer,
+    })
+}
+
+/// A fresh engine at the stream's rate with the remembered sounds
+fn build_engine(sample_rate: u32, first: bool) -> Engine {
+    let source = if first {
+        // The remembered soundfont, else the bundled one, else the built-in sounds
+        open_sound_source(sample_rate)
+    } else {
+        reopen_sound_source(sample_rate)
+    };
+    let mut engine = Engine::new(source, sample_rate);
+    let settings = SOUND_FONT.lock().settings.clone();
+    for part in InstrumentPart::iter() {
+        engine.handle_command(AudioCommand::SetInstrument(part, settings.instrument(part)));
+    }
+    for (sound, sample) in ClickSound::iter().zip(open_click_samples(sample_rate)) {
+        engine.handle_command(AudioCommand::SetClickSample(sound, sample));
+    }
+    engine
 }
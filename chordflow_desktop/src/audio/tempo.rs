@@ -0,0 +1,268 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+/// What a tempo ramp counts before taking its next step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, Display, Serialize, Deserialize)]
+pub enum RampUnit {
+    #[default]
+    Bars,
+    #[strum(to_string = "Chord cycles")]
+    Cycles,
+    Seconds,
+}
+
+/// Shape of the tempo ramp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, Display, Serialize, Deserialize)]
+pub enum RampCurve {
+    /// Tempo moves a fraction of a step on every bar
+    Linear,
+    /// Tempo jumps a full step once every interval
+    #[default]
+    Stepwise,
+    /// Two steps towards the target, one step back
+    #[strum(to_string = "Two up, one down")]
+    TwoUpOneDown,
+}
+
+/// Configuration for an automatic BPM ramp
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TempoRamp {
+    pub start_bpm: u16,
+    pub target_bpm: u16,
+    /// BPM change per step
+    pub step: u16,
+    /// Number of units between two steps
+    pub every: u32,
+    pub unit: RampUnit,
+    pub curve: RampCurve,
+    /// Stop playback after one full interval at the target tempo
+    pub stop_at_target: bool,
+}
+
+impl Default for TempoRamp {
+    fn default() -> Self {
+        Self {
+            start_bpm: 80,
+            target_bpm: 120,
+            step: 4,
+            every: 4,
+            unit: RampUnit::Bars,
+            curve: RampCurve::Stepwise,
+            stop_at_target: false,
+        }
+    }
+}
+
+/// What the audio engine should do at a bar boundary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RampAction {
+    /// Play the next bar at this tempo
+    Play(u16),
+    /// The ramp is done and playback should stop
+    Stop,
+}
+
+/// Running state of a tempo ramp, advanced by the audio engine on bar boundaries
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoRampState {
+    pub ramp: TempoRamp,
+    bars: u32,
+    cycles: u32,
+    seconds: f64,
+    /// Progress at which the tempo first reached the target
+    reached_at: Option<f64>,
+}
+
+impl TempoRampState {
+    pub fn new(ramp: TempoRamp) -> Self {
+        Self {
+            ramp,
+            bars: 0,
+            cycles: 0,
+            seconds: 0.0,
+            reached_at: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new(self.ramp);
+    }
+
+    pub fn start_bpm(&self) -> u16 {
+        self.ramp.start_bpm
+    }
+
+    /// Register a finished bar and return the action for the next bar.
+    ///
+    /// `bar_seconds` is the duration of the bar that just finished and `cycle_complete`
+    /// tells whether that bar closed a chord cycle.
+    pub fn on_bar_complete(&mut self, bar_seconds: f64, cycle_complete: bool) -> RampAction {
+        self.bars += 1;
+        self.seconds += bar_seconds;
+        if cycle_complete {
+            self.cycles += 1;
+        }
+
+        let elapsed = match self.ramp.unit {
+            RampUnit::Bars => self.bars as f64,
+            RampUnit::Cycles => self.cycles as f64,
+            RampUnit::Seconds => self.seconds,
+        };
+        let progress = elapsed / self.ramp.every.max(1) as f64;
+
+        let steps = match self.ramp.curve {
+            RampCurve::Linear => progress,
+            RampCurve::Stepwise => progress.floor(),
+            RampCurve::TwoUpOneDown => {
+                let taken = progress.floor() as u32;
+                // +1, +1, -1 repeating: every group of three steps nets one step
+                (taken / 3 + [0, 1, 2][(taken % 3) as usize]) as f64
+            }
+        };
+
+        let distance = self.ramp.start_bpm.abs_diff(self.ramp.target_bpm) as f64;
+        let offset = steps * self.ramp.step as f64;
+
+        // Stop once the target has been played for a full interval
+        if self.ramp.stop_at_target {
+            match self.reached_at {
+                Some(reached) if progress >= reached + 1.0 => return RampAction::Stop,
+                None if offset >= distance => self.reached_at = Some(progress),
+                _ => {}
+            }
+        }
+
+        let offset = offset.min(distance).round() as u16;
+        let bpm = if self.ramp.target_bpm >= self.ramp.start_bpm {
+            self.ramp.start_bpm + offset
+        } else {
+            self.ramp.start_bpm - offset
+        };
+        RampAction::Play(bpm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_bars(state: &mut TempoRampState, bars: usize) -> Vec<RampAction> {
+        (0..bars)
+            .map(|_| state.on_bar_complete(2.0, false))
+            .collect()
+    }
+
+    #[test]
+    fn test_stepwise_ramp_clamps_at_target() {
+        let mut state = TempoRampState::new(TempoRamp {
+            start_bpm: 100,
+            target_bpm: 110,
+            step: 4,
+            every: 2,
+            ..Default::default()
+        });
+        let bpms: Vec<RampAction> = run_bars(&mut state, 8);
+        assert_eq!(
+            bpms,
+            [100, 104, 104, 108, 108, 110, 110, 110].map(RampAction::Play)
+        );
+    }
+
+    #[test]
+    fn test_ramp_down() {
+        let mut state = TempoRampState::new(TempoRamp {
+            start_bpm: 120,
+            target_bpm: 100,
+            step: 10,
+            every: 1,
+            ..Default::default()
+        });
+        assert_eq!(
+            run_bars(&mut state, 3),
+            [110, 100, 100].map(RampAction::Play)
+        );
+    }
+
+    #[test]
+    fn test_two_up_one_down() {
+        let mut state = TempoRampState::new(TempoRamp {
+            start_bpm: 100,
+            target_bpm: 200,
+            step: 2,
+            every: 1,
+            curve: RampCurve::TwoUpOneDown,
+            ..Default::default()
+        });
+        assert_eq!(
+            run_bars(&mut state, 6),
+            [102, 104, 102, 104, 106, 104].map(RampAction::Play)
+        );
+    }
+
+    #[test]
+    fn test_two_up_one_down_stops_after_target() {
+        let mut state = TempoRampState::new(TempoRamp {
+            start_bpm: 100,
+            target_bpm: 120,
+            step: 2,
+            every: 1,
+            curve: RampCurve::TwoUpOneDown,
+            stop_at_target: true,
+            ..Default::default()
+        });
+        let actions = run_bars(&mut state, 27);
+        // Ten net steps take 26 steps
+        assert!(!actions[..26].contains(&RampAction::Stop));
+        assert_eq!(actions[25], RampAction::Play(120));
+        assert_eq!(actions[26], RampAction::Stop);
+    }
+
+    #[test]
+    fn test_linear_moves_every_bar() {
+        let mut state = TempoRampState::new(TempoRamp {
+            start_bpm: 100,
+            target_bpm: 120,
+            step: 4,
+            every: 4,
+            curve: RampCurve::Linear,
+            ..Default::default()
+        });
+        assert_eq!(
+            run_bars(&mut state, 4),
+            [101, 102, 103, 104].map(RampAction::Play)
+        );
+    }
+
+    #[test]
+    fn test_cycles_and_stop_at_target() {
+        let mut state = TempoRampState::new(TempoRamp {
+            start_bpm: 100,
+            target_bpm: 104,
+            step: 4,
+            every: 1,
+            unit: RampUnit::Cycles,
+            stop_at_target: true,
+            ..Default::default()
+        });
+        assert_eq!(state.on_bar_complete(2.0, false), RampAction::Play(100));
+        assert_eq!(state.on_bar_complete(2.0, true), RampAction::Play(104));
+        assert_eq!(state.on_bar_complete(2.0, false), RampAction::Play(104));
+        assert_eq!(state.on_bar_complete(2.0, true), RampAction::Stop);
+    }
+
+    #[test]
+    fn test_seconds_unit() {
+        let mut state = TempoRampState::new(TempoRamp {
+            start_bpm: 60,
+            target_bpm: 80,
+            step: 5,
+            every: 10,
+            unit: RampUnit::Seconds,
+            ..Default::default()
+        });
+        assert_eq!(state.on_bar_complete(4.0, false), RampAction::Play(60));
+        assert_eq!(state.on_bar_complete(4.0, false), RampAction::Play(60));
+        assert_eq!(state.on_bar_complete(4.0, false), RampAction::Play(65));
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+
+/// Most notes a scheduled chord carries, extra notes are dropped
+pub const MAX_CHORD_NOTES: usize = 8;
+
+/// Chords the audio thread can hold ahead of time without allocating
+const TIMELINE_CAPACITY: usize = 32;
+
+/// A chord placed on the musical timeline by the practice state.
+///
+/// The notes live inline so chords can be passed to and dropped on the audio thread
+/// without touching the allocator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledChord {
+    /// Position of the chord in the practice session, starting at 0
+    pub index: u64,
+    /// Bar (counted from the start of the session) on which the chord starts
+    pub start_bar: u64,
+    /// Beat of the start bar the chord starts on, 0 for the downbeat
+    pub start_beat: u8,
+    /// Number of bars the chord sounds in, counting those it only sounds in partly
+    pub bars: u8,
+    notes: [u8; MAX_CHORD_NOTES],
+    note_count: u8,
+}
+
+impl ScheduledChord {
+    pub fn new(index: u64, start_bar: u64, start_beat: u8, bars: u8, midi_notes: &[u8]) -> Self {
+        let note_count = midi_notes.len().min(MAX_CHORD_NOTES);
+        let mut notes = [0; MAX_CHORD_NOTES];
+        notes[..note_count].copy_from_slice(&midi_notes[..note_count]);
+        Self {
+            index,
+            start_bar,
+            start_beat,
+            bars,
+            notes,
+            note_count: note_count as u8,
+        }
+    }
+
+    pub fn midi_notes(&self) -> &[u8] {
+        &self.notes[..self.note_count as usize]
+    }
+}
+
+/// Queue of upcoming chords owned by the audio thread.
+///
+/// The audio engine advances the timeline on every main beat, so chord changes happen at the
+/// exact sample of the bar line or beat no matter how late the UI is.
+#[derive(Debug)]
+pub struct Timeline {
+    current: Option<ScheduledChord>,
+    upcoming: VecDeque<ScheduledChord>,
+    announce: bool,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            current: None,
+            upcoming: VecDeque::with_capacity(TIMELINE_CAPACITY),
+            announce: false,
+        }
+    }
+}
+
+impl Timeline {
+    /// Queue a chord; when the queue is full it is dropped rather than grown
+    pub fn push(&mut self, chord: ScheduledChord) {
+        if self.upcoming.len() < TIMELINE_CAPACITY {
+            self.upcoming.push_back(chord);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.upcoming.clear();
+        self.announce = false;
+    }
+
+    /// Forget the upcoming chords after `index`, the practice state schedules new ones
+    pub fn truncate_after(&mut self, index: u64) {
+        self.upcoming.retain(|c| c.index <= index);
+    }
+
+    pub fn current(&self) -> Option<&ScheduledChord> {
+        self.current.as_ref()
+    }
+
+    /// The chord that follows the current one, if it is already scheduled
+    pub fn next(&self) -> Option<&ScheduledChord> {
+        self.upcoming.front()
+    }
+
+    /// Announce the current chord again on the next downbeat, e.g. after resuming playback
+    pub fn reannounce(&mut self) {
+        self.announce = true;
+    }
+
+    /// Bar on which playback resumes: the start of the current chord
+    pub fn resume_bar(&self) -> u64 {
+        self.current
+            .as_ref()
+            .or(self.upcoming.front())
+            .map(|c| c.start_bar)
+            .unwrap_or(0)
+    }
+
+    /// Move the timeline to `beat` (0-based) of `bar`.
+    /// Returns the new current chord when it changed (or has to be announced again).
+    pub fn advance_to(&mut self, bar: u64, beat: u8) -> Option<&ScheduledChord> {
+        let mut changed = std::mem::take(&mut self.announce);
+        while self
+            .upcoming
+            .front()
+            .is_some_and(|next| (next.start_bar, next.start_beat) <= (bar, beat))
+        {
+            self.current = self.upcoming.pop_front();
+            changed = true;
+        }
+        if changed {
+            self.current.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// 1-based bar within the current chord.
+    /// When the UI falls behind the current chord is held and its bars start over.
+    pub fn bar_in_chord(&self, bar: u64) -> u8 {
+        match &self.current {
+            Some(chord) => {
+                ((bar.saturating_sub(chord.start_bar)) % chord.bars.max(1) as u64) as u8 + 1
+            }
+            None => 1,
+        }
+    }
+
+    /// Beat of `bar` on which the next chord starts, when it starts within the bar
+    pub fn next_change_in(&self, bar: u64) -> Option<u8> {
+        self.upcoming
+            .front()
+            .filter(|next| next.start_bar == bar)
+            .map(|next| next.start_beat)
+    }
+
+    /// Whether `bar` is the last bar before the next chord change
+    pub fn is_last_bar(&self, bar: u64) -> bool {
+        match (self.upcoming.front(), &self.current) {
+            (Some(next), _) => next.start_bar <= bar + 1,
+            (None, Some(current)) => self.bar_in_chord(bar) == current.bars.max(1),
+            (None, None) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(index: u64, start_bar: u64, bars: u8) -> ScheduledChord {
+        ScheduledChord::new(index, start_bar, 0, bars, &[60, 64, 67])
+    }
+
+    #[test]
+    fn test_chords_change_on_their_start_bar() {
+        let mut timeline = Timeline::default();
+        timeline.push(chord(0, 0, 2));
+        timeline.push(chord(1, 2, 1));
+
+        assert_eq!(timeline.advance_to(0, 0).map(|c| c.index), Some(0));
+        assert!(!timeline.is_last_bar(0));
+        assert_eq!(timeline.advance_to(1, 0), None);
+        assert_eq!(timeline.bar_in_chord(1), 2);
+        assert!(timeline.is_last_bar(1));
+        assert_eq!(timeline.advance_to(2, 0).map(|c| c.index), Some(1));
+        assert_eq!(timeline.bar_in_chord(2), 1);
+    }
+
+    #[test]
+    fn test_chord_changes_within_a_bar() {
+        let mut timeline = Timeline::default();
+        timeline.push(chord(0, 0, 1));
+        timeline.push(ScheduledChord::new(1, 0, 2, 1, &[67]));
+        assert_eq!(timeline.advance_to(0, 0).map(|c| c.index), Some(0));
+        assert_eq!(timeline.next_change_in(0), Some(2));
+        assert!(timeline.is_last_bar(0));
+        assert_eq!(timeline.advance_to(0, 1), None);
+        assert_eq!(timeline.advance_to(0, 2).map(|c| c.index), Some(1));
+        assert_eq!(timeline.bar_in_chord(0), 1);
+    }
+
+    #[test]
+    fn test_holds_last_chord_when_queue_runs_dry() {
+        let mut timeline = Timeline::default();
+        timeline.push(chord(0, 0, 2));
+        timeline.advance_to(0, 0);
+        assert_eq!(timeline.advance_to(2, 0), None);
+        assert_eq!(timeline.bar_in_chord(2), 1);
+        assert_eq!(timeline.bar_in_chord(3), 2);
+        assert!(timeline.is_last_bar(3));
+    }
+
+    #[test]
+    fn test_reannounce_after_resume() {
+        let mut timeline = Timeline::default();
+        timeline.push(chord(0, 0, 2));
+        timeline.push(chord(1, 2, 2));
+        timeline.advance_to(0, 0);
+        timeline.advance_to(2, 0);
+        assert_eq!(timeline.resume_bar(), 2);
+        timeline.reannounce();
+        assert_eq!(timeline.advance_to(2, 0).map(|c| c.index), Some(1));
+        assert_eq!(timeline.advance_to(2, 0), None);
+    }
+}
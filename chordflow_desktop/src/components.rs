@@ -1,3 +1,16 @@
 // Components module for UI components
 
+pub mod articulation;
+pub mod av_calibration;
+pub mod bass;
+pub mod click_samples;
+pub mod drums;
+pub mod export;
+pub mod gap_click;
+pub mod library;
+pub mod output_device;
+pub mod presets;
 pub mod settings_panel;
+pub mod sound_font;
+pub mod stats;
+pub mod tempo_trainer;
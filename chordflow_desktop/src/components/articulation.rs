@@ -0,0 +1,69 @@
+use dioxus::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::{
+    audio::articulation::{ArticulationStyle, ChordArticulation},
+    components::settings_panel::NumberField,
+    ui::app::MetronomeState,
+    AudioCommand, AUDIO_CMD,
+};
+
+#[component]
+pub fn ArticulationSettings() -> Element {
+    let mut metronome_state: Signal<MetronomeState> = use_context();
+    let articulation = metronome_state.read().articulation;
+    let ticks_per_bar = metronome_state.read().ticks_per_bar;
+
+    let mut update = move |articulation: ChordArticulation| {
+        metronome_state.write().articulation = articulation;
+        let _ = AUDIO_CMD
+            .0
+            .try_send(AudioCommand::SetArticulation(articulation));
+    };
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "Chord Articulation" }
+
+            label { class: "settings-row",
+                span { "Style" }
+                select {
+                    class: "select-styled",
+                    onchange: move |e| {
+                        if let Some(style) = ArticulationStyle::iter().find(|s| s.to_string() == e.value()) {
+                            update(ChordArticulation { style, ..articulation });
+                        }
+                    },
+                    for style in ArticulationStyle::iter() {
+                        option { selected: style == articulation.style, "{style}" }
+                    }
+                }
+            }
+
+            if articulation.style == ArticulationStyle::Staccato {
+                div { class: "settings-row",
+                    span { "Hit beats" }
+                    div { class: "segmented-control",
+                        for beat in 0..ticks_per_bar {
+                            button {
+                                key: "{beat}",
+                                class: if articulation.is_beat_selected(beat) { "segment active" } else { "segment" },
+                                onclick: move |_| {
+                                    let mut articulation = articulation;
+                                    articulation.toggle_beat(beat);
+                                    update(articulation);
+                                },
+                                "{beat + 1}"
+                            }
+                        }
+                    }
+                }
+                NumberField {
+                    label: "Hit length (% of a beat)",
+                    value: articulation.gate_percent as u32,
+                    on_change: move |v: u32| update(ChordArticulation { gate_percent: v.min(100) as u8, ..articulation }),
+                }
+            }
+        }
+    }
+}
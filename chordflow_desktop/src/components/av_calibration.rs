@@ -0,0 +1,130 @@
+use std::time::Instant;
+
+use dioxus::prelude::*;
+
+use crate::{
+    audio::output::{set_av_offset, MAX_AV_OFFSET_MS, OUTPUT},
+    ui::app::{AppState, MetronomeState},
+};
+
+/// Taps averaged into one calibration
+const CALIBRATION_TAPS: usize = 8;
+
+/// How far a tap is from the nearest shown beat in ms, negative when it came first
+fn tap_error(tap: Instant, beat_shown_at: Instant, bpm: u16) -> f64 {
+    let beat_ms = 60_000.0 / bpm.max(1) as f64;
+    let since_beat = tap.saturating_duration_since(beat_shown_at).as_secs_f64() * 1000.0;
+    let error = since_beat % beat_ms;
+    if error > beat_ms / 2.0 {
+        error - beat_ms
+    } else {
+        error
+    }
+}
+
+/// Median is robust against the odd missed or doubled tap
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+#[component]
+pub fn AvCalibration() -> Element {
+    let app_state: Signal<AppState> = use_context();
+    let metronome_state: Signal<MetronomeState> = use_context();
+    let mut offset = use_signal(|| OUTPUT.lock().settings.av_offset_ms);
+    let mut taps = use_signal(Vec::<f64>::new);
+
+    let mut apply = move |ms: i32| {
+        set_av_offset(ms);
+        offset.set(OUTPUT.lock().settings.av_offset_ms);
+    };
+
+    let mut tap = move || {
+        let now = Instant::now();
+        let metronome = metronome_state.read();
+        let Some(beat_shown_at) = metronome.beat_shown_at else {
+            return;
+        };
+        taps.write()
+            .push(tap_error(now, beat_shown_at, metronome.bpm));
+        if taps.read().len() >= CALIBRATION_TAPS {
+            // Tapping after the shown beat means the click is heard later than it is shown
+            let error = median(&mut taps.write());
+            taps.write().clear();
+            apply(offset() + error.round() as i32);
+        }
+    };
+
+    let playing = app_state.read().is_playing;
+    let tapped = taps.read().len();
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "Audio/Visual Offset" }
+
+            label { class: "settings-row",
+                span { "Beat display delay: {offset} ms" }
+                input {
+                    r#type: "range",
+                    min: -MAX_AV_OFFSET_MS,
+                    max: MAX_AV_OFFSET_MS,
+                    step: 5,
+                    value: offset(),
+                    oninput: move |e| {
+                        if let Ok(ms) = e.value().parse() {
+                            apply(ms);
+                        }
+                    },
+                }
+            }
+
+            div { class: "settings-row",
+                span { class: "label-small",
+                    if playing {
+                        "Tap along with the click ({tapped}/{CALIBRATION_TAPS})"
+                    } else {
+                        "Start playback, then tap along with the click you hear"
+                    }
+                }
+                button {
+                    class: "btn-parse-inline",
+                    disabled: !playing,
+                    // The press, not the release, is when the beat was felt
+                    onmousedown: move |_| tap(),
+                    "Tap"
+                }
+                button {
+                    class: "btn-parse-inline",
+                    onclick: move |_| {
+                        taps.write().clear();
+                        apply(0);
+                    },
+                    "Reset"
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_tap_error_is_relative_to_the_nearest_beat() {
+        let beat = Instant::now();
+        let error = |ms| tap_error(beat + Duration::from_millis(ms), beat, 120);
+        // 120 bpm, a beat every 500 ms
+        assert!((error(80) - 80.0).abs() < 1e-6);
+        assert!((error(460) + 40.0).abs() < 1e-6);
+        assert!((error(1030) - 30.0).abs() < 1e-6);
+        assert_eq!(median(&mut [40.0, -300.0, 60.0, 50.0]), 45.0);
+    }
+}
@@ -0,0 +1,68 @@
+use dioxus::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::{
+    audio::bass::{BassApproach, BassLine, BassStyle},
+    ui::app::MetronomeState,
+    AudioCommand, AUDIO_CMD,
+};
+
+#[component]
+pub fn BassSettings() -> Element {
+    let mut metronome_state: Signal<MetronomeState> = use_context();
+    let bass = metronome_state.read().bass;
+    let enabled = metronome_state.read().bass_enabled;
+
+    let mut update = move |enabled: bool, bass: BassLine| {
+        metronome_state.write().bass_enabled = enabled;
+        metronome_state.write().bass = bass;
+        let _ = AUDIO_CMD
+            .0
+            .try_send(AudioCommand::SetBass(enabled.then_some(bass)));
+    };
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "Bass Line" }
+
+            label { class: "settings-row",
+                span { "Play a generated bass line" }
+                input {
+                    r#type: "checkbox",
+                    checked: enabled,
+                    onchange: move |e| update(e.checked(), bass),
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Style" }
+                select {
+                    class: "select-styled",
+                    onchange: move |e| {
+                        if let Some(style) = BassStyle::iter().find(|s| s.to_string() == e.value()) {
+                            update(enabled, BassLine { style, ..bass });
+                        }
+                    },
+                    for style in BassStyle::iter() {
+                        option { selected: style == bass.style, "{style}" }
+                    }
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Approach" }
+                select {
+                    class: "select-styled",
+                    onchange: move |e| {
+                        if let Some(approach) = BassApproach::iter().find(|a| a.to_string() == e.value()) {
+                            update(enabled, BassLine { approach, ..bass });
+                        }
+                    },
+                    for approach in BassApproach::iter() {
+                        option { selected: approach == bass.approach, "{approach}" }
+                    }
+                }
+            }
+        }
+    }
+}
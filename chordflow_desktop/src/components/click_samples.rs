@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+
+use dioxus::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::audio::samples::{set_click_sample, ClickSound, CLICK_SAMPLES};
+
+#[component]
+pub fn ClickSamplePicker() -> Element {
+    let mut status = use_signal(|| CLICK_SAMPLES.lock().clone());
+    let mut loading = use_signal(|| false);
+
+    let mut set_sample = move |sound: ClickSound, path: Option<PathBuf>| {
+        loading.set(true);
+        spawn(async move {
+            let _ = tokio::task::spawn_blocking(move || set_click_sample(sound, path)).await;
+            status.set(CLICK_SAMPLES.lock().clone());
+            loading.set(false);
+        });
+    };
+
+    let current = status.read();
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "Click Samples" }
+            span { class: "label-small", "WAV, FLAC or OGG, played at the metronome volumes above" }
+
+            for sound in ClickSound::iter() {
+                div { key: "{sound}", class: "settings-row",
+                    span { "{sound}" }
+                    if let Some(path) = &current.settings.paths[sound as usize] {
+                        span { class: "label-small",
+                            {path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()}
+                        }
+                        button {
+                            class: "btn-parse-inline",
+                            disabled: loading(),
+                            onclick: move |_| set_sample(sound, None),
+                            "Use click"
+                        }
+                    }
+                    input {
+                        r#type: "file",
+                        accept: ".wav,.flac,.ogg",
+                        disabled: loading(),
+                        onchange: move |e| {
+                            if let Some(file) = e.files().first() {
+                                set_sample(sound, Some(file.path()));
+                            }
+                        },
+                    }
+                }
+                if let Some(error) = &current.errors[sound as usize] {
+                    div { class: "parse-error", "{error}" }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use dioxus::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::{
+    audio::drums::{DrumGroove, DrumStyle},
+    ui::app::MetronomeState,
+    AudioCommand, AUDIO_CMD,
+};
+
+#[component]
+pub fn DrumSettings() -> Element {
+    let mut metronome_state: Signal<MetronomeState> = use_context();
+    let drums = metronome_state.read().drums;
+    let enabled = metronome_state.read().drums_enabled;
+
+    let mut update = move |enabled: bool, drums: DrumGroove| {
+        metronome_state.write().drums_enabled = enabled;
+        metronome_state.write().drums = drums;
+        let _ = AUDIO_CMD
+            .0
+            .try_send(AudioCommand::SetDrums(enabled.then_some(drums)));
+    };
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "Drums" }
+
+            label { class: "settings-row",
+                span { "Play a drum groove" }
+                input {
+                    r#type: "checkbox",
+                    checked: enabled,
+                    onchange: move |e| update(e.checked(), drums),
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Groove" }
+                select {
+                    class: "select-styled",
+                    onchange: move |e| {
+                        if let Some(style) = DrumStyle::iter().find(|s| s.to_string() == e.value()) {
+                            update(enabled, DrumGroove { style, ..drums });
+                        }
+                    },
+                    for style in DrumStyle::iter() {
+                        option { selected: style == drums.style, "{style}" }
+                    }
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Keep the click" }
+                input {
+                    r#type: "checkbox",
+                    checked: drums.keep_click,
+                    onchange: move |e| update(enabled, DrumGroove { keep_click: e.checked(), ..drums }),
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Fill before chord changes" }
+                input {
+                    r#type: "checkbox",
+                    checked: drums.fills,
+                    onchange: move |e| update(enabled, DrumGroove { fills: e.checked(), ..drums }),
+                }
+            }
+        }
+    }
+}
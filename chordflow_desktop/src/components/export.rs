@@ -0,0 +1,159 @@
+use dioxus::prelude::*;
+
+use crate::{
+    audio::{
+        midi_export::export_midi,
+        render::{write_wav, OfflineRenderer, RenderLength, WAV_SAMPLE_RATE},
+    },
+    components::settings_panel::NumberField,
+    ui::app::{AppState, MetronomeState},
+    AudioCommand,
+};
+
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Wav,
+    Midi,
+}
+
+#[component]
+pub fn SessionExport() -> Element {
+    let app_state: Signal<AppState> = use_context();
+    let metronome_state: Signal<MetronomeState> = use_context();
+    let mut by_minutes = use_signal(|| false);
+    let mut cycles = use_signal(|| 4u32);
+    let mut minutes = use_signal(|| 5u32);
+    let mut busy = use_signal(|| false);
+    let mut message = use_signal(|| None::<Result<String, String>>);
+
+    let export = move |format: ExportFormat| {
+        let mut session = app_state.read().session_from_start();
+        let cycle_length = session.cycle_length();
+        if cycle_length == 0 {
+            message.set(Some(Err("This mode has no chords to render".to_string())));
+            return;
+        }
+        let length = if by_minutes() {
+            RenderLength::Seconds(minutes() * 60)
+        } else {
+            RenderLength::Cycles(cycles())
+        };
+        let metronome = metronome_state.read();
+        let count_in = metronome.count_in_enabled;
+        let beats_per_bar = metronome.ticks_per_bar;
+        let mut commands = Vec::from(metronome.settings_commands());
+        commands.push(AudioCommand::SetComping(session.comping_pattern()));
+        drop(metronome);
+
+        spawn(async move {
+            let (filter, extension) = match format {
+                ExportFormat::Wav => ("WAV audio", "wav"),
+                ExportFormat::Midi => ("MIDI file", "mid"),
+            };
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .add_filter(filter, &[extension])
+                .set_file_name(format!("chordflow.{}", extension))
+                .save_file()
+                .await
+            else {
+                return;
+            };
+            busy.set(true);
+            message.set(None);
+            let path = file.path().to_path_buf();
+            let result = tokio::task::spawn_blocking(move || {
+                // A duration cycles through the mode for as long as it takes
+                let mut remaining = match length {
+                    RenderLength::Cycles(cycles) => cycles as usize * cycle_length,
+                    RenderLength::Seconds(_) => usize::MAX,
+                };
+                let mut next_chord = || {
+                    remaining = remaining.checked_sub(1)?;
+                    session.next_session_chord()
+                };
+                match format {
+                    ExportFormat::Wav => {
+                        let mut renderer = OfflineRenderer::with_current_sounds(WAV_SAMPLE_RATE);
+                        for command in commands {
+                            renderer.send(command);
+                        }
+                        let rendered = renderer.render_session(length, count_in, || {
+                            next_chord().map(|c| c.to_scheduled())
+                        });
+                        write_wav(&path, &rendered)
+                    }
+                    ExportFormat::Midi => {
+                        export_midi(&path, length, commands, beats_per_bar, next_chord)
+                    }
+                }
+                .map(|_| path)
+            })
+            .await;
+            message.set(Some(match result {
+                Ok(Ok(path)) => Ok(format!("Saved {}", path.display())),
+                Ok(Err(e)) => Err(format!("{:#}", e)),
+                Err(e) => Err(e.to_string()),
+            }));
+            busy.set(false);
+        });
+    };
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "Export" }
+
+            div { class: "settings-row",
+                span { "Length" }
+                div { class: "segmented-control",
+                    button {
+                        class: if by_minutes() { "segment" } else { "segment active" },
+                        onclick: move |_| by_minutes.set(false),
+                        "Cycles"
+                    }
+                    button {
+                        class: if by_minutes() { "segment active" } else { "segment" },
+                        onclick: move |_| by_minutes.set(true),
+                        "Minutes"
+                    }
+                }
+            }
+            if by_minutes() {
+                NumberField {
+                    label: "Minutes",
+                    value: minutes(),
+                    on_change: move |v: u32| minutes.set(v.clamp(1, 120)),
+                }
+            } else {
+                NumberField {
+                    label: "Cycles",
+                    value: cycles(),
+                    on_change: move |v: u32| cycles.set(v.clamp(1, 100)),
+                }
+            }
+
+            div { class: "settings-row",
+                span { class: "label-small", "Click, chords and accompaniment as set up above" }
+                button {
+                    class: "btn-parse-inline",
+                    disabled: busy(),
+                    onclick: move |_| export(ExportFormat::Wav),
+                    if busy() { "Rendering…" } else { "Save WAV…" }
+                }
+                button {
+                    class: "btn-parse-inline",
+                    disabled: busy(),
+                    title: "Click, chords, bass and drums on their own tracks, with tempo and chord markers",
+                    onclick: move |_| export(ExportFormat::Midi),
+                    "Save MIDI…"
+                }
+            }
+
+            if let Some(Ok(saved)) = message() {
+                span { class: "label-small", "{saved}" }
+            }
+            if let Some(Err(error)) = message() {
+                div { class: "parse-error", "{error}" }
+            }
+        }
+    }
+}
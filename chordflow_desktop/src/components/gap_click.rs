@@ -0,0 +1,82 @@
+use dioxus::prelude::*;
+
+use crate::{
+    audio::gap::GapClick, components::settings_panel::NumberField, ui::app::MetronomeState,
+    AudioCommand, AUDIO_CMD,
+};
+
+#[component]
+pub fn GapClickSettings() -> Element {
+    let mut metronome_state: Signal<MetronomeState> = use_context();
+    let gap = metronome_state.read().gap_click;
+    let enabled = metronome_state.read().gap_click_enabled;
+    let ticks_per_bar = metronome_state.read().ticks_per_bar;
+
+    let mut update = move |enabled: bool, gap: GapClick| {
+        metronome_state.write().gap_click_enabled = enabled;
+        metronome_state.write().gap_click = gap;
+        let _ = AUDIO_CMD
+            .0
+            .try_send(AudioCommand::SetGapClick(enabled.then_some(gap)));
+    };
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "Gap Click" }
+
+            label { class: "settings-row",
+                span { "Mute the metronome in a pattern" }
+                input {
+                    r#type: "checkbox",
+                    checked: enabled,
+                    onchange: move |e| update(e.checked(), gap),
+                }
+            }
+
+            NumberField {
+                label: "Bars played",
+                value: gap.play_bars as u32,
+                on_change: move |v: u32| update(enabled, GapClick { play_bars: v.clamp(1, 64) as u8, ..gap }),
+            }
+            NumberField {
+                label: "Bars muted",
+                value: gap.mute_bars as u32,
+                min: 0,
+                on_change: move |v: u32| update(enabled, GapClick { mute_bars: v.min(64) as u8, ..gap }),
+            }
+            NumberField {
+                label: "Random beat mute (%)",
+                value: gap.random_mute_percent as u32,
+                min: 0,
+                on_change: move |v: u32| update(enabled, GapClick { random_mute_percent: v.min(100) as u8, ..gap }),
+            }
+
+            div { class: "settings-row",
+                span { "Always mute beats" }
+                div { class: "segmented-control",
+                    for beat in 0..ticks_per_bar {
+                        button {
+                            key: "{beat}",
+                            class: if gap.is_beat_selected(beat) { "segment active" } else { "segment" },
+                            onclick: move |_| {
+                                let mut gap = gap;
+                                gap.toggle_beat(beat);
+                                update(enabled, gap);
+                            },
+                            "{beat + 1}"
+                        }
+                    }
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Mute chords as well" }
+                input {
+                    r#type: "checkbox",
+                    checked: gap.mute_chords,
+                    onchange: move |e| update(enabled, GapClick { mute_chords: e.checked(), ..gap }),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use dioxus::prelude::*;
+
+use crate::{
+    import::to_progression,
+    library::{delete_song, load_library, save_song, LibrarySong, RecentSongs},
+    state::{modes::ModeOption, song::Song},
+    ui::{
+        app::{AppState, MetronomeState},
+        top_zone::time_signature_selector::BEATS_PER_BAR,
+    },
+    AudioCommand, AUDIO_CMD,
+};
+
+#[component]
+pub fn LibraryPanel(show: Signal<bool>) -> Element {
+    let mut app_state: Signal<AppState> = use_context();
+    let mut metronome_state: Signal<MetronomeState> = use_context();
+    let mut songs = use_signal(load_library);
+    let mut recent = use_signal(RecentSongs::load);
+    let mut query = use_signal(String::new);
+    let mut name = use_signal(String::new);
+    let mut key = use_signal(String::new);
+    let mut tags = use_signal(String::new);
+    let mut notes = use_signal(String::new);
+    let mut message = use_signal(|| None::<Result<String, String>>);
+
+    let mut save = move || {
+        let state = app_state.read();
+        let config = &state.progression_config;
+        let song = match config.unedited_song() {
+            Some(song) => song.clone(),
+            None => Song::from_progression(&config.chords, state.beats_per_bar),
+        };
+        let mut saved = LibrarySong::new(
+            &name(),
+            &song,
+            metronome_state.read().bpm,
+            state.beats_per_bar,
+        );
+        drop(state);
+        if !key().trim().is_empty() {
+            saved.key = key().trim().to_string();
+        }
+        saved.tags = tags()
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        saved.notes = notes();
+        message.set(Some(match save_song(&saved) {
+            Ok(()) => {
+                songs.set(load_library());
+                Ok(format!("Saved {}", saved.name))
+            }
+            Err(e) => Err(format!("{:#}", e)),
+        }));
+    };
+
+    let mut load = move |saved: LibrarySong| {
+        let result = saved.song().and_then(|song| {
+            let chords = to_progression(&song.chords())?;
+            Ok((song, chords))
+        });
+        let (song, chords) = match result {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                message.set(Some(Err(format!("{:#}", e))));
+                return;
+            }
+        };
+        if BEATS_PER_BAR.contains(&saved.beats_per_bar) {
+            metronome_state.write().ticks_per_bar = saved.beats_per_bar;
+            app_state.write().beats_per_bar = saved.beats_per_bar;
+            let _ = AUDIO_CMD
+                .0
+                .try_send(AudioCommand::SetTimeSignature(saved.beats_per_bar));
+        }
+        metronome_state.write().bpm = saved.bpm;
+        let _ = AUDIO_CMD.0.try_send(AudioCommand::SetBPM(saved.bpm));
+        {
+            let mut state = app_state.write();
+            state.selected_mode = ModeOption::Custom;
+            state.preset = None;
+            state.progression_config.chords = chords;
+            state.progression_config.song = Some(song);
+            state.progression_config.loop_section = None;
+        }
+        app_state.write().restart();
+        recent.write().played(&saved.name);
+        // Saving again keeps the details of the loaded song
+        name.set(saved.name.clone());
+        key.set(saved.key.clone());
+        tags.set(saved.tags.join(", "));
+        notes.set(saved.notes.clone());
+        message.set(Some(Ok(format!("Loaded {}", saved.name))));
+    };
+
+    let mut delete = move |song_name: String| {
+        message.set(Some(match delete_song(&song_name) {
+            Ok(()) => {
+                songs.set(load_library());
+                Ok(format!("Deleted {}", song_name))
+            }
+            Err(e) => Err(format!("{:#}", e)),
+        }));
+    };
+
+    if !show() {
+        return rsx! { div {} };
+    }
+
+    let can_save =
+        !app_state.read().progression_config.chords.is_empty() && !name().trim().is_empty();
+    let found: Vec<(LibrarySong, String)> = songs
+        .read()
+        .iter()
+        .filter(|song| song.matches(&query()))
+        .map(|song| {
+            let mut details = vec![
+                format!("{} bpm", song.bpm),
+                format!("{}/4", song.beats_per_bar),
+            ];
+            if !song.key.is_empty() {
+                details.insert(0, song.key.clone());
+            }
+            details.extend(song.tags.iter().cloned());
+            (song.clone(), details.join(" · "))
+        })
+        .collect();
+    // Recently played songs that are still in the library
+    let recently_played: Vec<LibrarySong> = recent
+        .read()
+        .names
+        .iter()
+        .filter_map(|n| songs.read().iter().find(|song| &song.name == n).cloned())
+        .collect();
+
+    rsx! {
+        div {
+            class: "settings-overlay",
+            onclick: move |_| show.set(false),
+
+            div {
+                class: "settings-panel",
+                onclick: move |e| e.stop_propagation(),
+
+                div { class: "settings-header",
+                    h2 { class: "settings-title", "Library" }
+                    button {
+                        class: "settings-close",
+                        onclick: move |_| show.set(false),
+                        "✕"
+                    }
+                }
+
+                div { class: "settings-content",
+                    div { class: "settings-section",
+                        h3 { class: "section-title", "Save the custom progression" }
+                        label { class: "settings-row",
+                            span { "Name" }
+                            input {
+                                class: "text-field",
+                                r#type: "text",
+                                value: "{name}",
+                                oninput: move |e| name.set(e.value()),
+                            }
+                        }
+                        label { class: "settings-row",
+                            span { "Key" }
+                            input {
+                                class: "text-field",
+                                r#type: "text",
+                                placeholder: "From the chart",
+                                value: "{key}",
+                                oninput: move |e| key.set(e.value()),
+                            }
+                        }
+                        label { class: "settings-row",
+                            span { "Tags" }
+                            input {
+                                class: "text-field",
+                                r#type: "text",
+                                placeholder: "standard, ballad",
+                                value: "{tags}",
+                                oninput: move |e| tags.set(e.value()),
+                            }
+                        }
+                        label { class: "settings-row",
+                            span { "Notes" }
+                            input {
+                                class: "text-field",
+                                r#type: "text",
+                                value: "{notes}",
+                                oninput: move |e| notes.set(e.value()),
+                            }
+                        }
+                        div { class: "settings-row",
+                            span { class: "label-small", "Tempo, time signature and bars per chord are saved as they are set" }
+                            button {
+                                class: "btn-parse-inline",
+                                disabled: !can_save,
+                                onclick: move |_| save(),
+                                "Save"
+                            }
+                        }
+                        if let Some(Ok(done)) = message() {
+                            span { class: "label-small", "{done}" }
+                        }
+                        if let Some(Err(error)) = message() {
+                            div { class: "parse-error", "{error}" }
+                        }
+                    }
+
+                    if !recently_played.is_empty() {
+                        div { class: "settings-section",
+                            h3 { class: "section-title", "Recently played" }
+                            for song in recently_played {
+                                div { key: "{song.name}", class: "settings-row",
+                                    span { "{song.name}" }
+                                    button {
+                                        class: "btn-parse-inline",
+                                        onclick: {
+                                            let song = song.clone();
+                                            move |_| load(song.clone())
+                                        },
+                                        "Load"
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    div { class: "settings-section",
+                        h3 { class: "section-title", "Songs" }
+                        input {
+                            class: "text-field",
+                            r#type: "search",
+                            placeholder: "Search names, keys, tags and notes",
+                            value: "{query}",
+                            oninput: move |e| query.set(e.value()),
+                        }
+                        if found.is_empty() {
+                            span { class: "label-small", "No songs found" }
+                        }
+                        for (song, details) in found {
+                            div { key: "{song.name}", class: "settings-row library-song",
+                                div {
+                                    span { "{song.name} " }
+                                    span { class: "label-small", "{details}" }
+                                    if !song.notes.is_empty() {
+                                        div { class: "label-small", "{song.notes}" }
+                                    }
+                                }
+                                div {
+                                    button {
+                                        class: "btn-parse-inline",
+                                        onclick: {
+                                            let song = song.clone();
+                                            move |_| load(song.clone())
+                                        },
+                                        "Load"
+                                    }
+                                    button {
+                                        class: "btn-parse-inline",
+                                        onclick: {
+                                            let song_name = song.name.clone();
+                                            move |_| delete(song_name.clone())
+                                        },
+                                        "Delete"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use dioxus::prelude::*;
+
+use crate::audio::output::{
+    list_output_devices, output_latency, set_output, OutputSettings, BUFFER_SIZES, OUTPUT,
+};
+
+/// Time the audio thread gets to rebuild the stream before the status is read again
+const REBUILD_WAIT: Duration = Duration::from_millis(500);
+/// How often the reported latency is read
+const LATENCY_REFRESH: Duration = Duration::from_secs(1);
+
+#[component]
+pub fn OutputDevicePicker() -> Element {
+    let mut status = use_signal(|| OUTPUT.lock().clone());
+    let mut devices = use_signal(Vec::new);
+    let mut switching = use_signal(|| false);
+    let mut latency = use_signal(output_latency);
+
+    use_future(move || async move {
+        loop {
+            tokio::time::sleep(LATENCY_REFRESH).await;
+            latency.set(output_latency());
+        }
+    });
+
+    let mut refresh_devices = move || {
+        spawn(async move {
+            // Probing every host can block for a moment
+            if let Ok(list) = tokio::task::spawn_blocking(list_output_devices).await {
+                devices.set(list);
+            }
+        });
+    };
+    use_hook(|| refresh_devices());
+
+    let mut switch_to = move |settings: OutputSettings| {
+        switching.set(true);
+        set_output(settings);
+        spawn(async move {
+            tokio::time::sleep(REBUILD_WAIT).await;
+            status.set(OUTPUT.lock().clone());
+            switching.set(false);
+        });
+    };
+
+    let current = status.read();
+    let devices = devices.read();
+    let chosen = current.settings.clone();
+    let selected = devices.iter().position(|d| {
+        chosen.device.as_deref() == Some(d.name.as_str())
+            && chosen.host.as_deref().is_none_or(|host| host == d.host)
+    });
+    let sample_rates = selected
+        .map(|i| devices[i].sample_rates.clone())
+        .unwrap_or_default();
+    // Without a known range the driver's own buffer size is the only safe choice
+    let buffer_sizes: Vec<u32> = selected
+        .and_then(|i| devices[i].buffer_sizes)
+        .map(|(min, max)| {
+            BUFFER_SIZES
+                .into_iter()
+                .filter(|size| (min..=max).contains(size))
+                .collect()
+        })
+        .unwrap_or_default();
+    let latency_ms = latency().as_secs_f64() * 1000.0;
+    let buffer = match current.buffer_size {
+        Some(frames) => format!("{} frames", frames),
+        None => "driver default".to_string(),
+    };
+    let device_list: Vec<(usize, String)> = devices
+        .iter()
+        .enumerate()
+        .map(|(i, d)| (i, format!("{}: {}", d.host, d.name)))
+        .collect();
+    let choices = devices.clone();
+    let rate_settings = chosen.clone();
+    let buffer_settings = chosen.clone();
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "Audio Output" }
+
+            div { class: "settings-row",
+                span {
+                    if switching() {
+                        "Switching…"
+                    } else {
+                        "{current.device} ({current.host}, {current.sample_rate} Hz)"
+                    }
+                }
+                button {
+                    class: "btn-parse-inline",
+                    onclick: move |_| refresh_devices(),
+                    "Refresh"
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Device" }
+                select {
+                    class: "select-styled",
+                    disabled: switching(),
+                    onchange: move |e| {
+                        let device = e.value().parse::<usize>().ok().and_then(|i| choices.get(i));
+                        switch_to(OutputSettings {
+                            host: device.map(|d| d.host.clone()),
+                            device: device.map(|d| d.name.clone()),
+                            // Rates and buffers differ between devices, start from the defaults
+                            sample_rate: None,
+                            buffer_size: None,
+                            ..OutputSettings::default()
+                        });
+                    },
+                    option { value: "", selected: selected.is_none(), "System default" }
+                    for (i, label) in device_list {
+                        option { key: "{i}", value: "{i}", selected: selected == Some(i), "{label}" }
+                    }
+                }
+            }
+
+            if !sample_rates.is_empty() {
+                label { class: "settings-row",
+                    span { "Sample rate" }
+                    select {
+                        class: "select-styled",
+                        disabled: switching(),
+                        onchange: move |e| {
+                            switch_to(OutputSettings {
+                                sample_rate: e.value().parse().ok(),
+                                ..rate_settings.clone()
+                            });
+                        },
+                        option { value: "", selected: chosen.sample_rate.is_none(), "Device default" }
+                        for rate in sample_rates {
+                            option {
+                                key: "{rate}",
+                                value: "{rate}",
+                                selected: chosen.sample_rate == Some(rate),
+                                "{rate} Hz"
+                            }
+                        }
+                    }
+                }
+            }
+
+            if !buffer_sizes.is_empty() {
+                label { class: "settings-row",
+                    span { "Buffer size" }
+                    select {
+                        class: "select-styled",
+                        disabled: switching(),
+                        onchange: move |e| {
+                            switch_to(OutputSettings {
+                                buffer_size: e.value().parse().ok(),
+                                ..buffer_settings.clone()
+                            });
+                        },
+                        option { value: "", selected: chosen.buffer_size.is_none(), "Driver default" }
+                        for size in buffer_sizes {
+                            option {
+                                key: "{size}",
+                                value: "{size}",
+                                selected: chosen.buffer_size == Some(size),
+                                "{size} frames"
+                            }
+                        }
+                    }
+                }
+            }
+
+            span { class: "label-small", "Output latency {latency_ms:.1} ms, buffer {buffer}" }
+
+            if let Some(error) = &current.error {
+                div { class: "parse-error", "{error}" }
+            }
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use std::time::{Duration, Instant};
+
+use dioxus::prelude::*;
+
+use crate::{
+    app_settings::{AppSettings, Volumes},
+    components::settings_panel::NumberField,
+    presets::{Preset, Presets, Routine, RoutineRun, RoutineStep},
+    ui::app::{AppState, MetronomeState},
+    AudioCommand, AUDIO_CMD,
+};
+
+/// Set the app up as `preset` has it; playback goes on with the new setup
+fn apply_preset(
+    preset: &Preset,
+    mut app_state: Signal<AppState>,
+    mut metronome_state: Signal<MetronomeState>,
+) {
+    preset
+        .settings
+        .apply(&mut app_state.write(), &mut metronome_state.write());
+    app_state.write().preset = Some(preset.name.clone());
+    metronome_state.read().send_settings();
+    app_state.write().rebuild_schedule();
+    let bars = app_state.read().current_bars();
+    let mut metronome = metronome_state.write();
+    metronome.bars_per_chord = bars;
+    metronome.current_bar = 1;
+    metronome.current_tick = 0;
+}
+
+/// Play the preset of the routine's current step, announced by a count-in. Steps whose
+/// preset was deleted are skipped.
+fn play_step(
+    mut run: Signal<Option<RoutineRun>>,
+    mut app_state: Signal<AppState>,
+    metronome_state: Signal<MetronomeState>,
+) {
+    let presets = Presets::load();
+    loop {
+        let Some(name) = run.read().as_ref().map(|r| r.current().preset.clone()) else {
+            return;
+        };
+        if let Some(preset) = presets.preset(&name) {
+            apply_preset(preset, app_state, metronome_state);
+            app_state.write().is_playing = true;
+            let _ = AUDIO_CMD.0.try_send(AudioCommand::StartWithCountIn);
+            return;
+        }
+        log::warn!("Skipping the step of the missing preset {}", name);
+        if !run.write().as_mut().is_some_and(|r| r.next_step()) {
+            run.set(None);
+            return;
+        }
+    }
+}
+
+/// Go on with the next step of the routine. After the last one the routine ends and the
+/// last preset keeps playing.
+fn next_step(
+    mut run: Signal<Option<RoutineRun>>,
+    app_state: Signal<AppState>,
+    metronome_state: Signal<MetronomeState>,
+) {
+    if run.write().as_mut().is_some_and(|r| r.next_step()) {
+        play_step(run, app_state, metronome_state);
+    } else {
+        run.set(None);
+    }
+}
+
+/// The step of the routine being played, with the time left in it
+#[component]
+pub fn RoutineStatus() -> Element {
+    let mut run: Signal<Option<RoutineRun>> = use_context();
+    let app_state: Signal<AppState> = use_context();
+    let metronome_state: Signal<MetronomeState> = use_context();
+
+    use_future(move || async move {
+        let mut last = Instant::now();
+        loop {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            let played = last.elapsed();
+            last = Instant::now();
+            // Time paused does not count
+            if !app_state.read().is_playing || run.read().is_none() {
+                continue;
+            }
+            let over = run.write().as_mut().is_some_and(|r| r.advance(played));
+            if over {
+                next_step(run, app_state, metronome_state);
+            }
+        }
+    });
+
+    let Some(current) = run.read().clone() else {
+        return rsx! {};
+    };
+    let remaining = current.remaining().as_secs();
+    let status = format!(
+        "{} {}/{} · {} · {}:{:02}",
+        current.routine.name,
+        current.step + 1,
+        current.routine.steps.len(),
+        current.current().preset,
+        remaining / 60,
+        remaining % 60
+    );
+
+    rsx! {
+        span { class: "label-small", "{status}" }
+        button {
+            class: "btn-parse-inline",
+            onclick: move |_| next_step(run, app_state, metronome_state),
+            "Skip"
+        }
+        button {
+            class: "btn-parse-inline",
+            onclick: move |_| run.set(None),
+            "End routine"
+        }
+    }
+}
+
+#[component]
+pub fn PresetsPanel(show: Signal<bool>) -> Element {
+    let app_state: Signal<AppState> = use_context();
+    let metronome_state: Signal<MetronomeState> = use_context();
+    let mut run: Signal<Option<RoutineRun>> = use_context();
+    let mut presets = use_signal(Presets::load);
+    let mut preset_name = use_signal(String::new);
+    let mut routine_name = use_signal(String::new);
+    let mut steps = use_signal(Vec::<RoutineStep>::new);
+    let mut step_preset = use_signal(String::new);
+    let mut step_minutes = use_signal(|| 5u32);
+
+    let mut update = move |change: &dyn Fn(&mut Presets)| {
+        change(&mut presets.write());
+        presets.read().save();
+    };
+
+    let mut save_preset = move || {
+        let settings = AppSettings::capture(
+            &app_state.read(),
+            &metronome_state.read(),
+            Volumes::current(),
+            Default::default(),
+        );
+        let preset = Preset::new(&preset_name(), settings);
+        update(&|p: &mut Presets| p.set_preset(preset.clone()));
+        preset_name.set(String::new());
+    };
+
+    let mut save_routine = move || {
+        let routine = Routine {
+            name: routine_name().trim().to_string(),
+            steps: steps(),
+        };
+        update(&|p: &mut Presets| p.set_routine(routine.clone()));
+        routine_name.set(String::new());
+        steps.set(Vec::new());
+    };
+
+    let mut start_routine = move |routine: Routine| {
+        run.set(RoutineRun::new(routine));
+        play_step(run, app_state, metronome_state);
+        show.set(false);
+    };
+
+    if !show() {
+        return rsx! { div {} };
+    }
+
+    let saved = presets.read().clone();
+    let names: Vec<String> = saved.presets.iter().map(|p| p.name.clone()).collect();
+    // The step added next plays the chosen preset, the first one until another is chosen
+    let chosen = match names.contains(&step_preset()) {
+        true => step_preset(),
+        false => names.first().cloned().unwrap_or_default(),
+    };
+
+    rsx! {
+        div {
+            class: "settings-overlay",
+            onclick: move |_| show.set(false),
+
+            div {
+                class: "settings-panel",
+                onclick: move |e| e.stop_propagation(),
+
+                div { class: "settings-header",
+                    h2 { class: "settings-title", "Presets & Routines" }
+                    button {
+                        class: "settings-close",
+                        onclick: move |_| show.set(false),
+                        "✕"
+                    }
+                }
+
+                div { class: "settings-content",
+                    div { class: "settings-section",
+                        h3 { class: "section-title", "Presets" }
+                        div { class: "settings-row",
+                            input {
+                                class: "text-field",
+                                r#type: "text",
+                                placeholder: "Name of the current setup",
+                                value: "{preset_name}",
+                                oninput: move |e| preset_name.set(e.value()),
+                            }
+                            button {
+                                class: "btn-parse-inline",
+                                disabled: preset_name().trim().is_empty(),
+                                onclick: move |_| save_preset(),
+                                "Save"
+                            }
+                        }
+                        for preset in saved.presets.clone() {
+                            div { key: "{preset.name}", class: "settings-row library-song",
+                                div {
+                                    span { "{preset.name} " }
+                                    span { class: "label-small", "{preset.summary()}" }
+                                }
+                                div {
+                                    button {
+                                        class: "btn-parse-inline",
+                                        onclick: {
+                                            let preset = preset.clone();
+                                            move |_| apply_preset(&preset, app_state, metronome_state)
+                                        },
+                                        "Apply"
+                                    }
+                                    button {
+                                        class: "btn-parse-inline",
+                                        onclick: {
+                                            let name = preset.name.clone();
+                                            move |_| {
+                                                update(&|p: &mut Presets| p.presets.retain(|preset| preset.name != name))
+                                            }
+                                        },
+                                        "Delete"
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    div { class: "settings-section",
+                        h3 { class: "section-title", "Routines" }
+                        for routine in saved.routines.clone() {
+                            div { key: "{routine.name}", class: "settings-row library-song",
+                                div {
+                                    span { "{routine.name} " }
+                                    span { class: "label-small", "{describe_steps(&routine.steps)}" }
+                                }
+                                div {
+                                    button {
+                                        class: "btn-parse-inline",
+                                        onclick: {
+                                            let routine = routine.clone();
+                                            move |_| start_routine(routine.clone())
+                                        },
+                                        "Start"
+                                    }
+                                    button {
+                                        class: "btn-parse-inline",
+                                        onclick: {
+                                            let name = routine.name.clone();
+                                            move |_| {
+                                                update(&|p: &mut Presets| p.routines.retain(|routine| routine.name != name))
+                                            }
+                                        },
+                                        "Delete"
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    div { class: "settings-section",
+                        h3 { class: "section-title", "New routine" }
+                        if names.is_empty() {
+                            span { class: "label-small", "Save a preset first, routines play presets in turn" }
+                        } else {
+                            div { class: "settings-row",
+                                input {
+                                    class: "text-field",
+                                    r#type: "text",
+                                    placeholder: "Daily warm-up",
+                                    value: "{routine_name}",
+                                    oninput: move |e| routine_name.set(e.value()),
+                                }
+                            }
+                            if !steps.read().is_empty() {
+                                div { class: "settings-row",
+                                    span { class: "label-small", "{describe_steps(&steps.read())}" }
+                                    button {
+                                        class: "btn-parse-inline",
+                                        onclick: move |_| {
+                                            steps.write().pop();
+                                        },
+                                        "Remove last"
+                                    }
+                                }
+                            }
+                            div { class: "settings-row",
+                                select {
+                                    class: "select-styled",
+                                    onchange: move |e| step_preset.set(e.value()),
+                                    for name in names {
+                                        option { key: "{name}", value: "{name}", selected: name == chosen, "{name}" }
+                                    }
+                                }
+                                button {
+                                    class: "btn-parse-inline",
+                                    onclick: {
+                                        let chosen = chosen.clone();
+                                        move |_| {
+                                            steps.write().push(RoutineStep {
+                                                preset: chosen.clone(),
+                                                minutes: step_minutes(),
+                                            });
+                                        }
+                                    },
+                                    "Add step"
+                                }
+                            }
+                            NumberField {
+                                label: "Minutes",
+                                value: step_minutes(),
+                                on_change: move |v: u32| step_minutes.set(v.clamp(1, 120)),
+                            }
+                            div { class: "settings-row",
+                                span { class: "label-small", "Each step starts with a count-in" }
+                                button {
+                                    class: "btn-parse-inline",
+                                    disabled: routine_name().trim().is_empty() || steps.read().is_empty(),
+                                    onclick: move |_| save_routine(),
+                                    "Save routine"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Steps like `Fourths m7 5′ → Diatonic B♭ 5′`
+fn describe_steps(steps: &[RoutineStep]) -> String {
+    steps
+        .iter()
+        .map(|step| format!("{} {}′", step.preset, step.minutes))
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
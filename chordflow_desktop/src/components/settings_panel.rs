@@ -1,13 +1,47 @@
-use dioxus::prelude::*;
-
-use crate::audio::settings::AUDIO_SETTINGS;
+use dioxus::{
+    desktop::{use_window, LogicalSize},
+    prelude::*,
+};
+
+use crate::{
+    app_settings::AppSettings,
+    audio::settings::AUDIO_SETTINGS,
+    components::{
+        articulation::ArticulationSettings, av_calibration::AvCalibration, bass::BassSettings,
+        click_samples::ClickSamplePicker, drums::DrumSettings, export::SessionExport,
+        gap_click::GapClickSettings, output_device::OutputDevicePicker,
+        sound_font::SoundFontPicker, tempo_trainer::TempoTrainer,
+    },
+    ui::app::{AppState, MetronomeState},
+};
 
 #[component]
 pub fn SettingsPanel(show: Signal<bool>) -> Element {
     let mut metronome_accent = use_signal(|| AUDIO_SETTINGS.get_metronome_accent_volume());
     let mut metronome_beat = use_signal(|| AUDIO_SETTINGS.get_metronome_beat_volume());
-    let mut metronome_subdivision = use_signal(|| AUDIO_SETTINGS.get_metronome_subdivision_volume());
+    let mut metronome_subdivision =
+        use_signal(|| AUDIO_SETTINGS.get_metronome_subdivision_volume());
     let mut chord_volume = use_signal(|| AUDIO_SETTINGS.get_chord_volume());
+    let mut bass_volume = use_signal(|| AUDIO_SETTINGS.get_bass_volume());
+    let mut drum_volume = use_signal(|| AUDIO_SETTINGS.get_drum_volume());
+    let mut app_state: Signal<AppState> = use_context();
+    let mut metronome_state: Signal<MetronomeState> = use_context();
+    let window = use_window();
+
+    // Volumes also change with a reset or a preset, they are shown as they are
+    let mut refresh_volumes = move || {
+        metronome_accent.set(AUDIO_SETTINGS.get_metronome_accent_volume());
+        metronome_beat.set(AUDIO_SETTINGS.get_metronome_beat_volume());
+        metronome_subdivision.set(AUDIO_SETTINGS.get_metronome_subdivision_volume());
+        chord_volume.set(AUDIO_SETTINGS.get_chord_volume());
+        bass_volume.set(AUDIO_SETTINGS.get_bass_volume());
+        drum_volume.set(AUDIO_SETTINGS.get_drum_volume());
+    };
+    use_effect(move || {
+        if show() {
+            refresh_volumes();
+        }
+    });
 
     if !show() {
         return rsx! { div {} };
@@ -71,6 +105,65 @@ pub fn SettingsPanel(show: Signal<bool>) -> Element {
                                 AUDIO_SETTINGS.set_chord_volume(val);
                             }
                         }
+
+                        VolumeSlider {
+                            label: "Bass Volume",
+                            value: bass_volume,
+                            on_change: move |val: f32| {
+                                bass_volume.set(val);
+                                AUDIO_SETTINGS.set_bass_volume(val);
+                            }
+                        }
+
+                        VolumeSlider {
+                            label: "Drum Volume",
+                            value: drum_volume,
+                            on_change: move |val: f32| {
+                                drum_volume.set(val);
+                                AUDIO_SETTINGS.set_drum_volume(val);
+                            }
+                        }
+                    }
+
+                    OutputDevicePicker {}
+
+                    AvCalibration {}
+
+                    SoundFontPicker {}
+
+                    ClickSamplePicker {}
+
+                    TempoTrainer {}
+
+                    GapClickSettings {}
+
+                    ArticulationSettings {}
+
+                    BassSettings {}
+
+                    DrumSettings {}
+
+                    SessionExport {}
+
+                    div { class: "settings-section",
+                        h3 { class: "section-title", "Reset" }
+                        div { class: "settings-row",
+                            span { class: "label-small",
+                                "Tempo, metronome, modes, accompaniment, volumes and window size. The progression and library are kept."
+                            }
+                            button {
+                                class: "btn-parse-inline",
+                                onclick: move |_| {
+                                    let defaults = AppSettings::default();
+                                    defaults.apply(&mut app_state.write(), &mut metronome_state.write());
+                                    metronome_state.read().send_settings();
+                                    app_state.write().restart();
+                                    window.set_inner_size(LogicalSize::new(defaults.window.width, defaults.window.height));
+                                    refresh_volumes();
+                                },
+                                "Reset to defaults"
+                            }
+                        }
                     }
 
                     // Keyboard Shortcuts Section
@@ -95,11 +188,7 @@ pub fn SettingsPanel(show: Signal<bool>) -> Element {
 }
 
 #[component]
-fn VolumeSlider(
-    label: String,
-    value: Signal<f32>,
-    on_change: EventHandler<f32>,
-) -> Element {
+fn VolumeSlider(label: String, value: Signal<f32>, on_change: EventHandler<f32>) -> Element {
     let percentage = (value() * 100.0) as i32;
 
     rsx! {
@@ -124,6 +213,31 @@ fn VolumeSlider(
     }
 }
 
+#[component]
+pub fn NumberField(
+    label: String,
+    value: u32,
+    #[props(default = 1)] min: u32,
+    on_change: EventHandler<u32>,
+) -> Element {
+    rsx! {
+        label { class: "settings-row",
+            span { "{label}" }
+            input {
+                r#type: "number",
+                class: "number-input",
+                min: "{min}",
+                value: "{value}",
+                onchange: move |e| {
+                    if let Ok(val) = e.value().parse::<u32>() {
+                        on_change.call(val);
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[component]
 fn KeyboardShortcut(keys: String, description: String) -> Element {
     rsx! {
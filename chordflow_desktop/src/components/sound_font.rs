@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+
+use dioxus::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::audio::soundfont::{
+    presets, set_instrument, switch_sound_font, use_builtin_sounds, Instrument, InstrumentPart,
+    SOUND_FONT,
+};
+
+fn preset_value(instrument: Instrument) -> String {
+    format!("{}:{}", instrument.bank, instrument.program)
+}
+
+fn parse_preset_value(value: &str) -> Option<Instrument> {
+    let (bank, program) = value.split_once(':')?;
+    Some(Instrument {
+        bank: bank.parse().ok()?,
+        program: program.parse().ok()?,
+    })
+}
+
+#[component]
+pub fn SoundFontPicker() -> Element {
+    let mut status = use_signal(|| SOUND_FONT.lock().clone());
+    let mut loading = use_signal(|| false);
+
+    let mut switch_to = move |path: Option<PathBuf>| {
+        loading.set(true);
+        spawn(async move {
+            // Parsing a large font takes a while, keep it off the UI thread
+            let _ = tokio::task::spawn_blocking(move || switch_sound_font(path)).await;
+            status.set(SOUND_FONT.lock().clone());
+            loading.set(false);
+        });
+    };
+
+    let current = status.read();
+    let name = current.name();
+    let builtin = current.sound_font.is_none();
+    let is_custom = current.settings.path.is_some() && current.error.is_none() && !builtin;
+    let remembered_path = current.settings.path.clone();
+    let presets = current
+        .sound_font
+        .as_ref()
+        .map(|sound_font| presets(sound_font))
+        .unwrap_or_default();
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "SoundFont" }
+
+            div { class: "settings-row",
+                span { if loading() { "Loading…" } else { "{name}" } }
+                if is_custom {
+                    button {
+                        class: "btn-parse-inline",
+                        disabled: loading(),
+                        onclick: move |_| switch_to(None),
+                        "Use bundled"
+                    }
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Built-in sounds (low CPU)" }
+                input {
+                    r#type: "checkbox",
+                    checked: builtin,
+                    disabled: loading(),
+                    onchange: move |e| {
+                        if e.checked() {
+                            use_builtin_sounds();
+                            status.set(SOUND_FONT.lock().clone());
+                        } else {
+                            switch_to(remembered_path.clone());
+                        }
+                    },
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Load a .sf2 file" }
+                input {
+                    r#type: "file",
+                    accept: ".sf2",
+                    disabled: loading(),
+                    onchange: move |e| {
+                        if let Some(file) = e.files().first() {
+                            switch_to(Some(file.path()));
+                        }
+                    },
+                }
+            }
+
+            if let Some(error) = &current.error {
+                div { class: "parse-error", "{error}" }
+            }
+
+            // The built-in sounds have no presets to choose from
+            if !presets.is_empty() {
+                for part in InstrumentPart::iter() {
+                    label { key: "{part}", class: "settings-row",
+                        span { "{part}" }
+                        select {
+                            class: "select-styled",
+                            onchange: move |e| {
+                                if let Some(instrument) = parse_preset_value(&e.value()) {
+                                    set_instrument(part, instrument);
+                                    status.set(SOUND_FONT.lock().clone());
+                                }
+                            },
+                            for preset in presets.iter() {
+                                option {
+                                    value: preset_value(preset.instrument),
+                                    selected: preset.instrument == current.settings.instrument(part),
+                                    "{preset.instrument.bank}:{preset.instrument.program} {preset.name}"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use chrono::{Datelike, Local, NaiveDate, TimeDelta};
+use dioxus::prelude::*;
+
+use crate::practice_log::{
+    load_sessions, minutes_per_day, streaks, tempo_per_preset, time_per_key, time_per_mode, to_csv,
+    to_json, PracticeSession,
+};
+
+/// Weeks shown in the calendar
+const CALENDAR_WEEKS: i64 = 18;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    fn write(self, sessions: &[PracticeSession]) -> anyhow::Result<String> {
+        match self {
+            ExportFormat::Csv => Ok(to_csv(sessions)),
+            ExportFormat::Json => to_json(sessions),
+        }
+    }
+}
+
+/// Like `1h 05m` or `12m`
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h {:02}m", hours, minutes % 60),
+    }
+}
+
+/// Shade of a day in the calendar, 0 for no practice
+fn heat(minutes: u32) -> u8 {
+    match minutes {
+        0 => 0,
+        1..15 => 1,
+        15..30 => 2,
+        30..60 => 3,
+        _ => 4,
+    }
+}
+
+#[component]
+pub fn StatsPanel(show: Signal<bool>) -> Element {
+    let mut sessions = use_signal(Vec::<PracticeSession>::new);
+    let mut message = use_signal(|| None::<String>);
+
+    // The log is read again whenever the panel opens
+    use_effect(move || {
+        if show() {
+            sessions.set(load_sessions());
+            message.set(None);
+        }
+    });
+
+    let export = move |format: ExportFormat| {
+        let text = format.write(&sessions.read());
+        spawn(async move {
+            let text = match text {
+                Ok(text) => text,
+                Err(e) => {
+                    message.set(Some(format!("Export error: {}", e)));
+                    return;
+                }
+            };
+            let extension = format.extension();
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .add_filter("Practice log", &[extension])
+                .set_file_name(format!("practice_log.{}", extension))
+                .save_file()
+                .await
+            else {
+                return;
+            };
+            match tokio::fs::write(file.path(), text).await {
+                Ok(()) => message.set(Some(format!("Exported to {}", file.path().display()))),
+                Err(e) => message.set(Some(format!("Export error: {}", e))),
+            }
+        });
+    };
+
+    if !show() {
+        return rsx! { div {} };
+    }
+
+    let sessions = sessions.read();
+    let today = Local::now().date_naive();
+    let total: Duration = sessions.iter().map(PracticeSession::duration).sum();
+    let minutes = minutes_per_day(&sessions);
+    let days: BTreeSet<NaiveDate> = minutes.keys().copied().collect();
+    let streaks = streaks(&days, today);
+    let modes = time_per_mode(&sessions);
+    let keys = time_per_key(&sessions);
+    let tempos = tempo_per_preset(&sessions);
+
+    let share = |time: Duration, most: Duration| match most.is_zero() {
+        true => 0.0,
+        false => time.as_secs_f64() / most.as_secs_f64() * 100.0,
+    };
+    let most_mode = modes.first().map(|(_, time)| *time).unwrap_or_default();
+    let most_key = keys.first().map(|(_, time)| *time).unwrap_or_default();
+
+    // Whole weeks from a Monday up to today, a column each
+    let first_day = today
+        - TimeDelta::days(today.weekday().num_days_from_monday() as i64)
+        - TimeDelta::weeks(CALENDAR_WEEKS - 1);
+    let calendar: Vec<(NaiveDate, u32)> = first_day
+        .iter_days()
+        .take_while(|day| *day <= today)
+        .map(|day| (day, minutes.get(&day).copied().unwrap_or_default()))
+        .collect();
+
+    rsx! {
+        div {
+            class: "settings-overlay",
+            onclick: move |_| show.set(false),
+
+            div {
+                class: "settings-panel",
+                onclick: move |e| e.stop_propagation(),
+
+                div { class: "settings-header",
+                    h2 { class: "settings-title", "Practice Statistics" }
+                    button {
+                        class: "settings-close",
+                        onclick: move |_| show.set(false),
+                        "✕"
+                    }
+                }
+
+                div { class: "settings-content",
+                    div { class: "settings-section",
+                        h3 { class: "section-title", "Summary" }
+                        div { class: "settings-row",
+                            span { "Total {format_duration(total)} in {sessions.len()} sessions" }
+                        }
+                        div { class: "settings-row",
+                            span { "Streak {streaks.current} days" }
+                            span { class: "label-small", "Longest {streaks.longest} days" }
+                        }
+                    }
+
+                    div { class: "settings-section",
+                        h3 { class: "section-title", "Calendar" }
+                        div { class: "stats-calendar",
+                            for (day, played) in calendar {
+                                div {
+                                    key: "{day}",
+                                    class: "stats-day heat-{heat(played)}",
+                                    title: "{day}: {played} min",
+                                }
+                            }
+                        }
+                    }
+
+                    if sessions.is_empty() {
+                        div { class: "settings-section",
+                            span { class: "label-small", "Sessions are logged while playing" }
+                        }
+                    } else {
+                        div { class: "settings-section",
+                            h3 { class: "section-title", "Time per mode" }
+                            for (mode, time) in modes {
+                                div { key: "{mode}", class: "stats-bar-row",
+                                    span { class: "stats-bar-label", "{mode}" }
+                                    div { class: "stats-bar-track",
+                                        div {
+                                            class: "stats-bar",
+                                            style: "width: {share(time, most_mode)}%",
+                                        }
+                                    }
+                                    span { class: "label-small", "{format_duration(time)}" }
+                                }
+                            }
+                        }
+
+                        div { class: "settings-section",
+                            h3 { class: "section-title", "Time per key" }
+                            for (key, time) in keys {
+                                div { key: "{key}", class: "stats-bar-row",
+                                    span { class: "stats-bar-label", "{key}" }
+                                    div { class: "stats-bar-track",
+                                        div {
+                                            class: "stats-bar",
+                                            style: "width: {share(time, most_key)}%",
+                                        }
+                                    }
+                                    span { class: "label-small", "{format_duration(time)}" }
+                                }
+                            }
+                        }
+
+                        div { class: "settings-section",
+                            h3 { class: "section-title", "Tempo per preset" }
+                            if tempos.is_empty() {
+                                span { class: "label-small", "Practise with a preset to follow its tempo" }
+                            }
+                            for (preset, days) in tempos {
+                                TempoChart { key: "{preset}", preset: preset.clone(), days }
+                            }
+                        }
+                    }
+
+                    div { class: "settings-section",
+                        h3 { class: "section-title", "Export" }
+                        div { class: "settings-row",
+                            span { class: "label-small", "Every session, e.g. for a teacher" }
+                            div {
+                                button {
+                                    class: "btn-parse-inline",
+                                    disabled: sessions.is_empty(),
+                                    onclick: move |_| export(ExportFormat::Csv),
+                                    "CSV"
+                                }
+                                button {
+                                    class: "btn-parse-inline",
+                                    disabled: sessions.is_empty(),
+                                    onclick: move |_| export(ExportFormat::Json),
+                                    "JSON"
+                                }
+                            }
+                        }
+                        if let Some(message) = message() {
+                            span { class: "label-small", "{message}" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Fastest tempo of a preset per day it was practised
+#[component]
+fn TempoChart(preset: String, days: Vec<(NaiveDate, u16)>) -> Element {
+    const WIDTH: f64 = 300.0;
+    const HEIGHT: f64 = 60.0;
+    let (Some(first), Some(last)) = (days.first(), days.last()) else {
+        return rsx! {};
+    };
+    let slowest = days.iter().map(|(_, bpm)| *bpm).min().unwrap_or_default();
+    let fastest = days.iter().map(|(_, bpm)| *bpm).max().unwrap_or_default();
+    let range = (fastest - slowest).max(1) as f64;
+    let step = WIDTH / (days.len().max(2) - 1) as f64;
+    let points = days
+        .iter()
+        .enumerate()
+        .map(|(i, (_, bpm))| {
+            let y = HEIGHT - (*bpm - slowest) as f64 / range * HEIGHT;
+            format!("{:.1},{:.1}", i as f64 * step, y)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let view_box = format!("-4 -4 {} {}", WIDTH + 8.0, HEIGHT + 8.0);
+    let summary = format!(
+        "{} → {} bpm, {} to {}",
+        first.1,
+        last.1,
+        first.0.format("%b %-d"),
+        last.0.format("%b %-d")
+    );
+
+    rsx! {
+        div { class: "stats-tempo",
+            div { class: "settings-row",
+                span { "{preset}" }
+                span { class: "label-small", "{summary}" }
+            }
+            svg {
+                class: "stats-tempo-chart",
+                view_box: "{view_box}",
+                preserve_aspect_ratio: "none",
+                polyline { points: "{points}" }
+            }
+        }
+    }
+}
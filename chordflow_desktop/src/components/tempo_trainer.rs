@@ -0,0 +1,104 @@
+use dioxus::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::{
+    audio::tempo::{RampCurve, RampUnit, TempoRamp},
+    components::settings_panel::NumberField,
+    ui::app::MetronomeState,
+    AudioCommand, AUDIO_CMD, MAX_BPM, MIN_BPM,
+};
+
+/// A typed tempo within what the metronome plays
+fn clamp_bpm(value: u32) -> u16 {
+    value.clamp(MIN_BPM as u32, MAX_BPM as u32) as u16
+}
+
+#[component]
+pub fn TempoTrainer() -> Element {
+    let mut metronome_state: Signal<MetronomeState> = use_context();
+    let ramp = metronome_state.read().tempo_ramp;
+    let enabled = metronome_state.read().tempo_ramp_enabled;
+
+    let mut update = move |enabled: bool, ramp: TempoRamp| {
+        metronome_state.write().tempo_ramp_enabled = enabled;
+        metronome_state.write().tempo_ramp = ramp;
+        let _ = AUDIO_CMD
+            .0
+            .try_send(AudioCommand::SetTempoRamp(enabled.then_some(ramp)));
+    };
+
+    rsx! {
+        div { class: "settings-section",
+            h3 { class: "section-title", "Tempo Trainer" }
+
+            label { class: "settings-row",
+                span { "Automatic tempo ramp" }
+                input {
+                    r#type: "checkbox",
+                    checked: enabled,
+                    onchange: move |e| update(e.checked(), ramp),
+                }
+            }
+
+            NumberField {
+                label: "Start BPM",
+                value: ramp.start_bpm as u32,
+                on_change: move |v: u32| update(enabled, TempoRamp { start_bpm: clamp_bpm(v), ..ramp }),
+            }
+            NumberField {
+                label: "Target BPM",
+                value: ramp.target_bpm as u32,
+                on_change: move |v: u32| update(enabled, TempoRamp { target_bpm: clamp_bpm(v), ..ramp }),
+            }
+            NumberField {
+                label: "Step (BPM)",
+                value: ramp.step as u32,
+                on_change: move |v: u32| update(enabled, TempoRamp { step: v as u16, ..ramp }),
+            }
+            NumberField {
+                label: "Every",
+                value: ramp.every,
+                on_change: move |v: u32| update(enabled, TempoRamp { every: v.max(1), ..ramp }),
+            }
+
+            label { class: "settings-row",
+                span { "Unit" }
+                select {
+                    class: "select-styled",
+                    onchange: move |e| {
+                        if let Some(unit) = RampUnit::iter().find(|u| u.to_string() == e.value()) {
+                            update(enabled, TempoRamp { unit, ..ramp });
+                        }
+                    },
+                    for unit in RampUnit::iter() {
+                        option { selected: unit == ramp.unit, "{unit}" }
+                    }
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Curve" }
+                select {
+                    class: "select-styled",
+                    onchange: move |e| {
+                        if let Some(curve) = RampCurve::iter().find(|c| c.to_string() == e.value()) {
+                            update(enabled, TempoRamp { curve, ..ramp });
+                        }
+                    },
+                    for curve in RampCurve::iter() {
+                        option { selected: curve == ramp.curve, "{curve}" }
+                    }
+                }
+            }
+
+            label { class: "settings-row",
+                span { "Stop at target" }
+                input {
+                    r#type: "checkbox",
+                    checked: ramp.stop_at_target,
+                    onchange: move |e| update(enabled, TempoRamp { stop_at_target: e.checked(), ..ramp }),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+
+fn file_path(name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("chordflow").join(name))
+}
+
+/// Settings saved under `name`, or the defaults when there are none or they are unreadable
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    let Some(path) = file_path(name).filter(|p| p.exists()) else {
+        return T::default();
+    };
+    match fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| Ok(toml::from_str(&text)?))
+    {
+        Ok(settings) => settings,
+        Err(e) => {
+            log::warn!("Ignoring unreadable settings {:?}: {}", path, e);
+            T::default()
+        }
+    }
+}
+
+pub fn save<T: Serialize>(name: &str, settings: &T) -> Result<()> {
+    let path = file_path(name).context("No configuration directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, toml::to_string(settings)?)?;
+    Ok(())
+}
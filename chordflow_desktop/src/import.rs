@@ -0,0 +1,57 @@
+pub mod chord_recognition;
+pub mod chordpro;
+pub mod ireal;
+pub mod lead_sheet;
+pub mod midi;
+pub mod musicxml;
+
+use anyhow::{Context, Result};
+use chordparser::{chord::Chord, parsing::Parser};
+
+use crate::{state::progression::ProgressionChord, MAX_BPM, MIN_BPM};
+
+/// A chord read from a file, before it is parsed for practice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedChord {
+    pub name: String,
+    pub bars: u8,
+    /// Beats the chord lasts instead of whole bars, when it shares a bar
+    pub beats: Option<u8>,
+}
+
+/// A tempo read from a file, when the metronome can play it
+pub fn read_bpm(bpm: f64) -> Option<u16> {
+    let bpm = bpm.round();
+    (MIN_BPM as f64..=MAX_BPM as f64)
+        .contains(&bpm)
+        .then_some(bpm as u16)
+}
+
+/// Parse a chord name from another program. A chord the parser does not understand is
+/// kept as the longest start of it that it does, `C7` of `C7alt`, and without its bass note.
+fn parse_chord(parser: &mut Parser, name: &str) -> Result<Chord> {
+    if let Ok(chord) = parser.parse(name) {
+        return Ok(chord);
+    }
+    let without_bass = name.split_once('/').map_or(name, |(chord, _)| chord);
+    (1..=without_bass.len())
+        .rev()
+        .filter(|&end| without_bass.is_char_boundary(end))
+        .find_map(|end| parser.parse(&without_bass[..end]).ok())
+        .with_context(|| format!("Unknown chord {}", name))
+}
+
+/// Parse imported chords into a custom progression
+pub fn to_progression(chords: &[ImportedChord]) -> Result<Vec<ProgressionChord>> {
+    let mut parser = Parser::new();
+    chords
+        .iter()
+        .map(|imported| {
+            Ok(ProgressionChord {
+                chord: parse_chord(&mut parser, &imported.name)?,
+                bars: imported.bars,
+                beats: imported.beats,
+            })
+        })
+        .collect()
+}
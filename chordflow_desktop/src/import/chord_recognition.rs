@@ -0,0 +1,132 @@
+/// Root names, spelled the way lead sheets usually do
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B",
+];
+
+/// Pitch classes lighter than this, relative to the heaviest, count as not played
+const PRESENT: f64 = 0.1;
+/// Score lost for every chord tone that is not played
+const MISSING_PENALTY: f64 = 0.5;
+/// Score won by a chord whose root is in the bass, to tell inversions apart
+const BASS_BONUS: f64 = 0.25;
+/// Voicings with a seventh often leave out the fifth
+const PERFECT_FIFTH: u8 = 7;
+
+/// Chords the recogniser knows as suffix and intervals, simple ones first so they win ties
+const KINDS: [(&str, &[u8]); 15] = [
+    ("", &[0, 4, 7]),
+    ("m", &[0, 3, 7]),
+    ("dim", &[0, 3, 6]),
+    ("aug", &[0, 4, 8]),
+    ("sus4", &[0, 5, 7]),
+    ("sus2", &[0, 2, 7]),
+    ("7", &[0, 4, 7, 10]),
+    ("maj7", &[0, 4, 7, 11]),
+    ("m7", &[0, 3, 7, 10]),
+    ("m7b5", &[0, 3, 6, 10]),
+    ("dim7", &[0, 3, 6, 9]),
+    ("6", &[0, 4, 7, 9]),
+    ("m6", &[0, 3, 7, 9]),
+    ("7sus4", &[0, 5, 7, 10]),
+    ("9", &[0, 2, 4, 7, 10]),
+];
+
+/// A chord found in a set of pitch classes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecognisedChord {
+    pub root: u8,
+    kind: usize,
+    /// Lowest chord tone, the root unless the chord is inverted
+    pub bass: u8,
+}
+
+impl RecognisedChord {
+    /// Chord symbol such as `Dm7` or `C/E`
+    pub fn name(&self) -> String {
+        let name = format!("{}{}", NOTE_NAMES[self.root as usize], KINDS[self.kind].0);
+        if self.bass == self.root {
+            name
+        } else {
+            format!("{}/{}", name, NOTE_NAMES[self.bass as usize])
+        }
+    }
+}
+
+/// The chord that best explains how long each pitch class sounded.
+///
+/// Every root and chord kind is scored by the weight of its tones that were played, less
+/// the weight of the notes that are not in it and a penalty for its tones that were not
+/// played. `bass` is the pitch class of the lowest note. Fewer than two pitch classes make
+/// no chord.
+pub fn recognise(weights: &[f64; 12], bass: u8) -> Option<RecognisedChord> {
+    let heaviest = weights.iter().cloned().fold(0.0, f64::max);
+    if heaviest <= 0.0 {
+        return None;
+    }
+    let weights = weights.map(|w| w / heaviest);
+    if weights.iter().filter(|&&w| w >= PRESENT).count() < 2 {
+        return None;
+    }
+    let bass = bass % 12;
+
+    let mut best: Option<(f64, RecognisedChord)> = None;
+    for root in 0..12u8 {
+        for (kind, (_, intervals)) in KINDS.iter().enumerate() {
+            let interval = |pitch_class: usize| (pitch_class as u8 + 12 - root) % 12;
+            let (matched, extra) = (0..12).fold((0.0, 0.0), |(matched, extra), pc| {
+                if intervals.contains(&interval(pc)) {
+                    (matched + weights[pc], extra)
+                } else {
+                    (matched, extra + weights[pc])
+                }
+            });
+            let missing = intervals
+                .iter()
+                .filter(|&&i| !(i == PERFECT_FIFTH && intervals.len() > 3))
+                .filter(|&&i| weights[((root + i) % 12) as usize] < PRESENT)
+                .count();
+            let mut score = matched - extra - MISSING_PENALTY * missing as f64;
+            if bass == root {
+                score += BASS_BONUS;
+            }
+            if best.is_none_or(|(best, _)| score > best + 1e-9) {
+                // A bass note outside the chord is a passing note, not an inversion
+                let bass = if intervals.contains(&interval(bass as usize)) {
+                    bass
+                } else {
+                    root
+                };
+                best = Some((score, RecognisedChord { root, kind, bass }));
+            }
+        }
+    }
+    best.map(|(_, chord)| chord)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(pitch_classes: &[u8], bass: u8) -> Option<String> {
+        let mut weights = [0.0; 12];
+        for &pc in pitch_classes {
+            weights[pc as usize] += 1.0;
+        }
+        recognise(&weights, bass).map(|c| c.name())
+    }
+
+    #[test]
+    fn test_recognise() {
+        assert_eq!(name(&[0, 4, 7], 0).as_deref(), Some("C"));
+        assert_eq!(name(&[0, 4, 7], 4).as_deref(), Some("C/E"));
+        assert_eq!(name(&[2, 5, 9, 0], 2).as_deref(), Some("Dm7"));
+        // Shell voicing without the fifth
+        assert_eq!(name(&[7, 11, 5], 7).as_deref(), Some("G7"));
+        // The same notes, told apart by the bass
+        assert_eq!(name(&[9, 0, 4, 7], 9).as_deref(), Some("Am7"));
+        assert_eq!(name(&[9, 0, 4, 7], 0).as_deref(), Some("C6"));
+        assert_eq!(name(&[11, 2, 5, 9], 11).as_deref(), Some("Bm7b5"));
+        assert_eq!(name(&[6, 10, 1], 6).as_deref(), Some("F#"));
+        assert_eq!(name(&[0], 0), None);
+    }
+}
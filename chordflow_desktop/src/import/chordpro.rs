@@ -0,0 +1,419 @@
+use anyhow::{bail, Result};
+
+use crate::{
+    import::read_bpm,
+    state::song::{split_beats, Song, SongBar, SongChord},
+};
+
+/// Bars on a line of a written grid
+const BARS_PER_LINE: usize = 4;
+/// Chords in lyrics that are not chords
+const NO_CHORD: [&str; 3] = ["N.C.", "NC", "x"];
+
+/// A bar line of a grid: `|`, `||`, `|.`, `|:`, `:|`, `:|:` or an ending like `|2>`
+struct BarLine {
+    repeat_end: bool,
+    repeat_start: bool,
+    ending: Option<u8>,
+}
+
+fn read_bar_line(token: &str) -> Option<BarLine> {
+    let rest = token.strip_prefix(':');
+    let repeat_end = rest.is_some();
+    let rest = rest.unwrap_or(token).strip_prefix('|')?;
+    let rest = rest.trim_start_matches(['|', '.']);
+    let repeat_start = rest.starts_with(':');
+    let rest = rest.trim_start_matches(':').trim_end_matches('>');
+    let ending = rest.parse().ok();
+    if !rest.is_empty() && ending.is_none() {
+        return None;
+    }
+    Some(BarLine {
+        repeat_end,
+        repeat_start,
+        ending,
+    })
+}
+
+/// The label of a section from the argument of its `start_of_` directive, either
+/// `label="Verse 1"` or the argument itself
+fn read_label(argument: &str) -> Option<String> {
+    if let Some((_, rest)) = argument.split_once("label=") {
+        let rest = rest.trim_start_matches('"');
+        return Some(rest.split('"').next().unwrap_or(rest).to_string());
+    }
+    // A grid's plain argument is its shape, like `1+4x2+4`
+    let is_shape = argument
+        .chars()
+        .all(|c| c.is_ascii_digit() || "+x ".contains(c));
+    (!argument.contains('=') && !is_shape).then(|| argument.to_string())
+}
+
+/// Reads a ChordPro file into bars
+struct ChordProReader {
+    song: Song,
+    /// Label of the section whose first bar is still to come
+    section: Option<String>,
+    /// Environment being read, like `chorus` or `grid`
+    environment: Option<String>,
+    /// Bars of the last chorus, to be recalled with `{chorus}`
+    chorus: std::ops::Range<usize>,
+    /// Signs of the next bar, read from the bar line before it
+    next_bar: SongBar,
+}
+
+impl ChordProReader {
+    fn beats(&self) -> u8 {
+        self.song.time_signature.0.max(1)
+    }
+
+    fn push_bar(&mut self, chords: Vec<SongChord>) {
+        let mut bar = std::mem::take(&mut self.next_bar);
+        bar.chords = chords;
+        if bar.section.is_none() {
+            bar.section = self.section.take();
+        }
+        self.song.bars.push(bar);
+    }
+
+    fn directive(&mut self, directive: &str) {
+        let (name, argument) = match directive.find([':', ' ']) {
+            Some(at) => (&directive[..at], directive[at + 1..].trim()),
+            None => (directive, ""),
+        };
+        let name = name.trim().to_lowercase();
+        // Directives for one instrument or user only, like `key-piano`
+        if name.contains('-') {
+            return;
+        }
+        let environment = match name.as_str() {
+            "soc" => "start_of_chorus",
+            "sov" => "start_of_verse",
+            "sob" => "start_of_bridge",
+            "sog" => "start_of_grid",
+            "sot" => "start_of_tab",
+            "eoc" => "end_of_chorus",
+            "eov" | "eob" | "eog" | "eot" => "end_of_",
+            name => name,
+        };
+        match name.as_str() {
+            "title" | "t" => self.song.title = argument.to_string(),
+            "composer" | "artist" if self.song.composer.is_empty() => {
+                self.song.composer = argument.to_string()
+            }
+            "key" => self.song.key = argument.to_string(),
+            "tempo" => self.song.bpm = argument.parse().ok().and_then(read_bpm),
+            "time" => {
+                if let Some((beats, value)) = argument.split_once('/') {
+                    if let (Ok(beats), Ok(value)) = (beats.trim().parse(), value.trim().parse()) {
+                        self.song.time_signature = (beats, value);
+                    }
+                }
+            }
+            "chorus" => {
+                let chorus = self.song.bars[self.chorus.clone()].to_vec();
+                for (i, mut bar) in chorus.into_iter().enumerate() {
+                    bar.section = (i == 0).then(|| "Chorus".to_string());
+                    self.song.bars.push(bar);
+                }
+            }
+            _ => {}
+        }
+        if let Some(kind) = environment.strip_prefix("start_of_") {
+            // A grid without a label keeps that of the section it is in
+            let section = self.section.take();
+            self.section = read_label(argument).or_else(|| match kind {
+                "grid" | "tab" => section,
+                _ => {
+                    let mut chars = kind.chars();
+                    let first = chars.next()?;
+                    Some(first.to_uppercase().chain(chars).collect())
+                }
+            });
+            if kind == "chorus" {
+                self.chorus = self.song.bars.len()..self.song.bars.len();
+            }
+            self.environment = Some(kind.to_string());
+        } else if environment.starts_with("end_of_") {
+            // A grid may be inside the chorus, so its end is only known at `end_of_chorus`
+            if environment == "end_of_chorus" {
+                self.chorus.end = self.song.bars.len();
+            }
+            self.environment = None;
+        }
+    }
+
+    /// Every chord of a lyrics line lasts a bar, the words do not tell how long
+    fn lyrics_line(&mut self, line: &str) {
+        for chord in line.split('[').skip(1).filter_map(|c| c.split_once(']')) {
+            let name = chord.0.trim();
+            // Annotations start with `*`
+            if name.is_empty() || name.starts_with('*') {
+                continue;
+            }
+            let chords = match NO_CHORD.contains(&name) {
+                true => Vec::new(),
+                false => vec![SongChord {
+                    name: name.to_string(),
+                    beats: self.beats(),
+                }],
+            };
+            self.push_bar(chords);
+        }
+    }
+
+    /// Cells between bar lines share the bar's beats, `.` continues a chord and `%`
+    /// repeats the bar before. Text outside the bar lines is a margin.
+    fn grid_line(&mut self, line: &str) {
+        let mut cells: Option<Vec<Option<String>>> = None;
+        let mut repeat = 0;
+        for token in line.split_whitespace() {
+            if let Some(bar_line) = read_bar_line(token) {
+                if let Some(cells) = cells.take().filter(|c| !c.is_empty() || repeat > 0) {
+                    let beats = self.beats();
+                    match repeat {
+                        0 => self.push_bar(split_beats(&cells, beats)),
+                        _ => {
+                            let start = self.song.bars.len().saturating_sub(repeat);
+                            for i in start..self.song.bars.len() {
+                                let chords = self.song.bars[i].chords.clone();
+                                self.push_bar(chords);
+                            }
+                        }
+                    }
+                }
+                if bar_line.repeat_end {
+                    if let Some(last) = self.song.bars.last_mut() {
+                        last.repeat_end = Some(2);
+                    }
+                }
+                self.next_bar.repeat_start |= bar_line.repeat_start;
+                self.next_bar.ending = self.next_bar.ending.or(bar_line.ending);
+                cells = Some(Vec::new());
+                repeat = 0;
+            } else if let Some(cells) = cells.as_mut() {
+                match token {
+                    "." | "/" | "-" => cells.push(None),
+                    "%" => repeat = 1,
+                    "%%" => repeat = 2,
+                    // Of chords sharing a cell, like `C~G`, the first one is kept
+                    chord => cells.push(chord.split('~').next().map(str::to_string)),
+                }
+            }
+        }
+    }
+}
+
+/// Read the chords of a ChordPro file with its sections, key, tempo and time signature
+pub fn parse_chordpro(text: &str) -> Result<Song> {
+    let mut reader = ChordProReader {
+        song: Song {
+            time_signature: (4, 4),
+            ..Song::default()
+        },
+        section: None,
+        environment: None,
+        chorus: 0..0,
+        next_bar: SongBar::default(),
+    };
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(directive) = line.strip_prefix('{').and_then(|l| l.strip_suffix('}')) {
+            reader.directive(directive);
+            continue;
+        }
+        match reader.environment.as_deref() {
+            Some("tab") => {}
+            Some("grid") => reader.grid_line(line),
+            _ => reader.lyrics_line(line),
+        }
+    }
+    if reader.song.bars.is_empty() {
+        bail!("No chords found");
+    }
+    Ok(reader.song)
+}
+
+/// Write a song as a ChordPro chord chart: a grid for each section with a cell per beat
+pub fn write_chordpro(song: &Song) -> String {
+    let mut out = String::new();
+    let mut directive = |name: &str, value: &str| {
+        if !value.is_empty() {
+            out.push_str(&format!("{{{}: {}}}\n", name, value));
+        }
+    };
+    directive("title", &song.title);
+    directive("composer", &song.composer);
+    directive("key", &song.key);
+    directive(
+        "tempo",
+        &song.bpm.map(|bpm| bpm.to_string()).unwrap_or_default(),
+    );
+    let (beats, value) = match song.time_signature {
+        (0, _) | (_, 0) => (4, 4),
+        signature => signature,
+    };
+    directive("time", &format!("{}/{}", beats, value));
+
+    // The chord that is held, and whether the bar before had more than one
+    let mut held: Option<&str> = None;
+    let mut split_bar = false;
+    let mut sections: Vec<(Option<&str>, Vec<&SongBar>)> = Vec::new();
+    for bar in &song.bars {
+        match sections.last_mut() {
+            Some((_, bars)) if bar.section.is_none() => bars.push(bar),
+            _ => sections.push((bar.section.as_deref(), vec![bar])),
+        }
+    }
+    for (label, bars) in sections {
+        out.push('\n');
+        match label {
+            Some(label) => out.push_str(&format!("{{start_of_grid: label=\"{}\"}}\n", label)),
+            None => out.push_str("{start_of_grid}\n"),
+        }
+        for line in bars.chunks(BARS_PER_LINE) {
+            let mut tokens = Vec::new();
+            for bar in line {
+                tokens.push(match (bar.repeat_start, bar.ending) {
+                    (true, _) => "|:".to_string(),
+                    (false, Some(ending)) => format!("|{}>", ending),
+                    (false, None) => "|".to_string(),
+                });
+                let mut cells = 0;
+                for chord in &bar.chords {
+                    tokens.push(chord.name.clone());
+                    tokens.extend(std::iter::repeat_n(
+                        ".".to_string(),
+                        chord.beats.max(1) as usize - 1,
+                    ));
+                    cells += chord.beats.max(1);
+                }
+                if bar.chords.is_empty() {
+                    // A bar that holds the chord of a split bar is not a copy of that bar
+                    match (held, split_bar) {
+                        (Some(chord), true) => tokens.push(chord.to_string()),
+                        _ => tokens.push("%".to_string()),
+                    }
+                    cells = 1;
+                    split_bar = false;
+                } else {
+                    split_bar = bar.chords.len() > 1;
+                    held = bar.chords.last().map(|c| c.name.as_str());
+                }
+                tokens.extend(std::iter::repeat_n(
+                    ".".to_string(),
+                    beats.saturating_sub(cells) as usize,
+                ));
+                if bar.repeat_end.is_some() {
+                    tokens.push(":|".to_string());
+                }
+            }
+            if line.last().is_none_or(|bar| bar.repeat_end.is_none()) {
+                tokens.push("|".to_string());
+            }
+            // Bar lines of bars that follow each other merge, like `:| |` into `:|`
+            out.push_str(&tokens.join(" ").replace(":| |", ":|"));
+            out.push('\n');
+        }
+        out.push_str("{end_of_grid}\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chords(song: &Song) -> Vec<Vec<(&str, u8)>> {
+        song.bars
+            .iter()
+            .map(|bar| {
+                bar.chords
+                    .iter()
+                    .map(|c| (c.name.as_str(), c.beats))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lyrics_grid_and_chorus() {
+        let text = "\
+{title: Example}
+{key: G}
+{tempo: 96}
+{time: 3/4}
+# A comment
+{start_of_verse: Verse 1}
+[G]Lyrics go [Em]here, and [*softly] [C]there
+{end_of_verse}
+{start_of_chorus}
+{start_of_grid}
+|: D7 . . | G . C :| x2
+{end_of_grid}
+{end_of_chorus}
+{start_of_grid: label=\"Outro\"}
+| Am . . | % | D7 . . |.
+{end_of_grid}
+{chorus}
+";
+        let song = parse_chordpro(text).unwrap();
+        assert_eq!(song.title, "Example");
+        assert_eq!(song.key, "G");
+        assert_eq!(song.bpm, Some(96));
+        assert_eq!(song.time_signature, (3, 4));
+        assert_eq!(
+            chords(&song),
+            [
+                vec![("G", 3)],
+                vec![("Em", 3)],
+                vec![("C", 3)],
+                vec![("D7", 3)],
+                vec![("G", 2), ("C", 1)],
+                vec![("Am", 3)],
+                vec![("Am", 3)],
+                vec![("D7", 3)],
+                vec![("D7", 3)],
+                vec![("G", 2), ("C", 1)],
+            ]
+        );
+        let sections: Vec<Option<&str>> = song.bars.iter().map(|b| b.section.as_deref()).collect();
+        assert_eq!(sections[0], Some("Verse 1"));
+        assert_eq!(sections[3], Some("Chorus"));
+        assert_eq!(sections[5], Some("Outro"));
+        assert_eq!(sections[8], Some("Chorus"));
+        assert!(song.bars[3].repeat_start);
+        assert_eq!(song.bars[4].repeat_end, Some(2));
+    }
+
+    #[test]
+    fn test_odd_directives() {
+        let song = parse_chordpro(
+            "{tempo: 0}\n{start_of_}\n[C]a\n{end_of_}\n{start_of_élan}\n[G]b\n{end_of_élan}",
+        )
+        .unwrap();
+        assert_eq!(song.bpm, None);
+        let sections: Vec<Option<&str>> = song.bars.iter().map(|b| b.section.as_deref()).collect();
+        assert_eq!(sections, [None, Some("Élan")]);
+    }
+
+    #[test]
+    fn test_written_chart_reads_back() {
+        let song = parse_chordpro(
+            "{time: 4/4}\n{sog: label=\"A\"}\n|: Dm7 . G7 . | Cmaj7 . . . :|\n| % | A7 . . . |\n{eog}",
+        )
+        .unwrap();
+        let written = write_chordpro(&song);
+        assert!(written.contains("{start_of_grid: label=\"A\"}"));
+        assert!(written.contains("|: Dm7 . G7 . | Cmaj7 . . . :| Cmaj7 . . . | A7 . . . |"));
+        assert_eq!(parse_chordpro(&written).unwrap(), song);
+
+        // A chord without beats, as in a hand-edited library chart, takes one
+        let mut song = song;
+        song.bars[3].chords[0].beats = 0;
+        assert!(write_chordpro(&song).contains("| A7 "));
+    }
+}
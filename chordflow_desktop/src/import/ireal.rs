@@ -0,0 +1,464 @@
+use anyhow::{bail, Result};
+use percent_encoding::percent_decode_str;
+
+use crate::state::song::{split_beats, Jump, Song, SongBar, SongChord};
+
+/// Marks the start of the scrambled chart in `irealb://` songs
+const MUSIC_PREFIX: &str = "1r34LbKcu7";
+/// Charts are unscrambled in blocks of this many characters
+const BLOCK: usize = 50;
+/// Shorthands of the chart that stand for other tokens
+const SHORTHANDS: [(&str, &str); 3] = [("XyQ", "   "), ("LZ", " |"), ("Kcl", "| x")];
+
+/// iReal Pro chord qualities and how they are usually written
+const QUALITIES: [(&str, &str); 62] = [
+    ("7susadd3", "7sus4"),
+    ("7b9sus", "7b9sus4"),
+    ("7b13sus", "7b13sus4"),
+    ("7#9#11", "7#9#11"),
+    ("7b9#11", "7b9#11"),
+    ("7b9b13", "7b9b13"),
+    ("7#9#5", "7#9#5"),
+    ("7#9b5", "7#9b5"),
+    ("7b9b5", "7b9b5"),
+    ("7b9#5", "7b9#5"),
+    ("7b9#9", "7b9#9"),
+    ("^7#11", "maj7#11"),
+    ("^9#11", "maj9#11"),
+    ("13sus", "13sus4"),
+    ("13#11", "13#11"),
+    ("^7#5", "maj7#5"),
+    ("-7b5", "m7b5"),
+    ("9#11", "9#11"),
+    ("7alt", "7alt"),
+    ("7sus", "7sus4"),
+    ("9sus", "9sus4"),
+    ("7b13", "7b13"),
+    ("13b9", "13b9"),
+    ("13#9", "13#9"),
+    ("add9", "add9"),
+    ("-b6", "mb6"),
+    ("-#5", "m#5"),
+    ("-69", "m69"),
+    ("-^7", "mMaj7"),
+    ("-^9", "mMaj9"),
+    ("-11", "m11"),
+    ("7b9", "7b9"),
+    ("7#9", "7#9"),
+    ("7#11", "7#11"),
+    ("7b5", "7b5"),
+    ("7#5", "7#5"),
+    ("9b5", "9b5"),
+    ("9#5", "9#5"),
+    ("sus", "sus4"),
+    ("^13", "maj13"),
+    ("^7", "maj7"),
+    ("^9", "maj9"),
+    ("-7", "m7"),
+    ("-6", "m6"),
+    ("-9", "m9"),
+    ("h7", "m7b5"),
+    ("h9", "m9b5"),
+    ("o7", "dim7"),
+    ("69", "69"),
+    ("13", "13"),
+    ("11", "11"),
+    ("^", "maj7"),
+    ("-", "m"),
+    ("h", "m7b5"),
+    ("o", "dim"),
+    ("+", "aug"),
+    ("2", "add9"),
+    ("5", "5"),
+    ("6", "6"),
+    ("7", "7"),
+    ("9", "9"),
+    ("", ""),
+];
+
+/// Songs in the `irealb://` or `irealbook://` links of a text, such as a link itself or
+/// a playlist exported as HTML
+pub fn parse_ireal(text: &str) -> Result<Vec<Song>> {
+    let mut songs = Vec::new();
+    for (start, _) in text.match_indices("irealb") {
+        let link = &text[start..];
+        let end = link
+            .find(|c: char| c == '"' || c == '\'' || c == '<' || c.is_whitespace())
+            .unwrap_or(link.len());
+        let link = percent_decode_str(&link[..end]).decode_utf8_lossy();
+        if let Some(body) = link.strip_prefix("irealb://") {
+            songs.extend(body.split("===").filter_map(parse_song));
+        } else if let Some(body) = link.strip_prefix("irealbook://") {
+            songs.extend(body.split("===").filter_map(parse_old_song));
+        }
+    }
+    if songs.is_empty() {
+        bail!("No iReal Pro songs found");
+    }
+    Ok(songs)
+}
+
+/// `Title=Composer=Unused=Style=Key=Transpose=Music=Comp style=BPM=Repeats`
+fn parse_song(text: &str) -> Option<Song> {
+    let fields: Vec<&str> = text.split('=').collect();
+    let music = fields.get(6)?.strip_prefix(MUSIC_PREFIX)?;
+    let bpm = fields
+        .get(8)
+        .and_then(|bpm| bpm.parse().ok())
+        .filter(|&bpm| bpm > 0);
+    Some(song(&fields, 4, &unscramble(music), bpm))
+}
+
+/// `Title=Composer=Style=Key=n=Music`, the chart is not scrambled
+fn parse_old_song(text: &str) -> Option<Song> {
+    let fields: Vec<&str> = text.split('=').collect();
+    Some(song(&fields, 3, fields.get(5)?, None))
+}
+
+fn song(fields: &[&str], key_field: usize, music: &str, bpm: Option<u16>) -> Song {
+    let mut song = parse_chart(music);
+    let field = |i: usize| fields.get(i).map_or("", |f| f.trim());
+    // Titles are filed like "Girl From Ipanema, The"
+    song.title = match field(0).strip_suffix(", The") {
+        Some(title) => format!("The {}", title),
+        None => field(0).to_string(),
+    };
+    // Composers are filed last name first
+    song.composer = match field(1).split_once(' ') {
+        Some((last, first)) => format!("{} {}", first, last),
+        None => field(1).to_string(),
+    };
+    song.key = match field(key_field).strip_suffix('-') {
+        Some(root) => format!("{}m", root),
+        None => field(key_field).to_string(),
+    };
+    song.bpm = bpm;
+    song
+}
+
+/// The scrambling swaps characters within each whole block of 50, except in the last
+/// stretch of up to 51 characters
+fn unscramble(music: &str) -> String {
+    let mut chars: Vec<char> = music.chars().collect();
+    let mut start = 0;
+    while chars.len() - start > BLOCK + 1 {
+        let block = &mut chars[start..start + BLOCK];
+        for i in (0..5).chain(10..24) {
+            block.swap(i, BLOCK - 1 - i);
+        }
+        start += BLOCK;
+    }
+    let mut music: String = chars.into_iter().collect();
+    for (shorthand, replacement) in SHORTHANDS {
+        music = music.replace(shorthand, replacement);
+    }
+    music
+}
+
+/// Reads the chart of a song into bars
+struct ChartReader {
+    bars: Vec<SongBar>,
+    /// The bar being read and the chords in it, as slots that split its beats
+    bar: SongBar,
+    slots: Vec<Option<String>>,
+    /// Whether the bar being read has chords or repeat signs, not just space
+    has_content: bool,
+    time_signature: Option<(u8, u8)>,
+    beats: u8,
+}
+
+impl ChartReader {
+    fn new() -> Self {
+        Self {
+            bars: Vec::new(),
+            bar: SongBar::default(),
+            slots: Vec::new(),
+            has_content: false,
+            time_signature: None,
+            beats: 4,
+        }
+    }
+
+    /// Close the bar being read. Space between bar lines with nothing in it is layout,
+    /// signs that end a bar found there belong to the bar before.
+    fn end_bar(&mut self) {
+        if !self.has_content {
+            if let Some(last) = self.bars.last_mut() {
+                last.repeat_end = self.bar.repeat_end.take().or(last.repeat_end);
+                last.jump = self.bar.jump.take().or(last.jump);
+                last.fine |= std::mem::take(&mut self.bar.fine);
+            }
+            return;
+        }
+        let mut bar = std::mem::take(&mut self.bar);
+        bar.chords = split_beats(&self.slots, self.beats);
+        self.bars.push(bar);
+        self.slots.clear();
+        self.has_content = false;
+    }
+
+    /// Repeat the chords of the bars `back` bars before
+    fn repeat_bars(&mut self, back: usize) {
+        let start = self.bars.len().saturating_sub(back);
+        let repeated: Vec<Vec<SongChord>> = self.bars[start..]
+            .iter()
+            .map(|b| b.chords.clone())
+            .collect();
+        for (i, chords) in repeated.into_iter().enumerate() {
+            if i > 0 {
+                self.end_bar();
+            }
+            // A slot per beat keeps the chords as long as they were
+            self.slots = chords
+                .into_iter()
+                .flat_map(|c| {
+                    let held = c.beats.saturating_sub(1) as usize;
+                    std::iter::once(Some(c.name)).chain(std::iter::repeat_n(None, held))
+                })
+                .collect();
+            self.has_content = true;
+        }
+    }
+}
+
+/// Read a root note such as `Bb` at the start of `text`
+fn read_root(text: &str) -> Option<&str> {
+    let mut chars = text.char_indices();
+    let (_, root) = chars.next()?;
+    if !('A'..='G').contains(&root) {
+        return None;
+    }
+    let length = match chars.next() {
+        Some((_, 'b' | '#')) => 2,
+        _ => 1,
+    };
+    Some(&text[..length])
+}
+
+/// Read a chord at the start of `text`: its name and the length it took up.
+/// An alternate chord in parentheses after it is skipped.
+fn read_chord(text: &str) -> Option<(Option<String>, usize)> {
+    // A `W` root only changes the bass, the chord before goes on
+    let (root, invisible) = match text.strip_prefix('W') {
+        Some(_) => ("", true),
+        None => (read_root(text)?, false),
+    };
+    let mut length = root.len() + invisible as usize;
+    let (ireal, quality) = QUALITIES
+        .iter()
+        .filter(|(ireal, _)| text[length..].starts_with(ireal))
+        .max_by_key(|(ireal, _)| ireal.len())?;
+    length += ireal.len();
+    let mut name = format!("{}{}", root, quality);
+    if let Some(bass) = text[length..].strip_prefix('/').and_then(read_root) {
+        name = format!("{}/{}", name, bass);
+        length += 1 + bass.len();
+    }
+    if text[length..].starts_with('(') {
+        length += text[length..]
+            .find(')')
+            .map_or(text.len() - length, |end| end + 1);
+    }
+    Some(((!invisible).then_some(name), length))
+}
+
+fn read_time_signature(digits: &str) -> Option<(u8, u8)> {
+    match digits {
+        "12" => Some((12, 8)),
+        _ => {
+            let mut chars = digits.chars().filter_map(|c| c.to_digit(10));
+            Some((chars.next()? as u8, chars.next()? as u8))
+        }
+    }
+}
+
+/// Staff text such as `D.S. al Coda`, `Fine` or `3x`
+fn read_comment(bar: &mut SongBar, repeats: &mut Option<u8>, comment: &str) {
+    let text = comment.trim().to_lowercase();
+    let jump = if text.starts_with("d.s.") {
+        Some(if text.contains("coda") {
+            Jump::DalSegnoAlCoda
+        } else if text.contains("fine") {
+            Jump::DalSegnoAlFine
+        } else {
+            Jump::DalSegno
+        })
+    } else if text.starts_with("d.c.") {
+        Some(if text.contains("coda") {
+            Jump::DaCapoAlCoda
+        } else if text.contains("fine") {
+            Jump::DaCapoAlFine
+        } else {
+            Jump::DaCapo
+        })
+    } else {
+        None
+    };
+    if jump.is_some() {
+        bar.jump = jump;
+    } else if text == "fine" {
+        bar.fine = true;
+    } else if let Some(times) = text.strip_suffix('x').and_then(|t| t.trim().parse().ok()) {
+        *repeats = Some(times);
+    }
+}
+
+/// Read the chart of a song, once it is unscrambled, into bars
+fn parse_chart(music: &str) -> Song {
+    let mut reader = ChartReader::new();
+    // Times the repeat being read is played, from staff text like `3x`
+    let mut repeats = None;
+
+    let mut i = 0;
+    while i < music.len() {
+        let rest = &music[i..];
+        let c = rest.chars().next().unwrap_or(' ');
+        let mut length = c.len_utf8();
+        match c {
+            '|' | '[' | ']' | '{' | '}' | 'Z' => {
+                if c == '}' {
+                    reader.bar.repeat_end = Some(repeats.take().unwrap_or(2));
+                }
+                reader.end_bar();
+                if c == '{' {
+                    reader.bar.repeat_start = true;
+                }
+            }
+            '*' => {
+                if let Some(mark) = rest[1..].chars().next() {
+                    reader.bar.section = Some(match mark {
+                        'i' => "Intro".to_string(),
+                        'V' => "Verse".to_string(),
+                        _ => mark.to_string(),
+                    });
+                    length += mark.len_utf8();
+                }
+            }
+            'T' => {
+                if let Some(signature) = rest.get(1..3).and_then(read_time_signature) {
+                    reader.time_signature.get_or_insert(signature);
+                    reader.beats = signature.0.max(1);
+                    length = 3;
+                }
+            }
+            'N' => {
+                if let Some(number) = rest.get(1..2) {
+                    reader.bar.ending = number.parse().ok().filter(|&n| n > 0);
+                    length = 2;
+                }
+            }
+            '<' => {
+                let end = rest.find('>').unwrap_or(rest.len());
+                read_comment(&mut reader.bar, &mut repeats, &rest[1..end]);
+                length = (end + 1).min(rest.len());
+            }
+            'S' => reader.bar.segno = true,
+            'Q' => reader.bar.coda = true,
+            'x' => reader.repeat_bars(1),
+            'r' => reader.repeat_bars(2),
+            // A slash or no chord continues the chord before
+            'p' | 'n' => {
+                reader.slots.push(None);
+                reader.has_content = true;
+            }
+            // Anything else is a chord, or spacing and chord sizes that only change how the
+            // chart looks
+            _ => {
+                if let Some((name, chord_length)) = read_chord(rest) {
+                    reader.slots.push(name);
+                    reader.has_content = true;
+                    length = chord_length.max(1);
+                }
+            }
+        }
+        i += length;
+        while i < music.len() && !music.is_char_boundary(i) {
+            i += 1;
+        }
+    }
+    reader.end_bar();
+
+    Song {
+        time_signature: reader.time_signature.unwrap_or((4, 4)),
+        bars: reader.bars,
+        ..Song::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+    use super::*;
+
+    fn scramble(music: &str) -> String {
+        // Swapping is its own inverse
+        let mut chars: Vec<char> = music.chars().collect();
+        let mut start = 0;
+        while chars.len() - start > BLOCK + 1 {
+            for i in (0..5).chain(10..24) {
+                chars.swap(start + i, start + BLOCK - 1 - i);
+            }
+            start += BLOCK;
+        }
+        chars.into_iter().collect()
+    }
+
+    fn chords(bar: &SongBar) -> Vec<(&str, u8)> {
+        bar.chords
+            .iter()
+            .map(|c| (c.name.as_str(), c.beats))
+            .collect()
+    }
+
+    #[test]
+    fn test_link_with_form() {
+        let music = "{*AT44D-7XyQ|G7XyQ|N1C^7 A7LZD-7 G7}XyQ|N2C^7XyQKcl\
+                     [*BSC-7 F7LZBb^7XyQ|Bh7 E7b9LZA-7<D.S. al Coda>XyQ|QC^7 n Z";
+        let link = format!(
+            "<a href=\"irealb://Test%20Song%2C%20The=Doe%20Jane==Medium%20Swing=C==\
+             {}{}==140=3\">Test</a>",
+            MUSIC_PREFIX,
+            utf8_percent_encode(&scramble(music), NON_ALPHANUMERIC)
+        );
+        let songs = parse_ireal(&link).unwrap();
+        assert_eq!(songs.len(), 1);
+        let song = &songs[0];
+        assert_eq!(song.title, "The Test Song");
+        assert_eq!(song.composer, "Jane Doe");
+        assert_eq!(song.key, "C");
+        assert_eq!(song.bpm, Some(140));
+        assert_eq!(song.time_signature, (4, 4));
+
+        let bars = &song.bars;
+        assert_eq!(bars.len(), 11);
+        assert!(bars[0].repeat_start);
+        assert_eq!(bars[0].section.as_deref(), Some("A"));
+        assert_eq!(chords(&bars[0]), [("Dm7", 4)]);
+        assert_eq!(chords(&bars[2]), [("Cmaj7", 2), ("A7", 2)]);
+        assert_eq!(bars[2].ending, Some(1));
+        assert_eq!(bars[3].repeat_end, Some(2));
+        assert_eq!(bars[4].ending, Some(2));
+        assert_eq!(chords(&bars[5]), [("Cmaj7", 4)]);
+        assert!(bars[6].segno);
+        assert_eq!(bars[6].section.as_deref(), Some("B"));
+        assert_eq!(chords(&bars[7]), [("Bbmaj7", 4)]);
+        assert_eq!(chords(&bars[8]), [("Bm7b5", 2), ("E7b9", 2)]);
+        assert_eq!(bars[9].jump, Some(Jump::DalSegnoAlCoda));
+        assert!(bars[10].coda);
+        assert_eq!(chords(&bars[10]), [("Cmaj7", 4)]);
+    }
+
+    #[test]
+    fn test_jump_and_coda() {
+        let song = parse_chart("[*AC^7 |QD-7 |G7<D.C. al Coda> ][QC^7 n |x Z");
+        let bars = &song.bars;
+        assert_eq!(bars.len(), 5);
+        assert_eq!(bars[2].jump, Some(Jump::DaCapoAlCoda));
+        assert!(bars[1].coda && bars[3].coda);
+        assert_eq!(chords(&bars[3]), [("Cmaj7", 4)]);
+        assert_eq!(chords(&bars[4]), [("Cmaj7", 4)]);
+        let order = song.play_order();
+        assert_eq!(order, [0, 1, 2, 0, 1, 3, 4]);
+    }
+}
@@ -0,0 +1,279 @@
+use anyhow::{bail, Context, Result};
+
+use crate::state::song::{split_beats, Song, SongBar, SongChord};
+
+/// A bar line: `|`, `||`, `|:`, `:|`, `:|:` or one starting an ending, like `|2.`
+struct BarLine {
+    repeat_end: bool,
+    repeat_start: bool,
+    ending: Option<u8>,
+}
+
+/// Split the bar line off the start of `text`
+fn read_bar_line(text: &str) -> Option<(BarLine, &str)> {
+    let rest = text.strip_prefix(':');
+    let repeat_end = rest.is_some();
+    let rest = rest.unwrap_or(text).strip_prefix('|')?;
+    let rest = rest.strip_prefix('|').unwrap_or(rest);
+    let after_colon = rest.strip_prefix(':');
+    let repeat_start = after_colon.is_some();
+    let rest = after_colon.unwrap_or(rest);
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let ending = rest[..digits].parse().ok();
+    let rest = match ending {
+        Some(_) => rest[digits..].strip_prefix('.').unwrap_or(&rest[digits..]),
+        None => rest,
+    };
+    Some((
+        BarLine {
+            repeat_end,
+            repeat_start,
+            ending,
+        },
+        rest,
+    ))
+}
+
+/// The chords of the bar with `number`, from cells like `Dm7` or `Dm7:3`. Chords without
+/// beats share the beats the others leave.
+fn read_bar(
+    cells: &[&str],
+    previous: Option<&SongBar>,
+    number: usize,
+    beats_per_bar: u8,
+) -> Result<Vec<SongChord>> {
+    if cells == ["%"] {
+        return previous
+            .map(|bar| bar.chords.clone())
+            .with_context(|| format!("Bar {}: nothing to repeat", number));
+    }
+    let mut chords = Vec::new();
+    for &cell in cells {
+        if cell == "%" {
+            bail!("Bar {}: % repeats a whole bar", number);
+        }
+        let chord = match cell.rsplit_once(':') {
+            Some((name, beats)) => {
+                let beats = beats.parse::<u8>().ok().filter(|&b| b > 0);
+                (
+                    name,
+                    Some(beats.with_context(|| format!("Bar {}: {} has no beats", number, cell))?),
+                )
+            }
+            None => (cell, None),
+        };
+        chords.push(chord);
+    }
+    let given: u32 = chords
+        .iter()
+        .filter_map(|(_, beats)| *beats)
+        .map(u32::from)
+        .sum();
+    let open = chords.iter().filter(|(_, beats)| beats.is_none()).count() as u32;
+    let fits = match open {
+        0 => given == beats_per_bar as u32,
+        _ => given + open <= beats_per_bar as u32,
+    };
+    if !fits {
+        bail!(
+            "Bar {}: the chords do not fill its {} beats",
+            number,
+            beats_per_bar
+        );
+    }
+    let open_names: Vec<Option<String>> = chords
+        .iter()
+        .filter(|(_, beats)| beats.is_none())
+        .map(|(name, _)| Some(name.to_string()))
+        .collect();
+    let mut shared = split_beats(&open_names, beats_per_bar - given as u8).into_iter();
+    Ok(chords
+        .into_iter()
+        .filter_map(|(name, beats)| match beats {
+            Some(beats) => Some(SongChord {
+                name: name.to_string(),
+                beats,
+            }),
+            None => shared.next(),
+        })
+        .collect())
+}
+
+/// Add the bar of `cells` to the song, if there are any
+fn close_bar(song: &mut Song, next_bar: &mut SongBar, cells: &mut Vec<&str>) -> Result<()> {
+    if cells.is_empty() {
+        return Ok(());
+    }
+    let number = song.bars.len() + 1;
+    let chords = read_bar(cells, song.bars.last(), number, song.time_signature.0)?;
+    cells.clear();
+    song.bars.push(SongBar {
+        chords,
+        ..std::mem::take(next_bar)
+    });
+    Ok(())
+}
+
+/// Read a lead sheet like `[A] |: Dm7 G7 | Cmaj7 |1. % :|2. A7:3 D7:1 |` in bars of
+/// `beats_per_bar`: bar lines with repeats and endings, `%` for the bar before, section
+/// labels, and the beats of a chord after a colon
+pub fn parse_lead_sheet(text: &str, beats_per_bar: u8) -> Result<Song> {
+    let beats_per_bar = beats_per_bar.max(1);
+    let mut song = Song {
+        time_signature: (beats_per_bar, 4),
+        ..Song::default()
+    };
+    let mut next_bar = SongBar::default();
+    let mut cells: Vec<&str> = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        if let Some((bar_line, after)) = read_bar_line(rest) {
+            close_bar(&mut song, &mut next_bar, &mut cells)?;
+            if bar_line.repeat_end {
+                let last = song
+                    .bars
+                    .last_mut()
+                    .context("A repeat ends before the first bar")?;
+                last.repeat_end = Some(2);
+            }
+            next_bar.repeat_start |= bar_line.repeat_start;
+            next_bar.ending = next_bar.ending.or(bar_line.ending);
+            rest = after;
+        } else if let Some(label) = rest.strip_prefix('[') {
+            let (label, after) = label
+                .split_once(']')
+                .context("A section label misses its ]")?;
+            close_bar(&mut song, &mut next_bar, &mut cells)?;
+            next_bar.section = Some(label.trim().to_string());
+            rest = after;
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '|')
+                .unwrap_or(rest.len());
+            // The colon of a closing repeat is not part of the chord
+            let cell = match rest[end..].starts_with('|') {
+                true => rest[..end].strip_suffix(':').unwrap_or(&rest[..end]),
+                false => &rest[..end],
+            };
+            if cell.is_empty() {
+                bail!("Unexpected {}", rest);
+            }
+            cells.push(cell);
+            rest = &rest[cell.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    close_bar(&mut song, &mut next_bar, &mut cells)?;
+    if song.bars.is_empty() {
+        bail!("No chords found");
+    }
+    Ok(song)
+}
+
+/// The bar line between a bar ending a repeat or not and the bar after, if any
+fn bar_line(repeat_end: bool, next: Option<&SongBar>) -> String {
+    let mut line = String::from(if repeat_end { ":|" } else { "|" });
+    match next {
+        Some(bar) if bar.repeat_start => line.push(':'),
+        Some(SongBar {
+            ending: Some(ending),
+            ..
+        }) => line.push_str(&format!("{}.", ending)),
+        _ => {}
+    }
+    line
+}
+
+/// Write a song as a lead sheet, a line per section. Beats are only written for chords
+/// that do not share their bar evenly.
+pub fn write_lead_sheet(song: &Song) -> String {
+    let beats_per_bar = song.time_signature.0.max(1);
+    let mut lines = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut previous: Option<&SongBar> = None;
+    for bar in &song.bars {
+        let repeat_end = previous.is_some_and(|p| p.repeat_end.is_some());
+        match &bar.section {
+            Some(section) => {
+                if !tokens.is_empty() {
+                    tokens.push(bar_line(repeat_end, None));
+                    lines.push(tokens.join(" "));
+                    tokens.clear();
+                }
+                tokens.push(format!("[{}]", section));
+                tokens.push(bar_line(false, Some(bar)));
+            }
+            None => tokens.push(bar_line(repeat_end, Some(bar))),
+        }
+
+        let held = previous.map(|p| p.chords.as_slice()).unwrap_or_default();
+        if bar.chords.is_empty() && held.len() > 1 {
+            // Held through the bar, which is not a copy of the shared one before
+            tokens.extend(held.last().map(|chord| chord.name.clone()));
+        } else if bar.chords.is_empty() || bar.chords == held {
+            tokens.push("%".to_string());
+        } else {
+            let names: Vec<Option<String>> =
+                bar.chords.iter().map(|c| Some(c.name.clone())).collect();
+            let even = split_beats(&names, beats_per_bar) == bar.chords;
+            tokens.extend(bar.chords.iter().map(|chord| match even {
+                true => chord.name.clone(),
+                false => format!("{}:{}", chord.name, chord.beats),
+            }));
+        }
+        previous = Some(bar);
+    }
+    if let Some(last) = previous {
+        tokens.push(bar_line(last.repeat_end.is_some(), None));
+        lines.push(tokens.join(" "));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lead_sheet_round_trip() {
+        let text = "[A] |: Dm7 G7 | Cmaj7 |1. % :|2. A7:3 D7:1 |\n[B] | Fmaj7 | % | E7 A7 |";
+        let song = parse_lead_sheet(text, 4).unwrap();
+        let chords: Vec<Vec<(&str, u8)>> = song
+            .bars
+            .iter()
+            .map(|bar| {
+                bar.chords
+                    .iter()
+                    .map(|c| (c.name.as_str(), c.beats))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            chords,
+            [
+                vec![("Dm7", 2), ("G7", 2)],
+                vec![("Cmaj7", 4)],
+                vec![("Cmaj7", 4)],
+                vec![("A7", 3), ("D7", 1)],
+                vec![("Fmaj7", 4)],
+                vec![("Fmaj7", 4)],
+                vec![("E7", 2), ("A7", 2)],
+            ]
+        );
+        assert!(song.bars[0].repeat_start);
+        assert_eq!(
+            (song.bars[2].ending, song.bars[2].repeat_end),
+            (Some(1), Some(2))
+        );
+        assert_eq!(song.bars[3].ending, Some(2));
+        assert_eq!(song.bars[4].section.as_deref(), Some("B"));
+        assert_eq!(write_lead_sheet(&song), text);
+
+        // Chords without beats share what the others leave
+        let song = parse_lead_sheet("|Dm7:2 G7 C|", 4).unwrap();
+        assert_eq!(song.bars[0].chords[2].beats, 1);
+        assert!(parse_lead_sheet("| C:3 G:3 |", 4).is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use anyhow::{bail, Context, Result};
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use crate::import::{chord_recognition::recognise, ImportedChord};
+
+/// MIDI channel General MIDI plays percussion on, it has no harmony
+const GM_PERCUSSION_CHANNEL: u8 = 9;
+/// Tempo of a file without tempo events, 120 bpm
+const DEFAULT_TEMPO_MICROS: f64 = 500_000.0;
+/// Notes shorter than this part of a bar do not make the bass note, unless all of them are
+const BASS_MIN_FRACTION: f64 = 1.0 / 8.0;
+/// Time signatures the metronome can count, in quarter notes per bar
+const BEATS_PER_BAR: std::ops::RangeInclusive<u8> = 2..=7;
+
+/// A note, in quarter notes from the start of the file
+#[derive(Debug, Clone, Copy)]
+struct ImportedNote {
+    start: f64,
+    end: f64,
+    key: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct MidiTrack {
+    pub name: String,
+    notes: Vec<ImportedNote>,
+}
+
+impl MidiTrack {
+    pub fn note_count(&self) -> usize {
+        self.notes.len()
+    }
+}
+
+/// Turns ticks into quarter notes. With SMPTE timing ticks are real time, so the
+/// tempo changes are needed to find the beats.
+struct Clock {
+    /// Tick a tempo starts on, quarter notes up to it and quarter notes per tick
+    segments: Vec<(u64, f64, f64)>,
+}
+
+impl Clock {
+    fn new(timing: Timing, tempos: &[(u64, u32)]) -> Self {
+        let ticks_per_second = match timing {
+            Timing::Metrical(ppq) => {
+                return Self {
+                    segments: vec![(0, 0.0, 1.0 / ppq.as_int().max(1) as f64)],
+                }
+            }
+            Timing::Timecode(fps, subframes) => fps.as_f32() as f64 * subframes.max(1) as f64,
+        };
+        let per_tick = |micros: f64| 1.0 / ticks_per_second / (micros / 1e6);
+        let mut clock = Self {
+            segments: vec![(0, 0.0, per_tick(DEFAULT_TEMPO_MICROS))],
+        };
+        for &(tick, micros) in tempos {
+            let quarters = clock.quarters(tick);
+            clock
+                .segments
+                .push((tick, quarters, per_tick(micros.max(1) as f64)));
+        }
+        clock
+    }
+
+    fn quarters(&self, tick: u64) -> f64 {
+        let (start, quarters, per_tick) = self
+            .segments
+            .iter()
+            .rev()
+            .find(|(start, _, _)| *start <= tick)
+            .copied()
+            .unwrap_or(self.segments[0]);
+        quarters + (tick - start.min(tick)) as f64 * per_tick
+    }
+}
+
+/// The tracks of a Standard MIDI File and its meter, ready to read chords from
+#[derive(Debug, Clone)]
+pub struct MidiImport {
+    /// Tracks that play pitched notes
+    pub tracks: Vec<MidiTrack>,
+    /// Where each time signature starts, in quarter notes, and its bar length
+    bar_lengths: Vec<(f64, f64)>,
+    /// Tempo at the start, in quarter notes per minute
+    pub bpm: Option<u16>,
+    /// The first time signature in quarter notes per bar, when the metronome can count it
+    pub beats_per_bar: Option<u8>,
+}
+
+impl MidiImport {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let smf = Smf::parse(bytes).context("Not a Standard MIDI File")?;
+        let tracks: Vec<Vec<(u64, TrackEventKind)>> = smf
+            .tracks
+            .iter()
+            .map(|track| {
+                let mut tick = 0;
+                track
+                    .iter()
+                    .map(|event| {
+                        tick += event.delta.as_int() as u64;
+                        (tick, event.kind)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Tempo and meter usually sit in the first track, but may be in any
+        let metas = || {
+            tracks
+                .iter()
+                .flatten()
+                .filter_map(|(tick, kind)| match kind {
+                    TrackEventKind::Meta(meta) => Some((*tick, meta)),
+                    _ => None,
+                })
+        };
+        let mut tempos: Vec<(u64, u32)> = metas()
+            .filter_map(|(tick, meta)| match meta {
+                MetaMessage::Tempo(micros) => Some((tick, micros.as_int())),
+                _ => None,
+            })
+            .collect();
+        tempos.sort_by_key(|(tick, _)| *tick);
+        let clock = Clock::new(smf.header.timing, &tempos);
+
+        let mut meters: Vec<(u64, u8, u8)> = metas()
+            .filter_map(|(tick, meta)| match meta {
+                MetaMessage::TimeSignature(numerator, denominator, _, _) => {
+                    Some((tick, (*numerator).max(1), *denominator))
+                }
+                _ => None,
+            })
+            .collect();
+        meters.sort_by_key(|(tick, _, _)| *tick);
+        let quarters_per_bar =
+            |numerator: u8, denominator: u8| numerator as f64 * 4.0 / 2f64.powi(denominator as i32);
+        let bar_lengths = meters
+            .iter()
+            .map(|&(tick, num, den)| (clock.quarters(tick), quarters_per_bar(num, den)))
+            .collect();
+        let beats_per_bar = meters
+            .first()
+            .map(|&(_, num, den)| quarters_per_bar(num, den))
+            .filter(|beats| beats.fract() == 0.0 && BEATS_PER_BAR.contains(&(*beats as u8)))
+            .map(|beats| beats as u8);
+        let bpm = tempos
+            .first()
+            .map(|(_, micros)| (60e6 / (*micros).max(1) as f64).round() as u16);
+
+        let tracks: Vec<MidiTrack> = tracks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, events)| {
+                let name = events.iter().find_map(|(_, kind)| match kind {
+                    TrackEventKind::Meta(MetaMessage::TrackName(name)) => {
+                        Some(String::from_utf8_lossy(name).trim().to_string())
+                    }
+                    _ => None,
+                });
+                let name = name
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| format!("Track {}", index + 1));
+                let notes = read_notes(events, &clock);
+                (!notes.is_empty()).then_some(MidiTrack { name, notes })
+            })
+            .collect();
+        if tracks.is_empty() {
+            bail!("The file has no notes to read chords from");
+        }
+
+        Ok(Self {
+            tracks,
+            bar_lengths,
+            bpm,
+            beats_per_bar,
+        })
+    }
+
+    /// Start and end of every bar up to `end`, in quarter notes. A time signature that
+    /// changes in the middle of a bar starts a new one.
+    fn bars(&self, end: f64) -> Vec<(f64, f64)> {
+        let mut bars = Vec::new();
+        let mut start = 0.0;
+        while start < end {
+            let length = self
+                .bar_lengths
+                .iter()
+                .rev()
+                .find(|(from, _)| *from <= start + 1e-6)
+                .map_or(4.0, |(_, length)| *length)
+                .max(0.25);
+            let next_meter = self
+                .bar_lengths
+                .iter()
+                .map(|(from, _)| *from)
+                .find(|from| *from > start + 1e-6);
+            let bar_end = next_meter.map_or(start + length, |from| from.min(start + length));
+            bars.push((start, bar_end));
+            start = bar_end;
+        }
+        bars
+    }
+
+    /// A chord for every bar of a track, repeated chords merged into longer ones.
+    /// Bars without a chord hold the one before, silence before the first chord is skipped.
+    pub fn chords(&self, track: usize) -> Result<Vec<ImportedChord>> {
+        let track = self.tracks.get(track).context("No such track")?;
+        let end = track.notes.iter().map(|n| n.end).fold(0.0, f64::max);
+
+        let mut chords: Vec<ImportedChord> = Vec::new();
+        for (start, bar_end) in self.bars(end) {
+            let mut weights = [0.0; 12];
+            let mut bass = None;
+            let mut lowest = None;
+            for note in &track.notes {
+                let overlap = note.end.min(bar_end) - note.start.max(start);
+                if overlap <= 0.0 {
+                    continue;
+                }
+                weights[(note.key % 12) as usize] += overlap;
+                if lowest.is_none_or(|lowest| note.key < lowest) {
+                    lowest = Some(note.key);
+                }
+                if overlap >= (bar_end - start) * BASS_MIN_FRACTION
+                    && bass.is_none_or(|bass| note.key < bass)
+                {
+                    bass = Some(note.key);
+                }
+            }
+            // Short notes only, like an arpeggio: the lowest of them is the bass
+            let name = bass
+                .or(lowest)
+                .and_then(|bass| recognise(&weights, bass % 12))
+                .map(|chord| chord.name());
+            match (name, chords.last_mut()) {
+                (Some(name), Some(last)) if last.name == name && last.bars < u8::MAX => {
+                    last.bars += 1
+                }
+                (Some(name), _) => chords.push(ImportedChord {
+                    name,
+                    bars: 1,
+                    beats: None,
+                }),
+                (None, Some(last)) if last.bars < u8::MAX => last.bars += 1,
+                (None, _) => {}
+            }
+        }
+        if chords.is_empty() {
+            bail!("No chords found in {}", track.name);
+        }
+        Ok(chords)
+    }
+}
+
+/// The pitched notes of a track. A key struck again ends its previous note, notes still
+/// held at the end of the track last until then.
+fn read_notes(events: &[(u64, TrackEventKind)], clock: &Clock) -> Vec<ImportedNote> {
+    let mut held = [[None::<u64>; 128]; 16];
+    let mut notes = Vec::new();
+    let mut end_note = |start: u64, end: u64, key: u8| {
+        notes.push(ImportedNote {
+            start: clock.quarters(start),
+            end: clock.quarters(end),
+            key,
+        })
+    };
+    for &(tick, kind) in events {
+        let TrackEventKind::Midi { channel, message } = kind else {
+            continue;
+        };
+        let channel = channel.as_int();
+        if channel == GM_PERCUSSION_CHANNEL {
+            continue;
+        }
+        let (key, starts) = match message {
+            MidiMessage::NoteOn { key, vel } => (key.as_int(), vel.as_int() > 0),
+            MidiMessage::NoteOff { key, .. } => (key.as_int(), false),
+            _ => continue,
+        };
+        let slot = &mut held[channel as usize][key as usize];
+        if let Some(start) = slot.take() {
+            end_note(start, tick, key);
+        }
+        if starts {
+            *slot = Some(tick);
+        }
+    }
+    let last_tick = events.last().map_or(0, |(tick, _)| *tick);
+    for channel in held {
+        for (key, start) in channel.into_iter().enumerate() {
+            if let Some(start) = start {
+                end_note(start, last_tick, key as u8);
+            }
+        }
+    }
+    notes.retain(|note| note.end > note.start);
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use midly::{
+        num::{u15, u24, u28, u4, u7},
+        Format, Header, TrackEvent,
+    };
+
+    use super::*;
+
+    const PPQ: u32 = 96;
+
+    fn event(delta: u32, kind: TrackEventKind<'static>) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: u28::new(delta),
+            kind,
+        }
+    }
+
+    /// Whole-bar chords of 3/4, each key list struck together
+    fn song(bars: &[&[u8]]) -> Vec<u8> {
+        let mut smf = Smf::new(Header::new(
+            Format::Parallel,
+            Timing::Metrical(u15::new(PPQ as u16)),
+        ));
+        smf.tracks.push(vec![
+            event(
+                0,
+                TrackEventKind::Meta(MetaMessage::TimeSignature(3, 2, 24, 8)),
+            ),
+            event(
+                0,
+                TrackEventKind::Meta(MetaMessage::Tempo(u24::new(600_000))),
+            ),
+            event(0, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+        ]);
+        let midi = |message| TrackEventKind::Midi {
+            channel: u4::new(0),
+            message,
+        };
+        let mut piano = vec![event(
+            0,
+            TrackEventKind::Meta(MetaMessage::TrackName(b"Piano")),
+        )];
+        // Time passed since the last event, an empty bar adds to it
+        let mut rest = 0;
+        for keys in bars {
+            for &key in keys.iter() {
+                let vel = u7::new(80);
+                let note_on = midi(MidiMessage::NoteOn {
+                    key: u7::new(key),
+                    vel,
+                });
+                piano.push(event(std::mem::take(&mut rest), note_on));
+            }
+            rest += 3 * PPQ;
+            for &key in keys.iter() {
+                let vel = u7::new(0);
+                let note_off = midi(MidiMessage::NoteOff {
+                    key: u7::new(key),
+                    vel,
+                });
+                piano.push(event(std::mem::take(&mut rest), note_off));
+            }
+        }
+        piano.push(event(0, TrackEventKind::Meta(MetaMessage::EndOfTrack)));
+        smf.tracks.push(piano);
+
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_chords_per_bar() {
+        let bytes = song(&[
+            &[50, 65, 69, 72],
+            &[43, 65, 71],
+            &[48, 64, 67, 71],
+            &[48, 64, 67, 71],
+            &[],
+            &[45, 61, 64, 67],
+        ]);
+        let import = MidiImport::parse(&bytes).unwrap();
+        assert_eq!(import.bpm, Some(100));
+        assert_eq!(import.beats_per_bar, Some(3));
+        assert_eq!(import.tracks.len(), 1);
+        assert_eq!(import.tracks[0].name, "Piano");
+
+        let chords: Vec<(String, u8)> = import
+            .chords(0)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.name, c.bars))
+            .collect();
+        let expected = [("Dm7", 1), ("G7", 1), ("Cmaj7", 3), ("A7", 1)];
+        assert_eq!(
+            chords,
+            expected.map(|(name, bars)| (name.to_string(), bars))
+        );
+    }
+
+    #[test]
+    fn test_arpeggiated_bars() {
+        // A bar of sixteenths per chord, no note long enough to count as the bass
+        let arpeggio = |bar: usize, keys: [u8; 4]| {
+            (0..16).map(move |i| ImportedNote {
+                start: bar as f64 * 4.0 + i as f64 * 0.25,
+                end: bar as f64 * 4.0 + (i + 1) as f64 * 0.25,
+                key: keys[i % 4],
+            })
+        };
+        let notes = arpeggio(0, [50, 53, 57, 60])
+            .chain(arpeggio(1, [43, 47, 50, 53]))
+            .collect();
+        let import = MidiImport {
+            tracks: vec![MidiTrack {
+                name: "Harp".to_string(),
+                notes,
+            }],
+            bar_lengths: vec![(0.0, 4.0)],
+            bpm: None,
+            beats_per_bar: Some(4),
+        };
+        let names: Vec<String> = import
+            .chords(0)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        assert_eq!(names, ["Dm7", "G7"]);
+    }
+}
@@ -0,0 +1,712 @@
+use std::io::{Cursor, Read};
+
+use anyhow::{bail, Context, Result};
+use quick_xml::{escape::escape, events::Event, Reader};
+
+use crate::{
+    import::read_bpm,
+    state::song::{Jump, Song, SongBar, SongChord},
+};
+
+/// MusicXML chord kinds and the chord symbol suffix they are written as
+const KINDS: [(&str, &str); 25] = [
+    ("major", ""),
+    ("minor", "m"),
+    ("augmented", "aug"),
+    ("diminished", "dim"),
+    ("dominant", "7"),
+    ("major-seventh", "maj7"),
+    ("minor-seventh", "m7"),
+    ("diminished-seventh", "dim7"),
+    ("augmented-seventh", "aug7"),
+    ("half-diminished", "m7b5"),
+    ("major-minor", "mmaj7"),
+    ("major-sixth", "6"),
+    ("minor-sixth", "m6"),
+    ("dominant-ninth", "9"),
+    ("major-ninth", "maj9"),
+    ("minor-ninth", "m9"),
+    ("dominant-11th", "11"),
+    ("major-11th", "maj11"),
+    ("minor-11th", "m11"),
+    ("dominant-13th", "13"),
+    ("major-13th", "maj13"),
+    ("minor-13th", "m13"),
+    ("suspended-second", "sus2"),
+    ("suspended-fourth", "sus4"),
+    ("power", "5"),
+];
+
+/// Major keys by their number of fifths, from 7 flats to 7 sharps
+const MAJOR_KEYS: [&str; 15] = [
+    "Cb", "Gb", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#",
+];
+const MINOR_KEYS: [&str; 15] = [
+    "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#", "G#", "D#", "A#",
+];
+
+/// An element of the document with its attributes, text and child elements
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn new(start: &quick_xml::events::BytesStart) -> Result<Self> {
+        let mut attributes = Vec::new();
+        for attribute in start.attributes() {
+            let attribute = attribute?;
+            let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+            attributes.push((key, attribute.unescape_value()?.into_owned()));
+        }
+        Ok(Self {
+            name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+            attributes,
+            ..Self::default()
+        })
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// The first element called `name` below this one, at any depth
+    fn find(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|c| {
+            if c.name == name {
+                Some(c)
+            } else {
+                c.find(name)
+            }
+        })
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn text_of(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|c| c.text.trim())
+    }
+
+    fn number_of(&self, name: &str) -> Option<f64> {
+        self.text_of(name).and_then(|t| t.parse().ok())
+    }
+}
+
+/// Read a document into its root element
+fn read_tree(xml: &str) -> Result<Element> {
+    let mut reader = Reader::from_str(xml.trim_start_matches('\u{feff}'));
+    let mut stack = vec![Element::default()];
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => stack.push(Element::new(&start)?),
+            Event::Empty(start) => {
+                let element = Element::new(&start)?;
+                stack.last_mut().unwrap().children.push(element);
+            }
+            Event::End(_) if stack.len() > 1 => {
+                let element = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(element);
+            }
+            Event::Text(text) => stack.last_mut().unwrap().text.push_str(&text.unescape()?),
+            Event::CData(text) => {
+                let text = text.into_inner();
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .text
+                    .push_str(&String::from_utf8_lossy(&text));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    stack
+        .swap_remove(0)
+        .children
+        .into_iter()
+        .next()
+        .context("The file has no elements")
+}
+
+fn read_file(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String> {
+    let mut bytes = Vec::new();
+    archive.by_name(name)?.read_to_end(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The score of a compressed `.mxl` file, named by its container or else its first document
+fn read_compressed(bytes: &[u8]) -> Result<String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let root_file = read_file(&mut archive, "META-INF/container.xml")
+        .ok()
+        .and_then(|container| read_tree(&container).ok())
+        .and_then(|container| {
+            let path = container.find("rootfile")?.attribute("full-path")?;
+            Some(path.to_string())
+        });
+    let path = match root_file {
+        Some(path) => path,
+        None => archive
+            .file_names()
+            .find(|name| {
+                !name.starts_with("META-INF")
+                    && (name.ends_with(".xml") || name.ends_with(".musicxml"))
+            })
+            .map(str::to_string)
+            .context("No score found in the compressed file")?,
+    };
+    read_file(&mut archive, &path)
+}
+
+/// A chord symbol from a `<harmony>`: root, kind, added or altered degrees and bass.
+/// None for `none`, which marks that no chord is played.
+fn chord_name(harmony: &Element) -> Option<String> {
+    let note = |element: &Element, step: &str, alter: &str| {
+        let step = element.text_of(step)?;
+        let accidental = match element.number_of(alter).unwrap_or(0.0) as i32 {
+            1 => "#",
+            -1 => "b",
+            _ => "",
+        };
+        Some(format!("{}{}", step, accidental))
+    };
+    let root = note(harmony.child("root")?, "root-step", "root-alter")?;
+    let kind = harmony.child("kind")?;
+    let mut name = match KINDS.iter().find(|(value, _)| *value == kind.text.trim()) {
+        Some((_, suffix)) => format!("{}{}", root, suffix),
+        None if kind.text.trim() == "none" => return None,
+        // Kinds without a common symbol, like `Tristan`, are named by their text
+        None => format!("{}{}", root, kind.attribute("text").unwrap_or_default()),
+    };
+    for degree in harmony.children("degree") {
+        let value = degree.text_of("degree-value").unwrap_or_default();
+        let accidental = match degree.number_of("degree-alter").unwrap_or(0.0) as i32 {
+            1 => "#",
+            -1 => "b",
+            _ => "",
+        };
+        match degree.text_of("degree-type") {
+            Some("subtract") => name.push_str(&format!("no{}", value)),
+            Some("add") if accidental.is_empty() => name.push_str(&format!("add{}", value)),
+            _ => name.push_str(&format!("{}{}", accidental, value)),
+        }
+    }
+    if let Some(bass) = harmony
+        .child("bass")
+        .and_then(|b| note(b, "bass-step", "bass-alter"))
+    {
+        name.push_str(&format!("/{}", bass));
+    }
+    Some(name)
+}
+
+/// Signs, texts and tempo of a `<direction>`, put on the bar it is in
+fn read_direction(direction: &Element, bar: &mut SongBar, song: &mut Song) {
+    for kind in direction.children("direction-type") {
+        if let Some(rehearsal) = kind.text_of("rehearsal") {
+            bar.section = Some(rehearsal.to_string());
+        }
+        bar.segno |= kind.child("segno").is_some();
+        bar.coda |= kind.child("coda").is_some();
+        if let Some(words) = kind.text_of("words") {
+            let words = words.to_lowercase().replace(['.', ' '], "");
+            let jump = match words.as_str() {
+                "dc" => Some(Jump::DaCapo),
+                "dcalcoda" => Some(Jump::DaCapoAlCoda),
+                "dcalfine" => Some(Jump::DaCapoAlFine),
+                "ds" => Some(Jump::DalSegno),
+                "dsalcoda" => Some(Jump::DalSegnoAlCoda),
+                "dsalfine" => Some(Jump::DalSegnoAlFine),
+                _ => None,
+            };
+            bar.jump = bar.jump.or(jump);
+            bar.fine |= words == "fine";
+        }
+    }
+    if let Some(sound) = direction.child("sound") {
+        read_sound(sound, song);
+    }
+}
+
+fn read_sound(sound: &Element, song: &mut Song) {
+    // A tempo the metronome cannot play is left out, a later one may do
+    if let Some(tempo) = sound.attribute("tempo").and_then(|t| t.parse().ok()) {
+        song.bpm = song.bpm.or(read_bpm(tempo));
+    }
+}
+
+/// Read the chord symbols of a MusicXML score, `.musicxml` or compressed `.mxl`, with its
+/// measures, repeats and endings. The first part with chord symbols is used.
+pub fn parse_musicxml(bytes: &[u8]) -> Result<Song> {
+    let xml = match bytes.starts_with(b"PK") {
+        true => read_compressed(bytes)?,
+        false => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    let score = read_tree(&xml)?;
+    if score.name != "score-partwise" {
+        bail!(
+            "Only part-wise MusicXML scores can be read, not {}",
+            score.name
+        );
+    }
+    let part = score
+        .children("part")
+        .find(|part| part.find("harmony").is_some())
+        .context("No chord symbols found")?;
+
+    let mut song = Song {
+        title: score
+            .child("work")
+            .and_then(|work| work.text_of("work-title"))
+            .or(score.text_of("movement-title"))
+            .unwrap_or_default()
+            .to_string(),
+        composer: score
+            .child("identification")
+            .and_then(|id| {
+                id.children("creator")
+                    .find(|c| c.attribute("type") == Some("composer"))
+            })
+            .map(|c| c.text.trim().to_string())
+            .unwrap_or_default(),
+        time_signature: (4, 4),
+        ..Song::default()
+    };
+    let mut divisions = 1.0;
+    let mut time_read = false;
+    let mut previous: Option<String> = None;
+    for measure in part.children("measure") {
+        let mut bar = SongBar::default();
+        // Position in divisions of a quarter note, and the chords with theirs
+        let mut position = 0.0;
+        let mut chords: Vec<(f64, Option<String>)> = Vec::new();
+        for element in &measure.children {
+            let duration = element.number_of("duration").unwrap_or(0.0);
+            match element.name.as_str() {
+                "attributes" => {
+                    divisions = element.number_of("divisions").unwrap_or(divisions);
+                    if let Some(time) = element.child("time").filter(|_| !time_read) {
+                        if let (Some(beats), Some(value)) =
+                            (time.number_of("beats"), time.number_of("beat-type"))
+                        {
+                            song.time_signature = (beats as u8, value as u8);
+                            time_read = true;
+                        }
+                    }
+                    if let Some(key) = element.child("key").filter(|_| song.key.is_empty()) {
+                        let fifths = key.number_of("fifths").unwrap_or(0.0) as i32;
+                        let index = (fifths + 7).clamp(0, 14) as usize;
+                        song.key = match key.text_of("mode") {
+                            Some("minor") => format!("{}m", MINOR_KEYS[index]),
+                            _ => MAJOR_KEYS[index].to_string(),
+                        };
+                    }
+                }
+                "harmony" => {
+                    let offset = element.number_of("offset").unwrap_or(0.0);
+                    chords.push((position + offset, chord_name(element)));
+                }
+                "note" if element.child("grace").is_none() && element.child("chord").is_none() => {
+                    position += duration
+                }
+                "backup" => position -= duration,
+                "forward" => position += duration,
+                "barline" => {
+                    if let Some(repeat) = element.child("repeat") {
+                        match repeat.attribute("direction") {
+                            Some("forward") => bar.repeat_start = true,
+                            _ => {
+                                let times = repeat.attribute("times").and_then(|t| t.parse().ok());
+                                bar.repeat_end = Some(times.unwrap_or(2));
+                            }
+                        }
+                    }
+                    if let Some(ending) = element.child("ending") {
+                        if ending.attribute("type") == Some("start") {
+                            // The first of the passes an ending like `1, 2` is played on
+                            bar.ending = ending
+                                .attribute("number")
+                                .and_then(|n| n.split([',', ' ']).next()?.trim().parse().ok());
+                        }
+                    }
+                    bar.segno |= element.child("segno").is_some();
+                    bar.coda |= element.child("coda").is_some();
+                }
+                "direction" => read_direction(element, &mut bar, &mut song),
+                "sound" => read_sound(element, &mut song),
+                _ => {}
+            }
+        }
+
+        // Chords are placed on the nearest beat, the last one on a beat wins
+        let (beats, value) = song.time_signature;
+        let beats = beats.max(1);
+        let beat_length = divisions * 4.0 / value.max(1) as f64;
+        let mut slots: Vec<Option<Option<String>>> = vec![None; beats as usize];
+        for (at, name) in chords {
+            let beat = ((at / beat_length).round().max(0.0) as usize).min(beats as usize - 1);
+            slots[beat] = Some(name);
+        }
+        if slots[0].is_none() && slots.iter().any(Option::is_some) {
+            slots[0] = Some(previous.clone());
+        }
+        for (beat, slot) in slots.iter().enumerate() {
+            let Some(name) = slot else {
+                continue;
+            };
+            let next = (beat + 1..slots.len())
+                .find(|&b| slots[b].is_some())
+                .unwrap_or(slots.len());
+            // A chord after `none` is silence, which practice holds the chord before through
+            if let Some(name) = name.clone().or(previous.clone()) {
+                bar.chords.push(SongChord {
+                    name: name.clone(),
+                    beats: (next - beat) as u8,
+                });
+                previous = Some(name);
+            }
+        }
+        song.bars.push(bar);
+    }
+    Ok(song)
+}
+
+/// A note as its step and alter in semitones
+type Note = (char, i32);
+
+/// Split a chord symbol into root, suffix and bass
+fn split_chord(name: &str) -> Option<(Note, &str, Option<Note>)> {
+    let note = |text: &str| -> Option<(char, i32, usize)> {
+        let step = text.chars().next().filter(|c| ('A'..='G').contains(c))?;
+        match text[1..].chars().next() {
+            Some('#') => Some((step, 1, 2)),
+            Some('b') => Some((step, -1, 2)),
+            _ => Some((step, 0, 1)),
+        }
+    };
+    let (chord, bass) = match name.rsplit_once('/') {
+        Some((chord, bass)) if note(bass).is_some_and(|(_, _, len)| len == bass.len()) => {
+            (chord, note(bass).map(|(step, alter, _)| (step, alter)))
+        }
+        _ => (name, None),
+    };
+    let (step, alter, len) = note(chord)?;
+    Some(((step, alter), &chord[len..], bass))
+}
+
+/// Number of fifths of a key like `Eb` or `F#m`
+fn fifths(key: &str) -> i32 {
+    let (keys, root) = match key.strip_suffix('m').or(key.strip_suffix('-')) {
+        Some(root) => (MINOR_KEYS, root),
+        None => (MAJOR_KEYS, key),
+    };
+    keys.iter()
+        .position(|k| *k == root)
+        .map_or(0, |i| i as i32 - 7)
+}
+
+fn harmony_xml(name: &str) -> String {
+    let Some(((step, alter), suffix, bass)) = split_chord(name) else {
+        return String::new();
+    };
+    let alter_xml = |tag: &str, alter: i32| match alter {
+        0 => String::new(),
+        alter => format!("<{tag}>{alter}</{tag}>"),
+    };
+    // A suffix without a kind of its own keeps its symbol as the text
+    let kind = KINDS
+        .iter()
+        .find(|(_, s)| *s == suffix)
+        .map_or("other", |(kind, _)| kind);
+    let mut xml = format!(
+        "<harmony><root><root-step>{}</root-step>{}</root><kind text=\"{}\">{}</kind>",
+        step,
+        alter_xml("root-alter", alter),
+        escape(suffix),
+        kind
+    );
+    if let Some((step, alter)) = bass {
+        xml.push_str(&format!(
+            "<bass><bass-step>{}</bass-step>{}</bass>",
+            step,
+            alter_xml("bass-alter", alter)
+        ));
+    }
+    xml.push_str("</harmony>");
+    xml
+}
+
+/// Write a song as a MusicXML lead sheet: chord symbols over slashes, one per beat
+pub fn write_musicxml(song: &Song) -> String {
+    let (beats, value) = match song.time_signature {
+        (0, _) | (_, 0) => (4, 4),
+        signature => signature,
+    };
+    // Divisions of a quarter that make a beat a whole number of them
+    let divisions = (value as u32 / 4).max(1);
+    let beat_duration = divisions * 4 / value as u32;
+    let note_type = match value {
+        1 => "whole",
+        2 => "half",
+        8 => "eighth",
+        16 => "16th",
+        _ => "quarter",
+    };
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \
+         \"http://www.musicxml.org/dtds/partwise.dtd\">\n\
+         <score-partwise version=\"4.0\">\n",
+    );
+    if !song.title.is_empty() {
+        out.push_str(&format!(
+            "<work><work-title>{}</work-title></work>\n",
+            escape(&song.title)
+        ));
+    }
+    if !song.composer.is_empty() {
+        out.push_str(&format!(
+            "<identification><creator type=\"composer\">{}</creator></identification>\n",
+            escape(&song.composer)
+        ));
+    }
+    out.push_str(
+        "<part-list><score-part id=\"P1\"><part-name>Chords</part-name></score-part></part-list>\n\
+         <part id=\"P1\">\n",
+    );
+
+    for (i, bar) in song.bars.iter().enumerate() {
+        out.push_str(&format!("<measure number=\"{}\">\n", i + 1));
+        if bar.repeat_start || bar.ending.is_some() {
+            out.push_str("<barline location=\"left\">");
+            if let Some(ending) = bar.ending {
+                out.push_str(&format!("<ending number=\"{}\" type=\"start\"/>", ending));
+            }
+            if bar.repeat_start {
+                out.push_str("<repeat direction=\"forward\"/>");
+            }
+            out.push_str("</barline>\n");
+        }
+        if i == 0 {
+            let mode = match song.key.ends_with('m') || song.key.ends_with('-') {
+                true => "minor",
+                false => "major",
+            };
+            out.push_str(&format!(
+                "<attributes><divisions>{}</divisions>\
+                 <key><fifths>{}</fifths><mode>{}</mode></key>\
+                 <time><beats>{}</beats><beat-type>{}</beat-type></time>\
+                 <clef><sign>G</sign><line>2</line></clef>\
+                 <measure-style><slash type=\"start\" use-stems=\"no\"/></measure-style>\
+                 </attributes>\n",
+                divisions,
+                fifths(&song.key),
+                mode,
+                beats,
+                value
+            ));
+            if let Some(bpm) = song.bpm {
+                out.push_str(&format!(
+                    "<direction placement=\"above\"><direction-type><metronome>\
+                     <beat-unit>quarter</beat-unit><per-minute>{}</per-minute>\
+                     </metronome></direction-type><sound tempo=\"{}\"/></direction>\n",
+                    bpm, bpm
+                ));
+            }
+        }
+        let mut direction = |content: String| {
+            out.push_str(&format!(
+                "<direction placement=\"above\"><direction-type>{}</direction-type></direction>\n",
+                content
+            ));
+        };
+        if let Some(section) = &bar.section {
+            direction(format!("<rehearsal>{}</rehearsal>", escape(section)));
+        }
+        if bar.segno {
+            direction("<segno/>".to_string());
+        }
+        if bar.coda {
+            direction("<coda/>".to_string());
+        }
+
+        // A slash per beat, chord symbols standing over the beat they start on
+        let mut symbols = bar.chords.iter().flat_map(|chord| {
+            std::iter::once(Some(chord.name.as_str())).chain(std::iter::repeat_n(
+                None,
+                chord.beats.saturating_sub(1) as usize,
+            ))
+        });
+        for _ in 0..beats {
+            if let Some(name) = symbols.next().flatten() {
+                out.push_str(&harmony_xml(name));
+                out.push('\n');
+            }
+            out.push_str(&format!(
+                "<note><pitch><step>B</step><octave>4</octave></pitch>\
+                 <duration>{}</duration><type>{}</type><stem>none</stem>\
+                 <notehead>slash</notehead></note>\n",
+                beat_duration, note_type
+            ));
+        }
+
+        let words = match bar.jump {
+            Some(Jump::DaCapo) => Some("D.C."),
+            Some(Jump::DaCapoAlCoda) => Some("D.C. al Coda"),
+            Some(Jump::DaCapoAlFine) => Some("D.C. al Fine"),
+            Some(Jump::DalSegno) => Some("D.S."),
+            Some(Jump::DalSegnoAlCoda) => Some("D.S. al Coda"),
+            Some(Jump::DalSegnoAlFine) => Some("D.S. al Fine"),
+            None if bar.fine => Some("Fine"),
+            None => None,
+        };
+        if let Some(words) = words {
+            out.push_str(&format!(
+                "<direction placement=\"below\"><direction-type><words>{}</words>\
+                 </direction-type></direction>\n",
+                words
+            ));
+        }
+
+        // An ending stops at the repeat it closes, a last ending right away
+        let ending_stop = (0..=i).rev().find_map(|start| {
+            let number = song.bars[start].ending?;
+            let closes = song.bars[start..=i].iter().enumerate().all(|(j, b)| {
+                let later = j > 0 && (b.ending.is_some() || b.repeat_start);
+                !later && (start + j == i || b.repeat_end.is_none())
+            });
+            let stop = match bar.repeat_end {
+                Some(_) => "stop",
+                None if start == i && song.bars[i..].iter().all(|b| b.repeat_end.is_none()) => {
+                    "discontinue"
+                }
+                None => return None,
+            };
+            closes.then_some((number, stop))
+        });
+        if bar.repeat_end.is_some() || ending_stop.is_some() {
+            out.push_str("<barline location=\"right\"><bar-style>light-heavy</bar-style>");
+            if let Some((number, stop)) = ending_stop {
+                out.push_str(&format!(
+                    "<ending number=\"{}\" type=\"{}\"/>",
+                    number, stop
+                ));
+            }
+            match bar.repeat_end {
+                Some(2) => out.push_str("<repeat direction=\"backward\"/>"),
+                Some(times) => out.push_str(&format!(
+                    "<repeat direction=\"backward\" times=\"{}\"/>",
+                    times
+                )),
+                None => {}
+            }
+            out.push_str("</barline>\n");
+        }
+        out.push_str("</measure>\n");
+    }
+    out.push_str("</part>\n</score-partwise>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn chords(song: &Song) -> Vec<Vec<(&str, u8)>> {
+        song.bars
+            .iter()
+            .map(|bar| {
+                bar.chords
+                    .iter()
+                    .map(|c| (c.name.as_str(), c.beats))
+                    .collect()
+            })
+            .collect()
+    }
+
+    const SCORE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <work><work-title>Blues &amp; Greens</work-title></work>
+  <part-list><score-part id="P1"><part-name>Lead</part-name></score-part></part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes><divisions>2</divisions><key><fifths>-1</fifths></key>
+        <time><beats>4</beats><beat-type>4</beat-type></time></attributes>
+      <direction><direction-type><rehearsal>A</rehearsal></direction-type><sound tempo="132"/></direction>
+      <harmony><root><root-step>F</root-step></root><kind text="7">dominant</kind></harmony>
+      <note><pitch><step>F</step><octave>4</octave></pitch><duration>4</duration></note>
+      <harmony><root><root-step>B</root-step><root-alter>-1</root-alter></root>
+        <kind>dominant</kind><degree><degree-value>9</degree-value><degree-alter>-1</degree-alter>
+        <degree-type>add</degree-type></degree></harmony>
+      <note><pitch><step>A</step><octave>4</octave></pitch><duration>4</duration></note>
+    </measure>
+    <measure number="2">
+      <barline location="left"><repeat direction="forward"/></barline>
+      <note><rest/><duration>2</duration></note>
+      <harmony><root><root-step>C</root-step></root><kind>minor-seventh</kind>
+        <bass><bass-step>G</bass-step></bass></harmony>
+      <note><pitch><step>C</step><octave>5</octave></pitch><duration>6</duration></note>
+      <barline location="right"><repeat direction="backward" times="3"/></barline>
+    </measure>
+  </part>
+</score-partwise>"#;
+
+    #[test]
+    fn test_harmonies_in_measures() {
+        let song = parse_musicxml(SCORE.as_bytes()).unwrap();
+        assert_eq!(song.title, "Blues & Greens");
+        assert_eq!((song.key.as_str(), song.bpm), ("F", Some(132)));
+        let silent = SCORE.replace("tempo=\"132\"", "tempo=\"0\"");
+        assert_eq!(parse_musicxml(silent.as_bytes()).unwrap().bpm, None);
+        assert_eq!(
+            chords(&song),
+            [
+                vec![("F7", 2), ("Bb7b9", 2)],
+                vec![("Bb7b9", 1), ("Cm7/G", 3)],
+            ]
+        );
+        assert_eq!(song.bars[0].section.as_deref(), Some("A"));
+        assert!(song.bars[1].repeat_start);
+        assert_eq!(song.bars[1].repeat_end, Some(3));
+
+        // The compressed file names its score in the container
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("META-INF/container.xml", options).unwrap();
+        zip.write_all(
+            br#"<container><rootfiles><rootfile full-path="score.xml"/></rootfiles></container>"#,
+        )
+        .unwrap();
+        zip.start_file("score.xml", options).unwrap();
+        zip.write_all(SCORE.as_bytes()).unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+        assert_eq!(parse_musicxml(&bytes).unwrap(), song);
+    }
+
+    #[test]
+    fn test_written_lead_sheet_reads_back() {
+        let mut song = parse_musicxml(SCORE.as_bytes()).unwrap();
+        song.bars[0].ending = Some(1);
+        song.bars[1].chords[1].name = "C7#9/Bb".to_string();
+        song.composer = "Someone".to_string();
+        let written = write_musicxml(&song);
+        assert!(written.contains("<notehead>slash</notehead>"));
+        assert_eq!(parse_musicxml(written.as_bytes()).unwrap(), song);
+    }
+}
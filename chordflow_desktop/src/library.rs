@@ -0,0 +1,253 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    import::lead_sheet::{parse_lead_sheet, write_lead_sheet},
+    state::song::Song,
+    INITIAL_BPM,
+};
+
+const RECENT_FILE: &str = "recent_songs.toml";
+/// Songs kept in the recently played list
+const RECENT_COUNT: usize = 10;
+
+/// A progression saved in the library with what it is practised with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibrarySong {
+    pub name: String,
+    pub key: String,
+    pub bpm: u16,
+    pub beats_per_bar: u8,
+    pub tags: Vec<String>,
+    pub notes: String,
+    /// The chords as a lead sheet, `| Dm7 G7 | Cmaj7 | % |`, which keeps the bars of each
+    /// chord and the sections and repeats of the song
+    pub chart: String,
+}
+
+impl Default for LibrarySong {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            key: String::new(),
+            bpm: INITIAL_BPM,
+            beats_per_bar: 4,
+            tags: Vec::new(),
+            notes: String::new(),
+            chart: String::new(),
+        }
+    }
+}
+
+impl LibrarySong {
+    pub fn new(name: &str, song: &Song, bpm: u16, beats_per_bar: u8) -> Self {
+        Self {
+            name: name.trim().to_string(),
+            key: song.key.clone(),
+            bpm,
+            beats_per_bar,
+            chart: write_lead_sheet(song),
+            ..Self::default()
+        }
+    }
+
+    /// The song of the chart, named and with the key and tempo it was saved with
+    pub fn song(&self) -> Result<Song> {
+        let mut song = parse_lead_sheet(&self.chart, self.beats_per_bar)
+            .with_context(|| format!("The chart of {} is unreadable", self.name))?;
+        song.title = self.name.clone();
+        song.key = self.key.clone();
+        song.bpm = Some(self.bpm);
+        Ok(song)
+    }
+
+    /// Whether the name, key, a tag or the notes contain `query`, ignoring case
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        [&self.name, &self.key, &self.notes]
+            .into_iter()
+            .chain(&self.tags)
+            .any(|text| text.to_lowercase().contains(&query))
+    }
+
+    /// A file for the song, named after it. The `number` tells apart songs whose names
+    /// only differ in characters a file name cannot hold, or in case.
+    fn file_name(name: &str, number: usize) -> String {
+        let name: String = name
+            .trim()
+            .chars()
+            .map(|c| match c.is_alphanumeric() || c == ' ' || c == '-' {
+                true => c,
+                false => '_',
+            })
+            .collect();
+        match number {
+            1 => format!("{}.toml", name),
+            number => format!("{} ({}).toml", name, number),
+        }
+    }
+}
+
+fn library_dir() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("chordflow").join("library"))
+        .context("No data directory")
+}
+
+fn read_song(path: &Path) -> Result<LibrarySong> {
+    Ok(toml::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn song_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "toml"))
+}
+
+/// The file the song called `name` is saved in
+fn saved_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    song_files(dir).find(|path| read_song(path).is_ok_and(|song| song.name == name))
+}
+
+/// All songs in the library by name. Files that cannot be read are skipped.
+pub fn load_library() -> Vec<LibrarySong> {
+    library_dir().map(|dir| load_from(&dir)).unwrap_or_default()
+}
+
+fn load_from(dir: &Path) -> Vec<LibrarySong> {
+    let mut songs: Vec<LibrarySong> = song_files(dir)
+        .filter_map(|path| match read_song(&path) {
+            Ok(song) => Some(song),
+            Err(e) => {
+                log::warn!("Skipping unreadable song {:?}: {}", path, e);
+                None
+            }
+        })
+        .collect();
+    songs.sort_by_key(|song| (song.name.to_lowercase(), song.name.clone()));
+    songs
+}
+
+/// Save `song` in the library, replacing a song of the same name
+pub fn save_song(song: &LibrarySong) -> Result<()> {
+    save_in(&library_dir()?, song)
+}
+
+fn save_in(dir: &Path, song: &LibrarySong) -> Result<()> {
+    if song.name.trim().is_empty() {
+        anyhow::bail!("The song needs a name");
+    }
+    fs::create_dir_all(dir)?;
+    // Another song may already have the file named after this one
+    let path = saved_path(dir, &song.name).unwrap_or_else(|| {
+        (1..)
+            .map(|number| dir.join(LibrarySong::file_name(&song.name, number)))
+            .find(|path| !path.exists())
+            .unwrap_or_default()
+    });
+    fs::write(path, toml::to_string(song)?)?;
+    Ok(())
+}
+
+pub fn delete_song(name: &str) -> Result<()> {
+    delete_in(&library_dir()?, name)
+}
+
+fn delete_in(dir: &Path, name: &str) -> Result<()> {
+    let path = saved_path(dir, name).with_context(|| format!("{} is not saved", name))?;
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Names of the library songs played last, the latest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentSongs {
+    pub names: Vec<String>,
+}
+
+impl RecentSongs {
+    pub fn load() -> Self {
+        config::load(RECENT_FILE)
+    }
+
+    /// Put `name` at the top of the list and save it
+    pub fn played(&mut self, name: &str) {
+        self.names.retain(|n| n != name);
+        self.names.insert(0, name.to_string());
+        self.names.truncate(RECENT_COUNT);
+        if let Err(e) = config::save(RECENT_FILE, self) {
+            log::warn!("Could not save the recently played songs: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_song_file() {
+        let song = LibrarySong {
+            name: "Autumn Leaves".to_string(),
+            key: "Gm".to_string(),
+            bpm: 120,
+            tags: vec!["standard".to_string()],
+            chart: "[A] | Cm7 | F7 | Bbmaj7 | Ebmaj7 |\n[B] | Am7b5 D7:2 | Gm6 |".to_string(),
+            ..LibrarySong::default()
+        };
+        let text = toml::to_string(&song).unwrap();
+        assert_eq!(toml::from_str::<LibrarySong>(&text).unwrap(), song);
+        assert!(song.matches("STANDARD") && song.matches("autumn") && !song.matches("blues"));
+
+        let chart = song.song().unwrap();
+        assert_eq!(
+            (chart.title.as_str(), chart.bpm),
+            ("Autumn Leaves", Some(120))
+        );
+        assert_eq!(chart.bars.len(), 6);
+        assert_eq!(LibrarySong::file_name("A/B: take 2", 1), "A_B_ take 2.toml");
+    }
+
+    #[test]
+    fn test_names_sharing_a_file_name() {
+        let dir = std::env::temp_dir().join(format!("chordflow-library-{}", std::process::id()));
+        let song = |name: &str, bpm| LibrarySong {
+            name: name.to_string(),
+            bpm,
+            ..LibrarySong::default()
+        };
+        let names = |dir: &Path| -> Vec<(String, u16)> {
+            load_from(dir)
+                .into_iter()
+                .map(|s| (s.name, s.bpm))
+                .collect()
+        };
+        for name in ["A/B", "A?B", "A_B", "Blues", "blues"] {
+            save_in(&dir, &song(name, 100)).unwrap();
+        }
+        save_in(&dir, &song("A?B", 140)).unwrap();
+        assert_eq!(names(&dir).len(), 5);
+        assert!(names(&dir).contains(&("A?B".to_string(), 140)));
+
+        delete_in(&dir, "A/B").unwrap();
+        assert!(delete_in(&dir, "A/B").is_err());
+        save_in(&dir, &song("A?B", 160)).unwrap();
+        let left: Vec<(String, u16)> = names(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            left,
+            [("A?B", 160), ("A_B", 100), ("Blues", 100), ("blues", 100)]
+                .map(|(name, bpm)| (name.to_string(), bpm))
+        );
+    }
+}
@@ -5,10 +5,31 @@ use dioxus::desktop::{
     tao::platform::macos::WindowBuilderExtMacOS, Config, LogicalSize, WindowBuilder,
 };
 
-use crate::{audio::stream::init_stream, ui::app::App};
+use crate::{
+    audio::{
+        articulation::ChordArticulation,
+        bass::BassLine,
+        comping::CompingPattern,
+        drums::DrumGroove,
+        gap::GapClick,
+        samples::ClickSound,
+        soundfont::{Instrument, InstrumentPart},
+        source::SoundSource,
+        stream::init_stream,
+        tempo::TempoRamp,
+        timeline::ScheduledChord,
+    },
+    ui::app::App,
+};
 
+mod app_settings;
 mod audio;
 mod components;
+mod config;
+mod import;
+mod library;
+mod practice_log;
+mod presets;
 mod state;
 mod ui;
 
@@ -18,13 +39,40 @@ pub enum AudioCommand {
     Stop,
     Restart,
     SetBPM(u16),
-    SetBarsPerCycle(u8),
     SetSubdivision(u8),
-    SetChord(Option<Vec<u8>>),
+    /// Beats per bar
+    SetTimeSignature(u8),
+    /// Swing in percent of a beat taken by the first eighth, 50 is straight
+    SetSwing(u8),
+    ScheduleChord(ScheduledChord),
+    DropChordsAfter(u64),
+    SetArticulation(ChordArticulation),
+    SetComping(CompingPattern),
+    SetBass(Option<BassLine>),
+    SetDrums(Option<DrumGroove>),
+    /// Replace what the notes are played on, e.g. after loading another SoundFont
+    SetSoundSource(Box<SoundSource>),
+    SetInstrument(InstrumentPart, Instrument),
+    /// Play a sample for a click sound instead of the synthesized click
+    SetClickSample(ClickSound, Option<Box<[f32]>>),
+    SetTempoRamp(Option<TempoRamp>),
+    SetGapClick(Option<GapClick>),
 }
 
+/// Events from the audio thread, stamped with the stream frame they sounded on
 pub enum AudioEvent {
-    Tick,
+    /// A main beat; `bar` is the bar within the current chord and `beat` the beat in that bar
+    Tick { sample: u64, bar: u8, beat: u8 },
+    /// The scheduled chord with this index became the current chord
+    ChordChanged { sample: u64, index: u64 },
+    TempoChanged(u16),
+    Stopped,
+    /// The replaced sound source, handed back to be freed off the audio thread
+    SoundSourceReleased(Box<SoundSource>),
+    /// A replaced click sample, freed off the audio thread
+    ClickSampleReleased(Box<[f32]>),
+    /// The stream was rebuilt with a fresh engine, which needs the settings and chords again
+    StreamRestarted,
 }
 
 pub enum MetronomeEvent {
@@ -33,6 +81,9 @@ pub enum MetronomeEvent {
 }
 
 pub const INITIAL_BPM: u16 = 100;
+/// Tempos the metronome plays
+pub const MIN_BPM: u16 = 20;
+pub const MAX_BPM: u16 = 400;
 
 pub static AUDIO_CMD: LazyLock<(Sender<AudioCommand>, Receiver<AudioCommand>)> =
     LazyLock::new(|| bounded(128));
@@ -51,6 +102,10 @@ fn main() {
     log::info!("Current working directory: {:?}", std::env::current_dir());
     log::info!("Executable path: {:?}", std::env::current_exe());
 
+    // Volumes are read by the audio thread and the settings panel from the start
+    let settings = app_settings::AppSettings::load();
+    settings.volumes.apply();
+    let window = settings.window.at_least_min();
     let window_builder = WindowBuilder::new()
         .with_transparent(false)
         .with_decorations(true)
@@ -60,25 +115,20 @@ fn main() {
         .with_has_shadow(true)
         .with_movable_by_window_background(true)
         .with_inner_size(LogicalSize {
-            height: 910,
-            width: 1000,
+            height: window.height,
+            width: window.width,
         })
         .with_always_on_top(false);
 
     let config = Config::default().with_window(window_builder);
 
     log::info!("Initializing audio system...");
-    // Initialize and leak the audio stream to keep it alive for the application lifetime
-    let stream = match init_stream() {
-        Ok(s) => s,
-        Err(e) => {
-            log::error!("Failed to initialize audio stream: {}", e);
-            show_error_dialog(&format!("Failed to initialize audio system:\n\n{}", e));
-            std::process::exit(1);
-        }
-    };
-    log::info!("Audio stream created, leaking to keep alive...");
-    Box::leak(Box::new(stream));
+    // The audio thread keeps the stream alive and rebuilds it when the output changes
+    if let Err(e) = init_stream() {
+        log::error!("Failed to initialize audio stream: {}", e);
+        show_error_dialog(&format!("Failed to initialize audio system:\n\n{}", e));
+        std::process::exit(1);
+    }
 
     log::info!("Launching Dioxus application...");
     dioxus::LaunchBuilder::new().with_cfg(config).launch(App)
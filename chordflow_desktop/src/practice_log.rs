@@ -0,0 +1,369 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+use crate::state::modes::ModeOption;
+
+const LOG_FILE: &str = "practice_log.jsonl";
+/// The session being played, kept until it is finished and logged
+const UNFINISHED_FILE: &str = "practice_session.json";
+
+/// One stretch of playing in a single mode and preset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PracticeSession {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub mode: ModeOption,
+    /// The preset that was applied, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Bars played per key
+    pub keys: BTreeMap<String, u32>,
+    /// Bars played per chord quality
+    pub qualities: BTreeMap<String, u32>,
+    pub min_bpm: u16,
+    pub max_bpm: u16,
+    pub bars: u32,
+}
+
+impl PracticeSession {
+    pub fn duration(&self) -> Duration {
+        (self.end - self.start).to_std().unwrap_or_default()
+    }
+}
+
+/// Split a chord name like `F♯-7` or `Bbmaj7/D` into its root and quality
+pub fn split_chord_name(name: &str) -> (&str, &str) {
+    let name = name.split_once('/').map_or(name, |(chord, _)| chord);
+    let mut root_end = name.chars().next().map_or(0, char::len_utf8);
+    for c in name[root_end..].chars() {
+        if !matches!(c, '#' | 'b' | '♯' | '♭') {
+            break;
+        }
+        root_end += c.len_utf8();
+    }
+    name.split_at(root_end)
+}
+
+/// A bar that was played
+pub struct PlayedBar<'a> {
+    pub mode: ModeOption,
+    pub preset: Option<&'a str>,
+    pub key: &'a str,
+    pub quality: &'a str,
+    pub bpm: u16,
+    pub beats: u8,
+}
+
+/// Collects the bars played into a session
+#[derive(Debug, Default)]
+pub struct SessionRecorder {
+    session: Option<PracticeSession>,
+}
+
+impl SessionRecorder {
+    /// Count `bar`, which started at `now`. A session of another mode or preset is
+    /// finished and returned.
+    pub fn bar(&mut self, bar: PlayedBar, now: DateTime<Local>) -> Option<PracticeSession> {
+        let finished = match &self.session {
+            Some(session)
+                if session.mode != bar.mode || session.preset.as_deref() != bar.preset =>
+            {
+                self.finish()
+            }
+            _ => None,
+        };
+        let session = self.session.get_or_insert_with(|| PracticeSession {
+            start: now,
+            end: now,
+            mode: bar.mode,
+            preset: bar.preset.map(str::to_string),
+            keys: BTreeMap::new(),
+            qualities: BTreeMap::new(),
+            min_bpm: bar.bpm,
+            max_bpm: bar.bpm,
+            bars: 0,
+        });
+        let length = bar.beats as i64 * 60_000 / bar.bpm.max(1) as i64;
+        session.end = now + TimeDelta::milliseconds(length);
+        session.bars += 1;
+        *session.keys.entry(bar.key.to_string()).or_default() += 1;
+        let quality = match bar.quality {
+            "" => "Major",
+            quality => quality,
+        };
+        *session.qualities.entry(quality.to_string()).or_default() += 1;
+        session.min_bpm = session.min_bpm.min(bar.bpm);
+        session.max_bpm = session.max_bpm.max(bar.bpm);
+        finished
+    }
+
+    /// The session being recorded, if any
+    pub fn current(&self) -> Option<&PracticeSession> {
+        self.session.as_ref()
+    }
+
+    /// End the session being recorded, if any
+    pub fn finish(&mut self) -> Option<PracticeSession> {
+        self.session.take()
+    }
+}
+
+fn data_path(file: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("chordflow").join(file))
+}
+
+/// Add `session` to the log, a line of JSON each
+pub fn append_session(session: &PracticeSession) -> Result<()> {
+    let path = data_path(LOG_FILE).context("No data directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(session)?)?;
+    // The unfinished copy of it is not needed anymore
+    if let Some(unfinished) = data_path(UNFINISHED_FILE) {
+        let _ = fs::remove_file(unfinished);
+    }
+    Ok(())
+}
+
+/// Keep the session still being played, so it is not lost if the app does not get to log it
+pub fn save_unfinished(session: &PracticeSession) -> Result<()> {
+    let path = data_path(UNFINISHED_FILE).context("No data directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(session)?)?;
+    Ok(())
+}
+
+/// Log the session that was still being played when the app last quit
+pub fn recover_unfinished() -> Result<()> {
+    let path = data_path(UNFINISHED_FILE).context("No data directory")?;
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    let session = serde_json::from_str(&text);
+    fs::remove_file(&path)?;
+    append_session(&session?)
+}
+
+/// Every logged session, oldest first. Lines that cannot be read are skipped.
+pub fn load_sessions() -> Vec<PracticeSession> {
+    let Some(text) = data_path(LOG_FILE).and_then(|path| fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    let mut sessions: Vec<PracticeSession> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                log::warn!("Skipping an unreadable practice session: {}", e);
+                None
+            }
+        })
+        .collect();
+    sessions.sort_by_key(|session| session.start);
+    sessions
+}
+
+/// Time played per mode, the most first
+pub fn time_per_mode(sessions: &[PracticeSession]) -> Vec<(ModeOption, Duration)> {
+    let mut times: Vec<(ModeOption, Duration)> = Vec::new();
+    for session in sessions {
+        match times.iter_mut().find(|(mode, _)| *mode == session.mode) {
+            Some((_, time)) => *time += session.duration(),
+            None => times.push((session.mode, session.duration())),
+        }
+    }
+    times.sort_by_key(|(_, time)| Reverse(*time));
+    times
+}
+
+/// Time played per key, each session's time shared by the bars in a key, the most first
+pub fn time_per_key(sessions: &[PracticeSession]) -> Vec<(String, Duration)> {
+    let mut times: BTreeMap<&str, Duration> = BTreeMap::new();
+    for session in sessions.iter().filter(|s| s.bars > 0) {
+        for (key, bars) in &session.keys {
+            *times.entry(key).or_default() += session.duration() * *bars / session.bars;
+        }
+    }
+    let mut times: Vec<(String, Duration)> = times
+        .into_iter()
+        .map(|(key, time)| (key.to_string(), time))
+        .collect();
+    times.sort_by_key(|(_, time)| Reverse(*time));
+    times
+}
+
+/// Fastest tempo per day for every preset
+pub fn tempo_per_preset(sessions: &[PracticeSession]) -> BTreeMap<String, Vec<(NaiveDate, u16)>> {
+    let mut tempos: BTreeMap<String, BTreeMap<NaiveDate, u16>> = BTreeMap::new();
+    for session in sessions {
+        if let Some(preset) = &session.preset {
+            let day = tempos
+                .entry(preset.clone())
+                .or_default()
+                .entry(session.start.date_naive())
+                .or_default();
+            *day = (*day).max(session.max_bpm);
+        }
+    }
+    tempos
+        .into_iter()
+        .map(|(preset, days)| (preset, days.into_iter().collect()))
+        .collect()
+}
+
+/// Minutes played per day
+pub fn minutes_per_day(sessions: &[PracticeSession]) -> BTreeMap<NaiveDate, u32> {
+    let mut minutes: BTreeMap<NaiveDate, Duration> = BTreeMap::new();
+    for session in sessions {
+        *minutes.entry(session.start.date_naive()).or_default() += session.duration();
+    }
+    minutes
+        .into_iter()
+        .map(|(day, time)| (day, (time.as_secs() / 60) as u32))
+        .collect()
+}
+
+/// Days in a row with practice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Streaks {
+    /// Up to today, or yesterday when today was not played yet
+    pub current: u32,
+    pub longest: u32,
+}
+
+pub fn streaks(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> Streaks {
+    let mut streaks = Streaks::default();
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in days {
+        run = match previous {
+            Some(previous) if previous.succ_opt() == Some(day) => run + 1,
+            _ => 1,
+        };
+        streaks.longest = streaks.longest.max(run);
+        previous = Some(day);
+    }
+    let yesterday = today.pred_opt();
+    if previous.is_some_and(|last| last == today || Some(last) == yesterday) {
+        streaks.current = run;
+    }
+    streaks
+}
+
+/// The log as CSV, a session per row
+pub fn to_csv(sessions: &[PracticeSession]) -> String {
+    let quote = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+    let counts = |counts: &BTreeMap<String, u32>| {
+        counts
+            .iter()
+            .map(|(name, bars)| format!("{} {}", name, bars))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    let mut csv =
+        String::from("start,end,minutes,mode,preset,keys,qualities,min_bpm,max_bpm,bars\n");
+    for session in sessions {
+        csv.push_str(&format!(
+            "{},{},{:.1},{},{},{},{},{},{},{}\n",
+            session.start.to_rfc3339(),
+            session.end.to_rfc3339(),
+            session.duration().as_secs_f64() / 60.0,
+            quote(session.mode.as_ref()),
+            quote(session.preset.as_deref().unwrap_or_default()),
+            quote(&counts(&session.keys)),
+            quote(&counts(&session.qualities)),
+            session.min_bpm,
+            session.max_bpm,
+            session.bars
+        ));
+    }
+    csv
+}
+
+pub fn to_json(sessions: &[PracticeSession]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(sessions)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn bar<'a>(mode: ModeOption, key: &'a str, bpm: u16) -> PlayedBar<'a> {
+        PlayedBar {
+            mode,
+            preset: None,
+            key,
+            quality: "-7",
+            bpm,
+            beats: 4,
+        }
+    }
+
+    #[test]
+    fn test_sessions_recorded() {
+        let start = Local.with_ymd_and_hms(2026, 3, 2, 18, 0, 0).unwrap();
+        let mut recorder = SessionRecorder::default();
+        assert!(recorder
+            .bar(bar(ModeOption::Fourths, "C", 60), start)
+            .is_none());
+        let later = start + TimeDelta::seconds(4);
+        assert!(recorder
+            .bar(bar(ModeOption::Fourths, "F", 90), later)
+            .is_none());
+
+        // Another mode starts another session
+        let next = later + TimeDelta::seconds(4);
+        let fourths = recorder
+            .bar(bar(ModeOption::Diatonic, "B♭", 90), next)
+            .unwrap();
+        assert_eq!(
+            (fourths.bars, fourths.min_bpm, fourths.max_bpm),
+            (2, 60, 90)
+        );
+        assert_eq!(
+            fourths.duration(),
+            Duration::from_secs(6) + Duration::from_millis(666)
+        );
+        assert_eq!(fourths.qualities.get("-7"), Some(&2));
+        let diatonic = recorder.finish().unwrap();
+        assert!(recorder.finish().is_none());
+
+        let sessions = [fourths, diatonic];
+        assert_eq!(time_per_mode(&sessions)[0].0, ModeOption::Fourths);
+        assert_eq!(time_per_key(&sessions).len(), 3);
+        assert_eq!(to_csv(&sessions).lines().count(), 3);
+        assert_eq!(split_chord_name("F♯-7/A"), ("F♯", "-7"));
+        assert_eq!(split_chord_name("Bbmaj7"), ("Bb", "maj7"));
+    }
+
+    #[test]
+    fn test_streaks() {
+        let day = |d| NaiveDate::from_ymd_opt(2026, 3, d).unwrap();
+        let days: BTreeSet<NaiveDate> = [1, 2, 3, 6, 7].map(day).into();
+        assert_eq!(
+            streaks(&days, day(8)),
+            Streaks {
+                current: 2,
+                longest: 3
+            }
+        );
+        assert_eq!(streaks(&days, day(9)).current, 0);
+    }
+}
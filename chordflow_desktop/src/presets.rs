@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{app_settings::AppSettings, config, state::modes::ModeOption};
+
+const PRESETS_FILE: &str = "presets.toml";
+
+/// A complete setup: mode and its options, tempo, meter, volumes and accompaniment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Preset {
+    pub name: String,
+    pub settings: AppSettings,
+}
+
+impl Preset {
+    /// A preset of `settings`. The custom progression is only kept when it is practised.
+    pub fn new(name: &str, mut settings: AppSettings) -> Self {
+        if settings.mode != ModeOption::Custom {
+            settings.progression = None;
+        }
+        Self {
+            name: name.trim().to_string(),
+            settings,
+        }
+    }
+
+    /// What the preset plays, like `Circle of Fourths · 80 bpm · 4/4`
+    pub fn summary(&self) -> String {
+        let settings = &self.settings;
+        let mode = match (&settings.mode, &settings.progression) {
+            (ModeOption::Fourths, _) => format!("{} {}", settings.mode, settings.fourths_quality),
+            (ModeOption::Diatonic, _) => format!("{} in {}", settings.mode, settings.diatonic_root),
+            (ModeOption::Custom, Some(song)) if !song.name.is_empty() => song.name.clone(),
+            _ => settings.mode.to_string(),
+        };
+        format!(
+            "{} · {} bpm · {}/4",
+            mode, settings.bpm, settings.beats_per_bar
+        )
+    }
+}
+
+/// A preset practised for a number of minutes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoutineStep {
+    pub preset: String,
+    pub minutes: u32,
+}
+
+impl RoutineStep {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.minutes.max(1) as u64 * 60)
+    }
+}
+
+/// Presets played one after the other, like a daily warm-up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Routine {
+    pub name: String,
+    pub steps: Vec<RoutineStep>,
+}
+
+/// The saved presets and routines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Presets {
+    pub presets: Vec<Preset>,
+    pub routines: Vec<Routine>,
+}
+
+impl Presets {
+    pub fn load() -> Self {
+        config::load(PRESETS_FILE)
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = config::save(PRESETS_FILE, self) {
+            log::warn!("Could not save the presets: {}", e);
+        }
+    }
+
+    pub fn preset(&self, name: &str) -> Option<&Preset> {
+        self.presets.iter().find(|preset| preset.name == name)
+    }
+
+    /// Add `preset`, replacing one of the same name
+    pub fn set_preset(&mut self, preset: Preset) {
+        match self.presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+    }
+
+    /// Add `routine`, replacing one of the same name
+    pub fn set_routine(&mut self, routine: Routine) {
+        match self.routines.iter_mut().find(|r| r.name == routine.name) {
+            Some(existing) => *existing = routine,
+            None => self.routines.push(routine),
+        }
+    }
+}
+
+/// A routine being played
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutineRun {
+    pub routine: Routine,
+    pub step: usize,
+    /// Time played in the current step; a pause does not count
+    pub elapsed: Duration,
+}
+
+impl RoutineRun {
+    /// Play `routine` from its first step, if it has any
+    pub fn new(routine: Routine) -> Option<Self> {
+        (!routine.steps.is_empty()).then_some(Self {
+            routine,
+            step: 0,
+            elapsed: Duration::ZERO,
+        })
+    }
+
+    pub fn current(&self) -> &RoutineStep {
+        &self.routine.steps[self.step]
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.current().duration().saturating_sub(self.elapsed)
+    }
+
+    /// Count `played` time towards the current step. True when the step is over.
+    pub fn advance(&mut self, played: Duration) -> bool {
+        self.elapsed += played;
+        self.remaining().is_zero()
+    }
+
+    /// Go on with the next step. False when the routine is done.
+    pub fn next_step(&mut self) -> bool {
+        if self.step + 1 >= self.routine.steps.len() {
+            return false;
+        }
+        self.step += 1;
+        self.elapsed = Duration::ZERO;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routine_run() {
+        let step = |preset: &str, minutes| RoutineStep {
+            preset: preset.to_string(),
+            minutes,
+        };
+        let routine = Routine {
+            name: "Warm-up".to_string(),
+            steps: vec![step("Fourths m7", 5), step("Diatonic Bb", 5)],
+        };
+        assert!(RoutineRun::new(Routine::default()).is_none());
+
+        let mut run = RoutineRun::new(routine).unwrap();
+        assert!(!run.advance(Duration::from_secs(299)));
+        assert_eq!(run.remaining(), Duration::from_secs(1));
+        assert!(run.advance(Duration::from_secs(1)));
+        assert!(run.next_step());
+        assert_eq!(
+            (run.current().preset.as_str(), run.elapsed),
+            ("Diatonic Bb", Duration::ZERO)
+        );
+        assert!(!run.next_step());
+    }
+}
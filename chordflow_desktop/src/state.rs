@@ -2,3 +2,5 @@ pub mod diatonic;
 pub mod fourths;
 pub mod modes;
 pub mod progression;
+pub mod schedule;
+pub mod song;
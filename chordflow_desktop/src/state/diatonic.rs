@@ -7,6 +7,7 @@ use chordflow_music_theory::{
 };
 use rand::{rng, seq::IndexedRandom};
 
+#[derive(Clone)]
 pub struct DiatonicConfig {
     pub scale: Scale,
     pub is_random: bool,
@@ -45,10 +46,6 @@ impl DiatonicConfig {
         self.next_scale_interval = interval;
         self.next_chord = Chord::new(next_note, quality);
     }
-
-    pub fn get_chords(&self) -> (String, String) {
-        (self.current_chord.to_string(), self.next_chord.to_string())
-    }
 }
 
 impl Default for DiatonicConfig {
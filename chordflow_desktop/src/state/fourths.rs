@@ -5,6 +5,7 @@ use chordflow_music_theory::{
     quality::Quality,
 };
 
+#[derive(Clone)]
 pub struct FourthsConfig {
     pub quality: Quality,
     pub current_chord: Chord,
@@ -35,10 +36,6 @@ impl FourthsConfig {
         }
         self.next_chord = Chord::new(next_note, self.quality);
     }
-
-    pub fn get_chords(&self) -> (String, String) {
-        (self.current_chord.to_string(), self.next_chord.to_string())
-    }
 }
 
 impl Default for FourthsConfig {
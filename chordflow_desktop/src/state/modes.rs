@@ -1,7 +1,19 @@
+use serde::{Deserialize, Serialize};
 use strum::{AsRefStr, Display, EnumCount, EnumIter, FromRepr};
 
 #[derive(
-    Clone, Copy, Debug, EnumIter, Display, AsRefStr, PartialEq, EnumCount, FromRepr, Default,
+    Clone,
+    Copy,
+    Debug,
+    EnumIter,
+    Display,
+    AsRefStr,
+    PartialEq,
+    EnumCount,
+    FromRepr,
+    Default,
+    Serialize,
+    Deserialize,
 )]
 pub enum ModeOption {
     #[default]
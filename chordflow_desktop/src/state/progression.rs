@@ -1,31 +1,39 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use chordparser::{chord::Chord, parsing::Parser};
 
+use crate::{
+    import::to_progression,
+    state::{
+        schedule::ChordLength,
+        song::{FormBar, Song},
+    },
+};
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ProgressionConfig {
     pub chords: Vec<ProgressionChord>,
     pub current_chord: Option<Chord>,
     pub next_chord: Option<Chord>,
     pub current_chord_index: usize,
+    /// The chart the chords were imported from, with its sections and repeats
+    pub song: Option<Song>,
+    /// Section of the song practised on its own instead of the whole form
+    pub loop_section: Option<String>,
 }
 
-impl ProgressionConfig {
-    pub fn get_chords(&self) -> (String, String) {
-        (
-            self.current_chord
-                .clone()
-                .map(|c| c.origin)
-                .unwrap_or_default(),
-            self.next_chord
-                .clone()
-                .map(|c| c.origin)
-                .unwrap_or_default(),
-        )
-    }
+/// Where playback is in the form of a song
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormPosition {
+    pub section: Option<String>,
+    /// 1-based bar within the form
+    pub bar: usize,
+    pub bars: usize,
+}
 
-    pub fn get_bars_per_cycle_current(&self) -> u8 {
-        self.chords[self.current_chord_index].bars
+impl ProgressionConfig {
+    pub fn current_length(&self) -> ChordLength {
+        self.chords[self.current_chord_index].length()
     }
 
     pub fn generate_next_chord(&mut self) {
@@ -35,18 +43,89 @@ impl ProgressionConfig {
         self.next_chord = Some(self.chords[next_index].chord.clone());
     }
 
+    /// Continue the progression from the chord at `index`
+    pub fn seek(&mut self, index: usize) {
+        if self.chords.is_empty() {
+            return;
+        }
+        self.current_chord_index = index % self.chords.len();
+        let next_index = (self.current_chord_index + 1) % self.chords.len();
+        self.current_chord = Some(self.chords[self.current_chord_index].chord.clone());
+        self.next_chord = Some(self.chords[next_index].chord.clone());
+    }
+
     pub fn decrements_bars(&mut self, index: usize) {
         if let Some(chord) = self.chords.get_mut(index) {
-            chord.bars = chord.bars.saturating_sub(1);
+            match chord.beats.as_mut() {
+                Some(beats) => *beats = beats.saturating_sub(1).max(1),
+                None => chord.bars = chord.bars.saturating_sub(1),
+            }
         }
     }
 
     pub fn increments_bars(&mut self, index: usize) {
         if let Some(chord) = self.chords.get_mut(index) {
-            chord.bars += 1;
+            match chord.beats.as_mut() {
+                Some(beats) => *beats = beats.saturating_add(1),
+                None => chord.bars = chord.bars.saturating_add(1),
+            }
+        }
+    }
+
+    /// The song's bars as they are practised: the whole form or the looped section
+    pub fn form(&self) -> Vec<FormBar> {
+        match (&self.song, &self.loop_section) {
+            (Some(song), Some(section)) => song.section_form(section),
+            (Some(song), None) => song.form(),
+            (None, _) => Vec::new(),
         }
     }
 
+    /// Practise only `section` of the song, or the whole form again
+    pub fn set_loop_section(&mut self, section: Option<String>) -> Result<()> {
+        let Some(song) = &self.song else {
+            return Ok(());
+        };
+        let form = match &section {
+            Some(section) => song.section_form(section),
+            None => song.form(),
+        };
+        if form.is_empty() {
+            bail!("The song has no section {}", section.unwrap_or_default());
+        }
+        self.chords = to_progression(&song.chords_in(&form))?;
+        self.loop_section = section;
+        Ok(())
+    }
+
+    /// The song the chords come from, unless they were edited since
+    pub fn unedited_song(&self) -> Option<&Song> {
+        self.song
+            .as_ref()
+            .filter(|song| song.matches_in(&self.form(), &self.chords))
+    }
+
+    /// Position in the form at 1-based `bar_in_chord` of the chord at `index`
+    pub fn form_position(&self, index: usize, bar_in_chord: u8) -> Option<FormPosition> {
+        let song = self.unedited_song()?;
+        let beats_per_bar = song.time_signature.0.max(1) as usize;
+        let beats: usize = self.chords[..index.min(self.chords.len())]
+            .iter()
+            .map(|chord| match chord.length() {
+                ChordLength::Beats(beats) => beats as usize,
+                ChordLength::Bars(bars) => bars as usize * beats_per_bar,
+            })
+            .sum();
+        let form = self.form();
+        let bar = (beats / beats_per_bar + bar_in_chord.max(1) as usize - 1)
+            .min(form.len().checked_sub(1)?);
+        Some(FormPosition {
+            section: form[bar].section.clone(),
+            bar: bar + 1,
+            bars: form.len(),
+        })
+    }
+
     pub fn reset(&mut self) {
         if self.chords.is_empty() {
             return;
@@ -67,11 +146,24 @@ impl ProgressionConfig {
 pub struct ProgressionChord {
     pub chord: Chord,
     pub bars: u8,
+    /// Beats the chord lasts instead of whole bars, when it shares a bar
+    pub beats: Option<u8>,
 }
 
 impl ProgressionChord {
     pub fn new(chord: Chord) -> Self {
-        Self { chord, bars: 1 }
+        Self {
+            chord,
+            bars: 1,
+            beats: None,
+        }
+    }
+
+    pub fn length(&self) -> ChordLength {
+        match self.beats {
+            Some(beats) => ChordLength::Beats(beats),
+            None => ChordLength::Bars(self.bars),
+        }
     }
 
     pub fn from_string(str: String) -> Result<Vec<ProgressionChord>> {
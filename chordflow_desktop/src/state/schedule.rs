@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+
+use crate::audio::timeline::ScheduledChord;
+
+/// How long a chord lasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChordLength {
+    Bars(u8),
+    /// Beats, for chords that share a bar
+    Beats(u8),
+}
+
+/// A chord on the audio timeline together with what the UI needs to display it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PracticeChord {
+    pub index: u64,
+    pub start_bar: u64,
+    /// Beat of the start bar the chord starts on, 0 for the downbeat
+    pub start_beat: u8,
+    /// Bars the chord sounds in, counting those it only sounds in partly
+    pub bars: u8,
+    pub name: String,
+    pub midi_notes: Vec<u8>,
+    /// Position in the custom progression this chord came from
+    pub progression_index: Option<usize>,
+    /// Where the next chord starts: bar and beat
+    end: (u64, u8),
+}
+
+impl PracticeChord {
+    pub fn to_scheduled(&self) -> ScheduledChord {
+        ScheduledChord::new(
+            self.index,
+            self.start_bar,
+            self.start_beat,
+            self.bars,
+            &self.midi_notes,
+        )
+    }
+}
+
+/// The chords the practice state has computed ahead of playback.
+///
+/// The first chord is the one currently sounding (or about to, before playback starts),
+/// the rest are already scheduled on the audio thread.
+#[derive(Debug, Default)]
+pub struct PracticeSchedule {
+    chords: VecDeque<PracticeChord>,
+    next_index: u64,
+    next_start_bar: u64,
+    next_start_beat: u8,
+}
+
+impl PracticeSchedule {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn len(&self) -> usize {
+        self.chords.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chords.is_empty()
+    }
+
+    pub fn current(&self) -> Option<&PracticeChord> {
+        self.chords.front()
+    }
+
+    pub fn next(&self) -> Option<&PracticeChord> {
+        self.chords.get(1)
+    }
+
+    /// The current chord and the ones scheduled after it
+    pub fn iter(&self) -> impl Iterator<Item = &PracticeChord> {
+        self.chords.iter()
+    }
+
+    /// Append a chord directly after the last scheduled one, in bars of `beats_per_bar`
+    pub fn push(
+        &mut self,
+        name: String,
+        midi_notes: Vec<u8>,
+        length: ChordLength,
+        beats_per_bar: u8,
+        progression_index: Option<usize>,
+    ) -> &PracticeChord {
+        let beats_per_bar = beats_per_bar.max(1) as u64;
+        let start = self.next_start_bar * beats_per_bar + self.next_start_beat as u64;
+        let end = start
+            + match length {
+                ChordLength::Bars(bars) => bars.max(1) as u64 * beats_per_bar,
+                ChordLength::Beats(beats) => beats.max(1) as u64,
+            };
+        let bars = (end - 1) / beats_per_bar - start / beats_per_bar + 1;
+        let end = (end / beats_per_bar, (end % beats_per_bar) as u8);
+        self.chords.push_back(PracticeChord {
+            index: self.next_index,
+            start_bar: self.next_start_bar,
+            start_beat: self.next_start_beat,
+            bars: bars.min(u8::MAX as u64) as u8,
+            name,
+            midi_notes,
+            progression_index,
+            end,
+        });
+        self.next_index += 1;
+        (self.next_start_bar, self.next_start_beat) = end;
+        self.chords.back().unwrap()
+    }
+
+    /// Drop every chord that finished before the chord with `index` started
+    pub fn advance_to(&mut self, index: u64) {
+        while self.chords.front().is_some_and(|c| c.index < index) {
+            self.chords.pop_front();
+        }
+    }
+
+    /// Forget every chord after the current one so they can be computed again
+    pub fn truncate_after_current(&mut self) {
+        self.chords.truncate(1);
+        if let Some(current) = self.chords.front() {
+            self.next_index = current.index + 1;
+            (self.next_start_bar, self.next_start_beat) = current.end;
+        } else {
+            self.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chords_are_placed_back_to_back() {
+        let mut schedule = PracticeSchedule::default();
+        schedule.push("C".into(), vec![60], ChordLength::Bars(2), 4, None);
+        schedule.push("F".into(), vec![65], ChordLength::Bars(1), 4, None);
+        let g = schedule
+            .push("G".into(), vec![67], ChordLength::Bars(3), 4, None)
+            .clone();
+        assert_eq!((g.index, g.start_bar), (2, 3));
+
+        schedule.advance_to(1);
+        assert_eq!(schedule.current().map(|c| c.name.as_str()), Some("F"));
+        assert_eq!(schedule.next().map(|c| c.name.as_str()), Some("G"));
+
+        schedule.truncate_after_current();
+        let c = schedule.push("C".into(), vec![60], ChordLength::Bars(1), 4, None);
+        assert_eq!((c.index, c.start_bar), (2, 3));
+    }
+
+    #[test]
+    fn test_chords_share_a_bar() {
+        let mut schedule = PracticeSchedule::default();
+        schedule.push("Dm7".into(), vec![62], ChordLength::Beats(2), 4, None);
+        let g = schedule
+            .push("G7".into(), vec![67], ChordLength::Beats(6), 4, None)
+            .clone();
+        assert_eq!((g.start_bar, g.start_beat, g.bars), (0, 2, 2));
+        let c = schedule.push("C".into(), vec![60], ChordLength::Bars(1), 4, None);
+        assert_eq!((c.start_bar, c.start_beat, c.bars), (2, 0, 1));
+    }
+}
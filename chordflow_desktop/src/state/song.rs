@@ -0,0 +1,423 @@
+use crate::{import::ImportedChord, state::progression::ProgressionChord};
+
+/// Longest play order a song unrolls to, a guard against charts that never end
+const MAX_PLAYED_BARS: usize = 4096;
+
+/// A chord and the beats it lasts within its bar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SongChord {
+    pub name: String,
+    pub beats: u8,
+}
+
+/// Share a bar's beats between its chords: evenly, with the beats left over going to the
+/// first chords. An empty slot continues the chord before it.
+pub fn split_beats(slots: &[Option<String>], beats: u8) -> Vec<SongChord> {
+    if slots.is_empty() {
+        return Vec::new();
+    }
+    let count = slots.len().min(beats.max(1) as usize);
+    let (share, extra) = (beats as usize / count, beats as usize % count);
+    let mut chords: Vec<SongChord> = Vec::new();
+    for (i, slot) in slots.iter().take(count).enumerate() {
+        let slot_beats = (share + (i < extra) as usize) as u8;
+        match (slot, chords.last_mut()) {
+            (Some(name), _) => chords.push(SongChord {
+                name: name.clone(),
+                beats: slot_beats,
+            }),
+            (None, Some(last)) => last.beats += slot_beats,
+            (None, None) => {}
+        }
+    }
+    chords
+}
+
+/// Where a D.C. or D.S. sends playback and where it stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jump {
+    DaCapo,
+    DaCapoAlCoda,
+    DaCapoAlFine,
+    DalSegno,
+    DalSegnoAlCoda,
+    DalSegnoAlFine,
+}
+
+impl Jump {
+    fn to_segno(self) -> bool {
+        matches!(
+            self,
+            Jump::DalSegno | Jump::DalSegnoAlCoda | Jump::DalSegnoAlFine
+        )
+    }
+
+    fn al_coda(self) -> bool {
+        matches!(self, Jump::DaCapoAlCoda | Jump::DalSegnoAlCoda)
+    }
+
+    fn al_fine(self) -> bool {
+        matches!(self, Jump::DaCapoAlFine | Jump::DalSegnoAlFine)
+    }
+}
+
+/// One bar of a chart as it is written, before repeats and jumps are played out
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SongBar {
+    /// Chords in the bar; none holds the chord of the bar before
+    pub chords: Vec<SongChord>,
+    /// Rehearsal mark the bar starts, e.g. `A`
+    pub section: Option<String>,
+    pub repeat_start: bool,
+    /// Times the repeat closed by this bar is played
+    pub repeat_end: Option<u8>,
+    /// First bar of the numbered ending
+    pub ending: Option<u8>,
+    pub segno: bool,
+    /// The first coda sign is where to leave for the coda, the last one starts it
+    pub coda: bool,
+    pub fine: bool,
+    /// Direction at the end of the bar
+    pub jump: Option<Jump>,
+}
+
+/// A bar as it is played in the form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormBar {
+    /// Index of the bar in the chart
+    pub bar: usize,
+    /// Section the bar is in: the last rehearsal mark written before it
+    pub section: Option<String>,
+}
+
+/// A chart with its form: sections, repeats, endings and jumps
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Song {
+    pub title: String,
+    pub composer: String,
+    pub key: String,
+    /// Beats per bar and the note value of a beat
+    pub time_signature: (u8, u8),
+    pub bpm: Option<u16>,
+    pub bars: Vec<SongBar>,
+}
+
+impl Song {
+    /// A song without a form playing a progression once, each chord for its bars. Chords
+    /// lasting beats share bars until they are full.
+    pub fn from_progression(chords: &[ProgressionChord], beats_per_bar: u8) -> Self {
+        let beats_per_bar = beats_per_bar.max(1);
+        let mut bars: Vec<SongBar> = Vec::new();
+        // Beats of the last bar taken so far by chords sharing it
+        let mut filled = beats_per_bar;
+        for chord in chords {
+            let name = &chord.chord.origin;
+            let Some(mut beats) = chord.beats else {
+                filled = beats_per_bar;
+                if chord.bars > 0 {
+                    bars.push(SongBar {
+                        chords: vec![SongChord {
+                            name: name.clone(),
+                            beats: beats_per_bar,
+                        }],
+                        ..SongBar::default()
+                    });
+                    // The bars after the first hold its chord
+                    let held = chord.bars as usize - 1;
+                    bars.extend(std::iter::repeat_n(SongBar::default(), held));
+                }
+                continue;
+            };
+            while beats > 0 {
+                if filled == beats_per_bar {
+                    bars.push(SongBar::default());
+                    filled = 0;
+                }
+                let taken = beats.min(beats_per_bar - filled);
+                if let Some(bar) = bars.last_mut() {
+                    bar.chords.push(SongChord {
+                        name: name.clone(),
+                        beats: taken,
+                    });
+                }
+                filled += taken;
+                beats -= taken;
+            }
+        }
+        Self {
+            time_signature: (beats_per_bar, 4),
+            bars,
+            ..Self::default()
+        }
+    }
+
+    /// Whether the song still plays as `chords`, or they were edited since it was imported
+    pub fn matches(&self, chords: &[ProgressionChord]) -> bool {
+        self.matches_in(&self.form(), chords)
+    }
+
+    /// Whether `chords` still last as long as the song's chords in the bars of `form`
+    pub fn matches_in(&self, form: &[FormBar], chords: &[ProgressionChord]) -> bool {
+        let own = self.chords_in(form);
+        own.len() == chords.len()
+            && own
+                .iter()
+                .zip(chords)
+                .all(|(own, c)| own.bars == c.bars && own.beats == c.beats)
+    }
+
+    /// Beats per bar counted in quarter notes, when it is a whole number
+    pub fn quarters_per_bar(&self) -> Option<u8> {
+        let (beats, value) = self.time_signature;
+        let quarters = beats as u32 * 4;
+        (value > 0 && quarters.is_multiple_of(value as u32))
+            .then(|| (quarters / value as u32) as u8)
+    }
+
+    /// Index of the last bar of the ending starting at `start`: the bar closing its repeat.
+    /// None for a last ending, which is played through.
+    fn ending_end(&self, start: usize) -> Option<usize> {
+        for (i, bar) in self.bars.iter().enumerate().skip(start) {
+            if i > start && (bar.ending.is_some() || bar.repeat_start) {
+                return None;
+            }
+            if bar.repeat_end.is_some() {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    /// Indices of the bars in the order they are played, with repeats, endings and
+    /// D.C./D.S. jumps played out. Repeats are not taken again after a jump.
+    pub fn play_order(&self) -> Vec<usize> {
+        let segno = self.bars.iter().position(|b| b.segno).unwrap_or(0);
+        let first_coda = self.bars.iter().position(|b| b.coda);
+        let last_coda = self.bars.iter().rposition(|b| b.coda);
+
+        let mut order = Vec::new();
+        let mut repeat_start = 0;
+        let mut pass = 1;
+        let mut jumped: Option<Jump> = None;
+        let mut i = 0;
+        while i < self.bars.len() && order.len() < MAX_PLAYED_BARS {
+            let bar = &self.bars[i];
+            if bar.repeat_start && i != repeat_start {
+                repeat_start = i;
+                pass = 1;
+            }
+            if let Some(number) = bar.ending {
+                // An ending that closes a repeat is only played on its own pass
+                if let Some(end) = self.ending_end(i) {
+                    if jumped.is_some() || number != pass {
+                        i = end + 1;
+                        continue;
+                    }
+                }
+            }
+            order.push(i);
+
+            if jumped.is_some_and(Jump::al_fine) && bar.fine {
+                break;
+            }
+            if jumped.is_some_and(Jump::al_coda) && Some(i) == first_coda {
+                if let Some(coda) = last_coda.filter(|&coda| coda > i) {
+                    i = coda;
+                    continue;
+                }
+            }
+            if let (Some(times), None) = (bar.repeat_end, jumped) {
+                if pass < times {
+                    pass += 1;
+                    i = repeat_start;
+                    continue;
+                }
+                // A repeat without a start sign goes back to the end of the one before
+                pass = 1;
+                repeat_start = i + 1;
+            }
+            if let (Some(jump), None) = (bar.jump, jumped) {
+                jumped = Some(jump);
+                i = if jump.to_segno() { segno } else { 0 };
+                continue;
+            }
+            i += 1;
+        }
+        order
+    }
+
+    /// The bars in the order they are played, each with its section
+    pub fn form(&self) -> Vec<FormBar> {
+        let mut section = None;
+        let sections: Vec<Option<String>> = self
+            .bars
+            .iter()
+            .map(|bar| {
+                if bar.section.is_some() {
+                    section = bar.section.clone();
+                }
+                section.clone()
+            })
+            .collect();
+        self.play_order()
+            .into_iter()
+            .map(|bar| FormBar {
+                bar,
+                section: sections[bar].clone(),
+            })
+            .collect()
+    }
+
+    /// The bars of the first time `section` is played, to practise it on its own
+    pub fn section_form(&self, section: &str) -> Vec<FormBar> {
+        let in_section = |bar: &FormBar| bar.section.as_deref() == Some(section);
+        self.form()
+            .into_iter()
+            .skip_while(|bar| !in_section(bar))
+            .take_while(in_section)
+            .collect()
+    }
+
+    /// Labels of the sections as they are written, each once
+    pub fn section_labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = Vec::new();
+        for label in self.bars.iter().filter_map(|bar| bar.section.as_ref()) {
+            if !labels.contains(label) {
+                labels.push(label.clone());
+            }
+        }
+        labels
+    }
+
+    /// The chords of the whole form, see [`Song::chords_in`]
+    pub fn chords(&self) -> Vec<ImportedChord> {
+        self.chords_in(&self.form())
+    }
+
+    /// The chords of the bars of `form`, repeated chords merged into longer ones and bars
+    /// without chords holding the chord before. Chords sharing a bar last their beats,
+    /// unless the beats are not quarter notes; then the longest one takes the bar.
+    pub fn chords_in(&self, form: &[FormBar]) -> Vec<ImportedChord> {
+        let split_bars = self.time_signature.1 == 4;
+        let mut chords: Vec<ImportedChord> = Vec::new();
+        for form_bar in form {
+            let bar = &self.bars[form_bar.bar].chords;
+            if split_bars && bar.len() > 1 {
+                chords.extend(bar.iter().map(|chord| ImportedChord {
+                    name: chord.name.clone(),
+                    bars: 1,
+                    beats: Some(chord.beats),
+                }));
+                continue;
+            }
+            let longest = bar
+                .iter()
+                .rev()
+                .max_by_key(|chord| chord.beats)
+                .map(|chord| chord.name.clone());
+            match (longest, chords.last_mut()) {
+                (Some(name), Some(last))
+                    if last.name == name && last.beats.is_none() && last.bars < u8::MAX =>
+                {
+                    last.bars += 1
+                }
+                (Some(name), _) => chords.push(ImportedChord {
+                    name,
+                    bars: 1,
+                    beats: None,
+                }),
+                (None, Some(last)) => match last.beats.as_mut() {
+                    // The last chord of a shared bar is held through the next one
+                    Some(beats) => *beats = beats.saturating_add(self.time_signature.0),
+                    None if last.bars < u8::MAX => last.bars += 1,
+                    None => {}
+                },
+                (None, None) => {}
+            }
+        }
+        chords
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(chord: &str) -> SongBar {
+        SongBar {
+            chords: vec![SongChord {
+                name: chord.to_string(),
+                beats: 4,
+            }],
+            ..SongBar::default()
+        }
+    }
+
+    fn names(song: &Song) -> Vec<String> {
+        song.play_order()
+            .into_iter()
+            .map(|i| song.bars[i].chords[0].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_repeats_with_endings() {
+        let mut bars: Vec<SongBar> = ["A", "B", "C", "D", "E"].map(bar).to_vec();
+        bars[0].repeat_start = true;
+        bars[2].ending = Some(1);
+        bars[2].repeat_end = Some(2);
+        bars[3].ending = Some(2);
+        let song = Song {
+            bars,
+            ..Song::default()
+        };
+        assert_eq!(names(&song), ["A", "B", "C", "A", "B", "D", "E"]);
+    }
+
+    #[test]
+    fn test_dal_segno_al_coda() {
+        let mut bars: Vec<SongBar> = ["A", "B", "C", "D", "E"].map(bar).to_vec();
+        bars[1].segno = true;
+        bars[2].coda = true;
+        bars[3].jump = Some(Jump::DalSegnoAlCoda);
+        bars[4].coda = true;
+        bars[0].repeat_start = true;
+        bars[1].repeat_end = Some(2);
+        let song = Song {
+            bars,
+            ..Song::default()
+        };
+        assert_eq!(names(&song), ["A", "B", "A", "B", "C", "D", "B", "C", "E"]);
+    }
+
+    #[test]
+    fn test_section_played_on_its_own() {
+        let mut bars: Vec<SongBar> = ["A", "B", "C", "D", "E"].map(bar).to_vec();
+        bars[0].section = Some("A".to_string());
+        bars[0].repeat_start = true;
+        bars[1].repeat_end = Some(2);
+        bars[2].section = Some("B".to_string());
+        bars[4].section = Some("A".to_string());
+        let song = Song {
+            bars,
+            ..Song::default()
+        };
+        let form = song.form();
+        let sections: Vec<Option<&str>> = form.iter().map(|bar| bar.section.as_deref()).collect();
+        assert_eq!(
+            sections,
+            [
+                Some("A"),
+                Some("A"),
+                Some("A"),
+                Some("A"),
+                Some("B"),
+                Some("B"),
+                Some("A")
+            ]
+        );
+        let b: Vec<usize> = song.section_form("B").iter().map(|bar| bar.bar).collect();
+        assert_eq!(b, [2, 3]);
+        assert_eq!(song.section_labels(), ["A", "B"]);
+    }
+}
@@ -1,16 +1,43 @@
-use std::time::Duration;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use chordflow_music_theory::chord::Chord;
-use dioxus::prelude::*;
+use chrono::Local;
+use dioxus::{
+    desktop::{
+        tao::event::{Event, WindowEvent},
+        use_window, use_wry_event_handler,
+    },
+    prelude::*,
+};
+use strum::EnumCount;
 
 const FAVICON: Asset = asset!("/assets/favicon.ico");
 const TAILWIND_CSS: Asset = asset!("/assets/tailwind.css");
 const MAIN_CSS: Asset = asset!("/assets/main.css");
+/// How often the session being played is saved, in case the app does not get to log it
+const UNFINISHED_SESSION_INTERVAL: Duration = Duration::from_secs(60);
 
 use crate::{
+    app_settings::{AppSettings, ProgressionCache, Volumes, WindowSize},
+    audio::{
+        articulation::ChordArticulation, bass::BassLine, comping::CompingPattern,
+        drums::DrumGroove, gap::GapClick, output::display_delay, tempo::TempoRamp,
+    },
+    practice_log::{
+        append_session, recover_unfinished, save_unfinished, split_chord_name, PlayedBar,
+        PracticeSession, SessionRecorder,
+    },
     state::{
-        diatonic::DiatonicConfig, fourths::FourthsConfig, modes::ModeOption,
+        diatonic::DiatonicConfig,
+        fourths::FourthsConfig,
+        modes::ModeOption,
         progression::ProgressionConfig,
+        schedule::{ChordLength, PracticeChord, PracticeSchedule},
     },
     ui::{
         bottom_zone::layout::BottomZone, center_stage::layout::CenterStage,
@@ -28,6 +55,19 @@ pub struct MetronomeState {
     pub current_tick: u8,
     pub subdivision: Subdivision,
     pub count_in_enabled: bool,
+    pub tempo_ramp_enabled: bool,
+    pub tempo_ramp: TempoRamp,
+    pub gap_click_enabled: bool,
+    pub gap_click: GapClick,
+    pub articulation: ChordArticulation,
+    /// Percent of a beat taken by the first of two eighths, 50 is straight
+    pub swing_percent: u8,
+    pub bass_enabled: bool,
+    pub bass: BassLine,
+    pub drums_enabled: bool,
+    pub drums: DrumGroove,
+    /// When the last main beat was shown, for the tap-along calibration
+    pub beat_shown_at: Option<Instant>,
 }
 
 impl Default for MetronomeState {
@@ -40,103 +80,204 @@ impl Default for MetronomeState {
             current_tick: 0,
             subdivision: Subdivision::default(),
             count_in_enabled: false,
+            tempo_ramp_enabled: false,
+            tempo_ramp: TempoRamp::default(),
+            gap_click_enabled: false,
+            gap_click: GapClick::default(),
+            articulation: ChordArticulation::default(),
+            swing_percent: 50,
+            bass_enabled: false,
+            bass: BassLine::default(),
+            drums_enabled: false,
+            drums: DrumGroove::default(),
+            beat_shown_at: None,
         }
     }
 }
 
 impl MetronomeState {
-    fn tick(&mut self) -> bool {
-        self.current_tick += 1;
-        let mut cycle_done = false;
-
-        if self.current_tick > self.ticks_per_bar {
-            self.current_tick = 1;
-            self.current_bar += 1;
-
-            if self.current_bar > self.bars_per_chord {
-                self.current_bar = 1;
-                cycle_done = true;
-            }
+    /// Send every setting to the audio thread, e.g. to a freshly built engine
+    pub fn send_settings(&self) {
+        for command in self.settings_commands() {
+            let _ = AUDIO_CMD.0.try_send(command);
         }
-        cycle_done
+    }
+
+    /// The commands that bring an engine to these settings
+    pub fn settings_commands(&self) -> [AudioCommand; 9] {
+        [
+            AudioCommand::SetBPM(self.bpm),
+            AudioCommand::SetSubdivision(self.subdivision.subdivisions_per_beat()),
+            AudioCommand::SetTimeSignature(self.ticks_per_bar),
+            AudioCommand::SetSwing(self.swing_percent),
+            AudioCommand::SetArticulation(self.articulation),
+            AudioCommand::SetBass(self.bass_enabled.then_some(self.bass)),
+            AudioCommand::SetDrums(self.drums_enabled.then_some(self.drums)),
+            AudioCommand::SetTempoRamp(self.tempo_ramp_enabled.then_some(self.tempo_ramp)),
+            AudioCommand::SetGapClick(self.gap_click_enabled.then_some(self.gap_click)),
+        ]
     }
 }
 
-#[derive(Default)]
+/// Chords kept scheduled on the audio thread beyond the one that is playing
+const LOOKAHEAD_CHORDS: usize = 4;
+
 pub struct AppState {
     pub is_playing: bool,
     pub selected_mode: ModeOption,
     pub fourths_config: FourthsConfig,
     pub diatonic_config: DiatonicConfig,
     pub progression_config: ProgressionConfig,
+    /// Bars per chord for the generated (non-custom) modes
+    pub bars_per_chord: u8,
+    /// Beats per bar the chords are placed in, as on the metronome
+    pub beats_per_bar: u8,
+    /// Comping pattern per mode, indexed by the mode's position
+    pub comping: [CompingPattern; ModeOption::COUNT],
+    pub schedule: PracticeSchedule,
+    /// Name of the preset that set the app up, until it is set up otherwise
+    pub preset: Option<String>,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self {
+            is_playing: false,
+            selected_mode: ModeOption::default(),
+            fourths_config: FourthsConfig::default(),
+            diatonic_config: DiatonicConfig::default(),
+            progression_config: ProgressionConfig::default(),
+            bars_per_chord: 2,
+            beats_per_bar: 4,
+            comping: [CompingPattern::default(); ModeOption::COUNT],
+            schedule: PracticeSchedule::default(),
+            preset: None,
+        }
+    }
 }
 
 impl AppState {
     pub fn get_chords(&self) -> (String, String) {
-        match self.selected_mode {
-            ModeOption::Fourths => {
-                let (current_chord, next_chord) = self.fourths_config.get_chords();
-                (current_chord, next_chord)
-            }
-            ModeOption::Diatonic => {
-                let (current_chord, next_chord) = self.diatonic_config.get_chords();
-                (current_chord, next_chord)
-            }
-            ModeOption::Custom => {
-                let (current_chord, next_chord) = self.progression_config.get_chords();
-                (current_chord, next_chord)
-            }
-            _ => ("".to_string(), "".to_string()),
-        }
+        (
+            self.schedule
+                .current()
+                .map(|c| c.name.clone())
+                .unwrap_or_default(),
+            self.schedule
+                .next()
+                .map(|c| c.name.clone())
+                .unwrap_or_default(),
+        )
     }
 
-    pub fn get_midi_codes_for_chords(&self) -> (Vec<u8>, Vec<u8>) {
+    pub fn comping_pattern(&self) -> CompingPattern {
+        self.comping[self.selected_mode as usize]
+    }
+
+    pub fn set_comping_pattern(&mut self, pattern: CompingPattern) {
+        self.comping[self.selected_mode as usize] = pattern;
+        let _ = AUDIO_CMD.0.try_send(AudioCommand::SetComping(pattern));
+    }
+
+    /// Bars of the chord that is currently playing
+    pub fn current_bars(&self) -> u8 {
+        self.schedule
+            .current()
+            .map(|c| c.bars)
+            .unwrap_or(self.bars_per_chord)
+    }
+
+    /// Key the playing chord is practised in: the scale's root in the diatonic mode, the
+    /// song's key when it has one and otherwise the chord's own root
+    pub fn current_key(&self) -> String {
         match self.selected_mode {
-            ModeOption::Fourths => (
-                chord_to_midi(self.fourths_config.current_chord),
-                chord_to_midi(self.fourths_config.next_chord),
-            ),
-            ModeOption::Diatonic => (
-                chord_to_midi(self.diatonic_config.current_chord),
-                chord_to_midi(self.diatonic_config.next_chord),
-            ),
-            ModeOption::Custom => (
-                self.progression_config
-                    .current_chord
-                    .clone()
-                    .map(|c| c.to_midi_codes())
-                    .unwrap_or_default(),
-                self.progression_config
-                    .next_chord
-                    .clone()
-                    .map(|c| c.to_midi_codes())
-                    .unwrap_or_default(),
-            ),
-
-            _ => (vec![], vec![]),
+            ModeOption::Diatonic => self.diatonic_config.scale.root.to_string(),
+            ModeOption::Custom => match &self.progression_config.song {
+                Some(song) if !song.key.is_empty() => song.key.clone(),
+                _ => self.current_chord_root(),
+            },
+            _ => self.current_chord_root(),
         }
     }
 
-    pub fn advance(&mut self) {
+    fn current_chord_root(&self) -> String {
+        let (chord, _) = self.get_chords();
+        split_chord_name(&chord).0.to_string()
+    }
+
+    /// Position of the playing chord in the custom progression
+    pub fn current_progression_index(&self) -> Option<usize> {
+        self.schedule.current().and_then(|c| c.progression_index)
+    }
+
+    /// Take the next chord from the selected mode: name, MIDI notes, length and progression index
+    fn next_practice_chord(&mut self) -> Option<(String, Vec<u8>, ChordLength, Option<usize>)> {
         match self.selected_mode {
             ModeOption::Fourths => {
+                let chord = self.fourths_config.current_chord;
                 self.fourths_config.generate_next_chord();
+                Some((
+                    chord.to_string(),
+                    chord_to_midi(chord),
+                    ChordLength::Bars(self.bars_per_chord),
+                    None,
+                ))
             }
             ModeOption::Diatonic => {
+                let chord = self.diatonic_config.current_chord;
                 self.diatonic_config.generate_next_chord();
+                Some((
+                    chord.to_string(),
+                    chord_to_midi(chord),
+                    ChordLength::Bars(self.bars_per_chord),
+                    None,
+                ))
             }
             ModeOption::Custom => {
-                self.progression_config.generate_next_chord();
-                let mut metronome_state: Signal<MetronomeState> = use_context();
-                metronome_state.write().bars_per_chord =
-                    self.progression_config.get_bars_per_cycle_current();
+                let config = &mut self.progression_config;
+                if config.chords.is_empty() {
+                    return None;
+                }
+                let chord = config.current_chord.clone()?;
+                let index = config.current_chord_index;
+                let length = config.current_length();
+                config.generate_next_chord();
+                Some((
+                    chord.origin.clone(),
+                    chord.to_midi_codes(),
+                    length,
+                    Some(index),
+                ))
             }
+            _ => None,
+        }
+    }
 
-            _ => {}
+    /// Keep the audio timeline `LOOKAHEAD_CHORDS` ahead of the playing chord
+    fn fill_schedule(&mut self) {
+        while self.schedule.len() <= LOOKAHEAD_CHORDS {
+            let Some((name, notes, length, progression_index)) = self.next_practice_chord() else {
+                break;
+            };
+            let chord = self
+                .schedule
+                .push(name, notes, length, self.beats_per_bar, progression_index)
+                .to_scheduled();
+            let _ = AUDIO_CMD.0.try_send(AudioCommand::ScheduleChord(chord));
         }
     }
 
-    pub fn restart(&mut self) {
+    /// Chords in one pass through the selected mode, 0 when it has none to play
+    pub fn cycle_length(&self) -> usize {
+        match self.selected_mode {
+            ModeOption::Fourths => 12,
+            ModeOption::Diatonic => self.diatonic_config.scale.intervals.len(),
+            ModeOption::Custom => self.progression_config.chords.len(),
+            _ => 0,
+        }
+    }
+
+    fn reset_mode(&mut self) {
         match self.selected_mode {
             ModeOption::Fourths => {
                 self.fourths_config.reset();
@@ -146,26 +287,113 @@ impl AppState {
             }
             ModeOption::Custom => {
                 self.progression_config.reset();
-                let mut metronome_state: Signal<MetronomeState> = use_context();
-                metronome_state.write().bars_per_chord =
-                    self.progression_config.get_bars_per_cycle_current();
             }
 
             _ => {}
         }
+    }
+
+    /// A copy that plays the selected mode from its first chord without touching playback,
+    /// e.g. for rendering the session to a file
+    pub fn session_from_start(&self) -> AppState {
+        let mut session = AppState {
+            is_playing: false,
+            selected_mode: self.selected_mode,
+            fourths_config: self.fourths_config.clone(),
+            diatonic_config: self.diatonic_config.clone(),
+            progression_config: self.progression_config.clone(),
+            bars_per_chord: self.bars_per_chord,
+            beats_per_bar: self.beats_per_bar,
+            comping: self.comping,
+            schedule: PracticeSchedule::default(),
+            preset: self.preset.clone(),
+        };
+        session.reset_mode();
+        session
+    }
+
+    /// Next chord of a session that is not played live
+    pub fn next_session_chord(&mut self) -> Option<PracticeChord> {
+        let (name, notes, length, progression_index) = self.next_practice_chord()?;
+        let chord = self
+            .schedule
+            .push(name, notes, length, self.beats_per_bar, progression_index)
+            .clone();
+        // Only the live schedule needs to remember what was played
+        self.schedule.advance_to(chord.index);
+        Some(chord)
+    }
+
+    /// Start the selected mode from its first chord on a fresh audio timeline
+    pub fn rebuild_schedule(&mut self) {
+        self.reset_mode();
+        self.schedule.clear();
+        // Send restart to audio stream to drop the old timeline
+        let _ = AUDIO_CMD.0.try_send(AudioCommand::Restart);
+        let _ = AUDIO_CMD
+            .0
+            .try_send(AudioCommand::SetComping(self.comping_pattern()));
+        self.fill_schedule();
+    }
+
+    /// Schedule the playing chord and the ones after it again on a fresh audio timeline,
+    /// e.g. on the engine of a new output device. Playback goes on where it was.
+    pub fn resend_schedule(&mut self) {
+        if self.schedule.is_empty() {
+            self.rebuild_schedule();
+            return;
+        }
+        let _ = AUDIO_CMD.0.try_send(AudioCommand::Restart);
+        let _ = AUDIO_CMD
+            .0
+            .try_send(AudioCommand::SetComping(self.comping_pattern()));
+        for chord in self.schedule.iter() {
+            let _ = AUDIO_CMD
+                .0
+                .try_send(AudioCommand::ScheduleChord(chord.to_scheduled()));
+        }
+    }
+
+    /// Recompute the chords after the playing one, e.g. after editing the progression
+    pub fn reschedule_upcoming(&mut self) {
+        let Some(current) = self.schedule.current() else {
+            self.rebuild_schedule();
+            return;
+        };
+        let index = current.index;
+        if let Some(progression_index) = current.progression_index {
+            self.progression_config.seek(progression_index + 1);
+        }
+        self.schedule.truncate_after_current();
+        let _ = AUDIO_CMD.0.try_send(AudioCommand::DropChordsAfter(index));
+        self.fill_schedule();
+    }
+
+    /// Place the chords in bars of `beats`, e.g. after the time signature changed
+    pub fn set_beats_per_bar(&mut self, beats: u8) {
+        if beats != self.beats_per_bar {
+            self.beats_per_bar = beats;
+            self.reschedule_upcoming();
+        }
+    }
+
+    /// The audio thread started the chord with `index`
+    pub fn on_chord_changed(&mut self, index: u64) {
+        self.schedule.advance_to(index);
+        self.fill_schedule();
+    }
+
+    pub fn restart(&mut self) {
+        self.rebuild_schedule();
         self.metronome_restart();
         // self.is_playing = false;
     }
 
     fn metronome_restart(&self) {
-        let _ = AUDIO_CMD.0.try_send(AudioCommand::SetChord(Some(
-            self.get_midi_codes_for_chords().0,
-        )));
         let mut metronome_state: Signal<MetronomeState> = use_context();
+        metronome_state.write().bars_per_chord = self.current_bars();
         metronome_state.write().current_bar = 1;
         metronome_state.write().current_tick = 0;
-        // Send restart to audio stream to sync counters
-        let _ = AUDIO_CMD.0.try_send(AudioCommand::Restart);
     }
 }
 
@@ -179,50 +407,174 @@ pub fn App() -> Element {
     use_context_provider(|| app_state);
     use_context_provider(|| metronome_state);
 
-    use_future(move || async move {
-        let _ = AUDIO_CMD.0.try_send(AudioCommand::SetChord(Some(
-            app_state.read().get_midi_codes_for_chords().0,
-        )));
+    let window = use_window();
+    use_future(move || {
+        let window = window.clone();
+        async move {
+            // The last session is restored before anything is scheduled
+            let mut saved = AppSettings::load();
+            saved.apply(&mut app_state.write(), &mut metronome_state.write());
+            metronome_state.read().send_settings();
+            app_state.write().rebuild_schedule();
+
+            // Settings are saved once they changed, checked every second
+            let mut progression = ProgressionCache::default();
+            let mut capture = move || {
+                let size = window.inner_size().to_logical::<u32>(window.scale_factor());
+                AppSettings::capture_cached(
+                    &app_state.read(),
+                    &metronome_state.read(),
+                    Volumes::current(),
+                    WindowSize {
+                        width: size.width,
+                        height: size.height,
+                    },
+                    &mut progression,
+                )
+            };
+            // Settings of a newer version are only saved over once something changes
+            if saved.is_newer() {
+                saved = capture();
+            }
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let current = capture();
+                if current != saved {
+                    current.save();
+                    saved = current;
+                }
+            }
+        }
     });
 
     use_effect(move || {
-        let _ = AUDIO_CMD.0.try_send(AudioCommand::SetBarsPerCycle(
-            metronome_state.read().bars_per_chord,
-        ));
         let _ = AUDIO_CMD
             .0
             .try_send(AudioCommand::SetBPM(metronome_state.read().bpm));
     });
 
-    let _ = use_future(move || async move {
-        loop {
-            while let Ok(event) = AUDIO_EVT.1.try_recv() {
-                match event {
-                    AudioEvent::Tick => {
-                        let cycle_done = metronome_state.write().tick();
-                        if cycle_done {
-                            app_state.write().advance();
-                        }
+    let mut handle_event = move |event: AudioEvent| match event {
+        AudioEvent::Tick { bar, beat, .. } => {
+            // Beats still on their way when playback was stopped are not shown
+            if !app_state.read().is_playing {
+                return;
+            }
+            let mut metronome = metronome_state.write();
+            metronome.current_bar = bar;
+            metronome.current_tick = beat;
+            metronome.beat_shown_at = Some(Instant::now());
+        }
+        AudioEvent::ChordChanged { index, .. } => {
+            app_state.write().on_chord_changed(index);
+            let bars = app_state.read().current_bars();
+            metronome_state.write().bars_per_chord = bars;
+        }
+        AudioEvent::TempoChanged(bpm) => {
+            metronome_state.write().bpm = bpm;
+        }
+        AudioEvent::Stopped => {
+            app_state.write().is_playing = false;
+            metronome_state.write().current_bar = 1;
+            metronome_state.write().current_tick = 0;
+        }
+        AudioEvent::SoundSourceReleased(source) => drop(source),
+        AudioEvent::ClickSampleReleased(sample) => drop(sample),
+        AudioEvent::StreamRestarted => {
+            metronome_state.read().send_settings();
+            // The new engine resumes at the start of the playing chord
+            app_state.write().resend_schedule();
+            metronome_state.write().current_bar = 1;
+            metronome_state.write().current_tick = 0;
+            if app_state.read().is_playing {
+                let _ = AUDIO_CMD.0.try_send(AudioCommand::Start);
+            }
+        }
+    };
 
-                        let state = metronome_state.read();
-
-                        if state.current_tick + 1 > state.ticks_per_bar {
-                            if state.current_bar + 1 > state.bars_per_chord {
-                                let _ = AUDIO_CMD.0.try_send(AudioCommand::SetChord(Some(
-                                    app_state.read().get_midi_codes_for_chords().1,
-                                )));
-                            } else {
-                                let _ = AUDIO_CMD.0.try_send(AudioCommand::SetChord(Some(
-                                    app_state.read().get_midi_codes_for_chords().0,
-                                )));
+    let recorder = use_hook(|| Rc::new(RefCell::new(SessionRecorder::default())));
+
+    // A session still being played when the app quits is logged too
+    use_wry_event_handler({
+        let recorder = recorder.clone();
+        move |event, _| {
+            if matches!(
+                event,
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } | Event::LoopDestroyed
+            ) {
+                if let Some(session) = recorder.borrow_mut().finish() {
+                    log_session(&session);
+                }
+            }
+        }
+    });
+
+    let _ = use_future(move || {
+        let recorder = recorder.clone();
+        async move {
+            if let Err(e) = recover_unfinished() {
+                log::warn!("Could not recover the last practice session: {:#}", e);
+            }
+            // Beats are shown once they are heard, after the output latency
+            let mut delayed: VecDeque<(Instant, AudioEvent)> = VecDeque::new();
+            let mut unfinished_saved_at = Instant::now();
+            loop {
+                let now = Instant::now();
+                while let Ok(event) = AUDIO_EVT.1.try_recv() {
+                    if matches!(
+                        event,
+                        AudioEvent::Tick { .. }
+                            | AudioEvent::ChordChanged { .. }
+                            | AudioEvent::TempoChanged(_)
+                            | AudioEvent::Stopped
+                    ) {
+                        delayed.push_back((now + display_delay(), event));
+                    } else {
+                        handle_event(event);
+                    }
+                }
+                while delayed.front().is_some_and(|(due, _)| *due <= now) {
+                    if let Some((_, event)) = delayed.pop_front() {
+                        let bar_started = matches!(event, AudioEvent::Tick { beat: 1, .. });
+                        handle_event(event);
+                        if bar_started && app_state.read().is_playing {
+                            let state = app_state.read();
+                            let metronome = metronome_state.read();
+                            let (chord, _) = state.get_chords();
+                            let key = state.current_key();
+                            let bar = PlayedBar {
+                                mode: state.selected_mode,
+                                preset: state.preset.as_deref(),
+                                key: &key,
+                                quality: split_chord_name(&chord).1,
+                                bpm: metronome.bpm,
+                                beats: metronome.ticks_per_bar,
+                            };
+                            let finished = recorder.borrow_mut().bar(bar, Local::now());
+                            if let Some(session) = finished {
+                                log_session(&session);
                             }
-                        } else {
-                            let _ = AUDIO_CMD.0.try_send(AudioCommand::SetChord(None));
                         }
                     }
                 }
+                // A session ends when playback stops
+                if !app_state.read().is_playing {
+                    let finished = recorder.borrow_mut().finish();
+                    if let Some(session) = finished {
+                        log_session(&session);
+                    }
+                } else if now.duration_since(unfinished_saved_at) >= UNFINISHED_SESSION_INTERVAL {
+                    unfinished_saved_at = now;
+                    if let Some(session) = recorder.borrow().current() {
+                        if let Err(e) = save_unfinished(session) {
+                            log::warn!("Could not save the practice session: {:#}", e);
+                        }
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
             }
-            tokio::time::sleep(Duration::from_millis(5)).await;
         }
     });
 
@@ -270,6 +622,12 @@ pub fn App() -> Element {
     }
 }
 
+fn log_session(session: &PracticeSession) {
+    if let Err(e) = append_session(session) {
+        log::warn!("Could not log the practice session: {:#}", e);
+    }
+}
+
 pub fn chord_to_midi(chord: Chord) -> Vec<u8> {
     chord
         .to_c_based_semitones()
@@ -1,11 +1,20 @@
 #![allow(non_snake_case)]
 
 use dioxus::prelude::*;
+use strum::IntoEnumIterator;
 
-use crate::ui::app::MetronomeState;
+use crate::{
+    audio::comping::CompingPattern,
+    ui::app::{AppState, MetronomeState},
+    AudioCommand, AUDIO_CMD,
+};
+
+/// Swing amounts offered, in percent of a beat taken by the first eighth
+const SWING_PERCENTS: [u8; 5] = [50, 55, 60, 67, 75];
 
 pub fn PlayControls() -> Element {
     let mut metronome_state: Signal<MetronomeState> = use_context();
+    let mut app_state: Signal<AppState> = use_context();
 
     rsx! {
         div { class: "control-group-center",
@@ -21,6 +30,37 @@ pub fn PlayControls() -> Element {
                 }
                 span { "Count-in" }
             }
+
+            // Comping pattern for the selected mode
+            span { class: "label-small", "Comping" }
+            select {
+                class: "select-styled",
+                onchange: move |e| {
+                    if let Some(pattern) = CompingPattern::iter().find(|p| p.to_string() == e.value()) {
+                        app_state.write().set_comping_pattern(pattern);
+                    }
+                },
+                for pattern in CompingPattern::iter() {
+                    option { selected: pattern == app_state.read().comping_pattern(), "{pattern}" }
+                }
+            }
+
+            span { class: "label-small", "Swing" }
+            select {
+                class: "select-styled",
+                onchange: move |e| {
+                    let percent = e.value().parse::<u8>().unwrap_or(50);
+                    metronome_state.write().swing_percent = percent;
+                    let _ = AUDIO_CMD.0.try_send(AudioCommand::SetSwing(percent));
+                },
+                for percent in SWING_PERCENTS {
+                    option {
+                        value: "{percent}",
+                        selected: percent == metronome_state.read().swing_percent,
+                        if percent == 50 { "Straight" } else { "{percent}%" }
+                    }
+                }
+            }
         }
     }
 }
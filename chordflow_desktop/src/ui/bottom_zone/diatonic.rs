@@ -18,6 +18,7 @@ pub fn DiatonicSelector() -> Element {
                 onchange: move |e| {
                     if let Some(root) = generate_all_roots().get(e.value().parse::<usize>().unwrap_or(0)) {
                         config_state.write().diatonic_config.set_root(*root);
+                        config_state.write().restart();
                     }
                 },
                 for (i, root) in generate_all_roots().into_iter().enumerate() {
@@ -36,6 +37,7 @@ pub fn DiatonicSelector() -> Element {
                 checked: config_state.read().diatonic_config.is_random,
                 onchange: move |e| {
                     config_state.write().diatonic_config.is_random = e.value().parse::<bool>().unwrap_or(false);
+                    config_state.write().reschedule_upcoming();
                 }
             }
         }
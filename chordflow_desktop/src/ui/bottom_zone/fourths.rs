@@ -18,6 +18,7 @@ pub fn CircleOfFourthsQuality() -> Element {
                 let value = e.value();
                 let q = Quality::from_name(&value);
                 app_state.write().fourths_config = FourthsConfig::new(q);
+                app_state.write().restart();
             },
             for quality in Quality::iter() {
                 option { selected: quality == app_state.read().fourths_config.quality, "{quality.name()}" }
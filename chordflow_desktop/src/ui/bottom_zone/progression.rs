@@ -1,28 +1,173 @@
 #![allow(non_snake_case)]
 
+use anyhow::Result;
 use dioxus::prelude::*;
 
 use crate::{
-    state::progression::ProgressionChord,
-    ui::app::{AppState, MetronomeState},
+    import::{
+        chordpro::{parse_chordpro, write_chordpro},
+        ireal::parse_ireal,
+        lead_sheet::{parse_lead_sheet, write_lead_sheet},
+        midi::MidiImport,
+        musicxml::{parse_musicxml, write_musicxml},
+        to_progression, ImportedChord,
+    },
+    state::{progression::ProgressionChord, song::Song},
+    ui::{
+        app::{AppState, MetronomeState},
+        top_zone::time_signature_selector::BEATS_PER_BAR,
+    },
+    AudioCommand, AUDIO_CMD, MAX_BPM, MIN_BPM,
 };
 
+/// An imported file with several tracks or songs, waiting until one is chosen
+enum PendingImport {
+    Midi(MidiImport),
+    Songs(Vec<Song>),
+}
+
+impl PendingImport {
+    fn choices(&self) -> Vec<String> {
+        match self {
+            PendingImport::Midi(import) => import
+                .tracks
+                .iter()
+                .map(|track| format!("{} ({} notes)", track.name, track.note_count()))
+                .collect(),
+            PendingImport::Songs(songs) => songs
+                .iter()
+                .map(|song| {
+                    if song.composer.is_empty() {
+                        song.title.clone()
+                    } else {
+                        format!("{} ({})", song.title, song.composer)
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    fn default_choice(&self) -> usize {
+        match self {
+            // The track with the most notes is most likely the accompaniment
+            PendingImport::Midi(import) => (0..import.tracks.len())
+                .max_by_key(|&i| import.tracks[i].note_count())
+                .unwrap_or(0),
+            PendingImport::Songs(_) => 0,
+        }
+    }
+}
+
+/// Chord chart formats the progression can be exported as
+#[derive(Clone, Copy)]
+enum ChartFormat {
+    ChordPro,
+    MusicXml,
+}
+
+impl ChartFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ChartFormat::ChordPro => "cho",
+            ChartFormat::MusicXml => "musicxml",
+        }
+    }
+
+    fn write(self, song: &Song) -> String {
+        match self {
+            ChartFormat::ChordPro => write_chordpro(song),
+            ChartFormat::MusicXml => write_musicxml(song),
+        }
+    }
+}
+
 pub fn ProgressionSelector() -> Element {
     let mut app_state = use_context::<Signal<AppState>>();
-    let mut metronome_state = use_context::<Signal<MetronomeState>>();
-    let mut input_value = use_signal(String::new);
+    // A song loaded elsewhere, e.g. from the library, can be edited as a lead sheet
+    let mut input_value = use_signal(|| {
+        app_state
+            .read()
+            .progression_config
+            .song
+            .as_ref()
+            .map(write_lead_sheet)
+            .unwrap_or_default()
+    });
     let mut parse_error = use_signal(|| Option::<String>::None);
+    let mut metronome_state = use_context::<Signal<MetronomeState>>();
+    let mut pending = use_signal(|| Option::<PendingImport>::None);
+    let mut choice = use_signal(|| 0usize);
+
+    let mut apply_import = move |chords: Result<Vec<ImportedChord>>,
+                                 beats_per_bar: Option<u8>,
+                                 bpm: Option<u16>,
+                                 song: Option<Song>| {
+        match chords.and_then(|chords| to_progression(&chords)) {
+            Ok(chords) => {
+                // Take over the meter and tempo, the chords are counted in their bars
+                if let Some(beats) = beats_per_bar.filter(|b| BEATS_PER_BAR.contains(b)) {
+                    metronome_state.write().ticks_per_bar = beats;
+                    app_state.write().beats_per_bar = beats;
+                    let _ = AUDIO_CMD.0.try_send(AudioCommand::SetTimeSignature(beats));
+                }
+                if let Some(bpm) = bpm.filter(|bpm| (MIN_BPM..=MAX_BPM).contains(bpm)) {
+                    metronome_state.write().bpm = bpm;
+                    let _ = AUDIO_CMD.0.try_send(AudioCommand::SetBPM(bpm));
+                }
+                app_state.write().progression_config.chords = chords;
+                app_state.write().progression_config.song = song;
+                app_state.write().progression_config.loop_section = None;
+                app_state.write().restart();
+                parse_error.set(None);
+            }
+            Err(e) => parse_error.set(Some(format!("Import error: {:#}", e))),
+        }
+    };
+
+    let mut import_choice = move |import: &PendingImport, index: usize| match import {
+        PendingImport::Midi(midi) => {
+            apply_import(midi.chords(index), midi.beats_per_bar, midi.bpm, None)
+        }
+        PendingImport::Songs(songs) => {
+            if let Some(song) = songs.get(index) {
+                // The chart can be edited as a lead sheet from here on
+                input_value.set(write_lead_sheet(song));
+                let (beats, bpm) = (song.quarters_per_bar(), song.bpm);
+                apply_import(Ok(song.chords()), beats, bpm, Some(song.clone()));
+            }
+        }
+    };
+
+    // A single track or song is used right away, otherwise one is chosen first
+    let mut offer = move |import: Result<PendingImport>| match import {
+        Ok(import) if import.choices().len() == 1 => {
+            pending.set(None);
+            import_choice(&import, 0);
+        }
+        Ok(import) => {
+            choice.set(import.default_choice());
+            pending.set(Some(import));
+            parse_error.set(None);
+        }
+        Err(e) => parse_error.set(Some(format!("Import error: {:#}", e))),
+    };
 
     let mut parse_progression = move || {
         let input = input_value.read().clone();
+        if input.trim_start().starts_with("irealb") {
+            offer(parse_ireal(&input).map(PendingImport::Songs));
+            return;
+        }
+        if input.contains('|') {
+            let beats = metronome_state.read().ticks_per_bar;
+            offer(parse_lead_sheet(&input, beats).map(|song| PendingImport::Songs(vec![song])));
+            return;
+        }
         match ProgressionChord::from_string(input) {
             Ok(chords) => {
                 app_state.write().progression_config.chords = chords;
-                app_state.write().progression_config.reset();
-                metronome_state.write().bars_per_chord = app_state
-                    .read()
-                    .progression_config
-                    .get_bars_per_cycle_current();
+                app_state.write().progression_config.song = None;
+                app_state.write().progression_config.loop_section = None;
                 app_state.write().restart();
                 parse_error.set(None);
             }
@@ -32,6 +177,111 @@ pub fn ProgressionSelector() -> Element {
         }
     };
 
+    let open_file = move |_: MouseEvent| {
+        spawn(async move {
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .add_filter(
+                    "Chord charts",
+                    &[
+                        "mid", "midi", "html", "htm", "cho", "chordpro", "chopro", "crd", "pro",
+                        "musicxml", "xml", "mxl",
+                    ],
+                )
+                .pick_file()
+                .await
+            else {
+                return;
+            };
+            let extension = file
+                .path()
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let bytes = file.read().await;
+            offer(match extension.as_str() {
+                "mid" | "midi" => MidiImport::parse(&bytes).map(PendingImport::Midi),
+                "musicxml" | "xml" | "mxl" => {
+                    parse_musicxml(&bytes).map(|song| PendingImport::Songs(vec![song]))
+                }
+                "cho" | "chordpro" | "chopro" | "crd" | "pro" => {
+                    parse_chordpro(&String::from_utf8_lossy(&bytes))
+                        .map(|song| PendingImport::Songs(vec![song]))
+                }
+                // iReal Pro playlists exported as HTML
+                _ => parse_ireal(&String::from_utf8_lossy(&bytes)).map(PendingImport::Songs),
+            });
+        });
+    };
+
+    let export_chart = move |format: ChartFormat| {
+        // The imported song keeps its form unless the chords were edited since
+        let config = &app_state.read().progression_config;
+        let song = match config.unedited_song() {
+            Some(song) => song.clone(),
+            None => Song::from_progression(&config.chords, metronome_state.read().ticks_per_bar),
+        };
+        spawn(async move {
+            let extension = format.extension();
+            let name = match song.title.is_empty() {
+                true => "progression".to_string(),
+                false => song.title.clone(),
+            };
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .add_filter("Chord chart", &[extension])
+                .set_file_name(format!("{}.{}", name, extension))
+                .save_file()
+                .await
+            else {
+                return;
+            };
+            if let Err(e) = tokio::fs::write(file.path(), format.write(&song)).await {
+                parse_error.set(Some(format!("Export error: {}", e)));
+            }
+        });
+    };
+
+    let mut loop_section = move |section: Option<String>| {
+        let result = app_state
+            .write()
+            .progression_config
+            .set_loop_section(section);
+        match result {
+            Ok(()) => {
+                app_state.write().restart();
+                parse_error.set(None);
+            }
+            Err(e) => parse_error.set(Some(format!("{:#}", e))),
+        }
+    };
+
+    let choices = pending
+        .read()
+        .as_ref()
+        .map(|p| p.choices())
+        .unwrap_or_default();
+    let song = app_state
+        .read()
+        .progression_config
+        .song
+        .as_ref()
+        .filter(|song| !song.title.is_empty())
+        .map(|song| {
+            if song.key.is_empty() {
+                song.title.clone()
+            } else {
+                format!("{} in {}", song.title, song.key)
+            }
+        });
+
+    let sections = app_state
+        .read()
+        .progression_config
+        .song
+        .as_ref()
+        .map(|song| song.section_labels())
+        .unwrap_or_default();
+    let looped = app_state.read().progression_config.loop_section.clone();
+
     rsx! {
         div { class: "progression-container",
             // Input section
@@ -39,7 +289,7 @@ pub fn ProgressionSelector() -> Element {
                 input {
                     class: "progression-input",
                     r#type: "text",
-                    placeholder: "Fm7, G#dim",
+                    placeholder: "Fm7 G#dim, | Dm7 G7 | Cmaj7 | or irealb://…",
                     value: "{input_value}",
                     oninput: move |e| input_value.set(e.value()),
                     onkeydown: move |e| {
@@ -55,6 +305,75 @@ pub fn ProgressionSelector() -> Element {
                 }
             }
 
+            div { class: "settings-row",
+                if choices.is_empty() {
+                    span { class: "label-small",
+                        "Or paste an iReal Pro link, or import MIDI, ChordPro, MusicXML or an iReal Pro playlist"
+                    }
+                    button { class: "btn-parse-inline", onclick: open_file, "Import…" }
+                    if !app_state.read().progression_config.chords.is_empty() {
+                        button {
+                            class: "btn-parse-inline",
+                            onclick: move |_| export_chart(ChartFormat::ChordPro),
+                            "Export ChordPro…"
+                        }
+                        button {
+                            class: "btn-parse-inline",
+                            onclick: move |_| export_chart(ChartFormat::MusicXml),
+                            "Export MusicXML…"
+                        }
+                    }
+                } else {
+                    select {
+                        class: "select-styled",
+                        onchange: move |e| choice.set(e.value().parse().unwrap_or(0)),
+                        for (i, label) in choices.into_iter().enumerate() {
+                            option { key: "{i}", value: "{i}", selected: i == choice(), "{label}" }
+                        }
+                    }
+                    button {
+                        class: "btn-parse-inline",
+                        onclick: move |_| {
+                            if let Some(import) = pending.take() {
+                                import_choice(&import, choice());
+                            }
+                        },
+                        "Import"
+                    }
+                    button {
+                        class: "btn-parse-inline",
+                        onclick: move |_| pending.set(None),
+                        "Cancel"
+                    }
+                }
+            }
+
+            if let Some(song) = song {
+                span { class: "label-small", "{song}" }
+            }
+
+            if sections.len() > 1 {
+                div { class: "settings-row",
+                    span { class: "label-small", "Loop" }
+                    select {
+                        class: "select-styled",
+                        onchange: move |e| {
+                            let section = e.value();
+                            loop_section((!section.is_empty()).then_some(section));
+                        },
+                        option { value: "", selected: looped.is_none(), "Whole form" }
+                        for label in sections {
+                            option {
+                                key: "{label}",
+                                value: "{label}",
+                                selected: looped.as_deref() == Some(label.as_str()),
+                                "Section {label} only"
+                            }
+                        }
+                    }
+                }
+            }
+
             // Error message
             if let Some(error) = parse_error.read().as_ref() {
                 div { class: "parse-error", "{error}" }
@@ -65,18 +384,28 @@ pub fn ProgressionSelector() -> Element {
                 div { class: "progression-chords",
                     for (index, progression_chord) in app_state.read().progression_config.chords.iter().enumerate() {
                         div { class: "chord-card",
-                            class: if index == app_state.read().progression_config.current_chord_index { "active" } else { "" },
+                            class: if Some(index) == app_state.read().current_progression_index() { "active" } else { "" },
                             div { class: "chord-name", "{progression_chord.chord.origin}" }
                             div { class: "bars-control",
                                 button {
                                     class: "btn-icon btn-small",
-                                    onclick: move |_| app_state.write().progression_config.decrements_bars(index),
+                                    onclick: move |_| {
+                                        app_state.write().progression_config.decrements_bars(index);
+                                        app_state.write().reschedule_upcoming();
+                                    },
                                     "−"
                                 }
-                                span { class: "bars-value", "{progression_chord.bars} b" }
+                                if let Some(beats) = progression_chord.beats {
+                                    span { class: "bars-value", "{beats} ♩" }
+                                } else {
+                                    span { class: "bars-value", "{progression_chord.bars} b" }
+                                }
                                 button {
                                     class: "btn-icon btn-small",
-                                    onclick: move |_| app_state.write().progression_config.increments_bars(index),
+                                    onclick: move |_| {
+                                        app_state.write().progression_config.increments_bars(index);
+                                        app_state.write().reschedule_upcoming();
+                                    },
                                     "+"
                                 }
                             }
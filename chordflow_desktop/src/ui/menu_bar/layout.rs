@@ -1,10 +1,24 @@
 use dioxus::prelude::*;
 
-use crate::{components::settings_panel::SettingsPanel, ui::menu_bar::mode_selector::ModeSelector};
+use crate::{
+    components::{
+        library::LibraryPanel,
+        presets::{PresetsPanel, RoutineStatus},
+        settings_panel::SettingsPanel,
+        stats::StatsPanel,
+    },
+    presets::RoutineRun,
+    ui::menu_bar::mode_selector::ModeSelector,
+};
 
 #[component]
 pub fn MenuBar() -> Element {
     let mut show_settings = use_signal(|| false);
+    let mut show_library = use_signal(|| false);
+    let mut show_presets = use_signal(|| false);
+    let mut show_stats = use_signal(|| false);
+    // The routine being played, started in the presets panel and shown in the menu bar
+    use_context_provider(|| Signal::new(None::<RoutineRun>));
 
     rsx! {
         div { class: "menu-bar",
@@ -12,6 +26,22 @@ pub fn MenuBar() -> Element {
                 ModeSelector {}
             }
             div { class: "menu-right",
+                RoutineStatus {}
+                button {
+                    class: "settings-button",
+                    onclick: move |_| show_presets.set(true),
+                    "🎯 Presets"
+                }
+                button {
+                    class: "settings-button",
+                    onclick: move |_| show_stats.set(true),
+                    "📈 Stats"
+                }
+                button {
+                    class: "settings-button",
+                    onclick: move |_| show_library.set(true),
+                    "📚 Library"
+                }
                 button {
                     class: "settings-button",
                     onclick: move |_| show_settings.set(true),
@@ -21,5 +51,8 @@ pub fn MenuBar() -> Element {
         }
 
         SettingsPanel { show: show_settings }
+        LibraryPanel { show: show_library }
+        PresetsPanel { show: show_presets }
+        StatsPanel { show: show_stats }
     }
 }
@@ -24,6 +24,8 @@ pub fn ModeSelector() -> Element {
                                             return;
                                         }
                                         app_state.write().selected_mode = mode;
+                                        app_state.write().preset = None;
+                                        app_state.write().restart();
                                     },
                                     "{mode}"
                                 }
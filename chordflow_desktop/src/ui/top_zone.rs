@@ -5,3 +5,4 @@ mod bpm_control;
 pub mod layout;
 mod play_control;
 pub mod subdivision_selector;
+pub mod time_signature_selector;
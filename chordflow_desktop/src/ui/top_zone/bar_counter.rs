@@ -2,12 +2,27 @@
 
 use dioxus::prelude::*;
 
-use crate::ui::app::MetronomeState;
+use crate::ui::app::{AppState, MetronomeState};
 
 pub fn BarCounter() -> Element {
     let metronome_state: Signal<MetronomeState> = use_context();
+    let app_state: Signal<AppState> = use_context();
     let state = metronome_state.read();
+    // Playing a song, the bar within its form is shown as well
+    let app = app_state.read();
+    let position = app.current_progression_index().and_then(|index| {
+        app.progression_config
+            .form_position(index, state.current_bar)
+    });
     rsx! {
-        div { class: "bar-counter", "Bar {state.current_bar}/{state.bars_per_chord}" }
+        div { class: "bar-counter",
+            if let Some(position) = position {
+                if let Some(section) = position.section {
+                    span { class: "form-section", "{section}" }
+                }
+                span { "Form {position.bar}/{position.bars} · " }
+            }
+            "Bar {state.current_bar}/{state.bars_per_chord}"
+        }
     }
 }
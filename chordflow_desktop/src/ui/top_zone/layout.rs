@@ -4,8 +4,8 @@ use dioxus::prelude::*;
 
 use crate::ui::top_zone::{
     bar_counter::BarCounter, beat_fraction::BeatFraction, beat_viz::BeatViz,
-    bpm_control::BeatControl, play_control::PlayControl,
-    subdivision_selector::SubdivisionSelector,
+    bpm_control::BeatControl, play_control::PlayControl, subdivision_selector::SubdivisionSelector,
+    time_signature_selector::TimeSignatureSelector,
 };
 
 pub fn TopZone() -> Element {
@@ -15,6 +15,7 @@ pub fn TopZone() -> Element {
                 BarCounter {}
                 BeatViz {}
                 BeatFraction {}
+                TimeSignatureSelector {}
                 SubdivisionSelector {}
                 BeatControl {}
                 PlayControl {}
@@ -1,10 +1,11 @@
 #![allow(non_snake_case)]
 
 use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{ui::app::MetronomeState, AudioCommand, AUDIO_CMD};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum Subdivision {
     #[default]
     None,
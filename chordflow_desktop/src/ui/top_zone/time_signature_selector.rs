@@ -0,0 +1,41 @@
+#![allow(non_snake_case)]
+
+use dioxus::prelude::*;
+
+use crate::{
+    ui::app::{AppState, MetronomeState},
+    AudioCommand, AUDIO_CMD,
+};
+
+/// Beats per bar that can be selected, all counted in quarter notes
+pub const BEATS_PER_BAR: [u8; 6] = [2, 3, 4, 5, 6, 7];
+
+pub fn TimeSignatureSelector() -> Element {
+    let mut metronome_state: Signal<MetronomeState> = use_context();
+    let mut app_state: Signal<AppState> = use_context();
+
+    rsx! {
+        div { class: "subdivision-control",
+            select {
+                class: "subdivision-select",
+                value: "{metronome_state.read().ticks_per_bar}",
+                onchange: move |e| {
+                    let beats = e.value().parse::<u8>().unwrap_or(4);
+                    // Reset UI state when changing the time signature
+                    metronome_state.write().current_bar = 1;
+                    metronome_state.write().current_tick = 0;
+                    metronome_state.write().ticks_per_bar = beats;
+                    let _ = AUDIO_CMD.0.try_send(AudioCommand::SetTimeSignature(beats));
+                    app_state.write().set_beats_per_bar(beats);
+                },
+                for beats in BEATS_PER_BAR {
+                    option {
+                        value: "{beats}",
+                        selected: beats == metronome_state.read().ticks_per_bar,
+                        "{beats}/4"
+                    }
+                }
+            }
+        }
+    }
+}